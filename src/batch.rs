@@ -0,0 +1,391 @@
+/* src/batch.rs */
+
+//! Two-phase resolution with [`BatchResolver`]s.
+//!
+//! [`resolve_batched`] first walks the tree and collects every string as a
+//! [`Candidate`], then hands them to the resolver in batches of
+//! [`Config::batch_size`], and finally walks the tree again to put the
+//! results in place.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future::Future;
+
+use crate::deadline::Guard;
+use crate::fixpoint::Fixpoint;
+use crate::{Config, Error, Path, PathSegment, ResolvableValue, Resolved, ValueKind};
+
+/// A string collected for a [`BatchResolver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+	/// Location of the string. Map keys in the path are the keys as written,
+	/// before any [`Config::resolve_keys`] resolution; a key itself has the
+	/// path of its map.
+	pub path: Path,
+	/// The string to resolve.
+	pub input: String,
+}
+
+/// Trait for resolvers looking up many strings in one call.
+///
+/// Used by [`resolve_batched`] for backends with bulk lookups. The returned
+/// results must match the batch one-to-one and in order; a result count
+/// differing from the batch size fails with [`Error::BatchMismatch`].
+///
+/// # Example
+///
+/// ```rust
+/// use serde_resolve::{BatchResolver, Candidate, Resolved};
+///
+/// struct Upper;
+///
+/// impl BatchResolver for Upper {
+///     type Error = std::convert::Infallible;
+///
+///     async fn resolve_batch(&self, batch: &[Candidate]) -> Result<Vec<Resolved>, Self::Error> {
+///         Ok(batch
+///             .iter()
+///             .map(|c| Resolved::changed(c.input.to_uppercase()))
+///             .collect())
+///     }
+/// }
+/// ```
+pub trait BatchResolver: Send + Sync {
+	/// Error type returned by this resolver.
+	type Error: Send;
+
+	/// Resolve a batch of strings, returning one result per candidate.
+	fn resolve_batch(
+		&self,
+		batch: &[Candidate],
+	) -> impl Future<Output = Result<Vec<Resolved>, Self::Error>> + Send;
+}
+
+impl<F, Fut, E> BatchResolver for F
+where
+	F: Fn(&[Candidate]) -> Fut + Send + Sync,
+	Fut: Future<Output = Result<Vec<Resolved>, E>> + Send,
+	E: Send,
+{
+	type Error = E;
+
+	#[inline]
+	fn resolve_batch(
+		&self,
+		batch: &[Candidate],
+	) -> impl Future<Output = Result<Vec<Resolved>, Self::Error>> + Send {
+		self(batch)
+	}
+}
+
+/// Resolve all strings in any [`ResolvableValue`] with a [`BatchResolver`].
+///
+/// Strings are collected in traversal order, passed to the resolver in
+/// batches of at most [`Config::batch_size`] and then written back. With
+/// [`Config::fixpoint`], changed strings are batched again until they
+/// settle. [`Config::string_timeout`](Config) applies to each batch call.
+///
+/// Batching needs a whole value tree, so it is offered for [`ResolvableValue`]
+/// types and the `json`, `yaml`, `toml`, `msgpack`, `cbor`, `ron`, `json5` and
+/// `text` modules. The comment-preserving `json5::resolve_preserving`, the flat
+/// file modules and `json::stream` rewrite text as they scan it and take a
+/// [`Resolver`](crate::Resolver) instead.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The resolver returns the wrong number of results
+/// - The depth limit is exceeded
+pub async fn resolve_batched<V, B>(
+	value: V,
+	resolver: &B,
+	config: &Config,
+) -> Result<V, Error<B::Error>>
+where
+	V: ResolvableValue,
+	B: BatchResolver + ?Sized,
+{
+	let mut candidates = Vec::new();
+	let value = walk(value, config, 0, &mut Path::root(), &mut |path, s| {
+		candidates.push(Candidate {
+			path: path.clone(),
+			input: s.clone(),
+		});
+		s
+	})?;
+
+	#[cfg(feature = "tracing")]
+	tracing::debug!(strings = candidates.len(), "collected strings for batching");
+
+	let mut guard = Guard::new(config)?;
+	let results = resolve_candidates(resolver, &candidates, config, &mut guard).await?;
+
+	let mut results = results.into_iter();
+	walk(value, config, 0, &mut Path::root(), &mut |_, s| {
+		let Some(resolved) = results.next() else {
+			return s;
+		};
+		#[cfg(feature = "tracing")]
+		crate::trace_resolved(&s, &resolved);
+		resolved.into_changed().unwrap_or(s)
+	})
+}
+
+/// Resolve `candidates`, re-resolving changed ones until they settle if
+/// enabled.
+async fn resolve_candidates<B>(
+	resolver: &B,
+	candidates: &[Candidate],
+	config: &Config,
+	guard: &mut Guard,
+) -> Result<Vec<Resolved>, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	let mut results = call(resolver, candidates, config, guard).await?;
+	let Some(limit) = config.fixpoint else {
+		return Ok(results);
+	};
+
+	let mut active = Vec::new();
+	let mut latest = Vec::new();
+	for (i, (candidate, resolved)) in candidates.iter().zip(&mut results).enumerate() {
+		if resolved.is_changed() {
+			active.push((i, Fixpoint::new(&candidate.input, limit)));
+			latest.push(core::mem::replace(resolved, Resolved::Unchanged));
+		}
+	}
+
+	while !active.is_empty() {
+		let mut next = Vec::new();
+		let mut pending = Vec::new();
+		for ((i, mut fixpoint), resolved) in active.into_iter().zip(latest) {
+			if let Some(input) = fixpoint.step(resolved.into())? {
+				next.push(Candidate {
+					path: candidates[i].path.clone(),
+					input: input.into(),
+				});
+				pending.push((i, fixpoint));
+			} else {
+				results[i] = fixpoint.finish().resolved;
+			}
+		}
+		latest = call(resolver, &next, config, guard).await?;
+		active = pending;
+	}
+	Ok(results)
+}
+
+/// Pass `candidates` to the resolver in batches.
+async fn call<B>(
+	resolver: &B,
+	candidates: &[Candidate],
+	config: &Config,
+	guard: &mut Guard,
+) -> Result<Vec<Resolved>, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	let mut results = Vec::with_capacity(candidates.len());
+	for batch in candidates.chunks(config.batch_size.max(1)) {
+		#[cfg(feature = "tracing")]
+		tracing::trace!(size = batch.len(), path = %batch[0].path, "resolving batch");
+
+		let resolved = guard
+			.run(&batch[0].path, async {
+				resolver.resolve_batch(batch).await.map_err(Error::resolver)
+			})
+			.await?;
+		if resolved.len() != batch.len() {
+			return Err(Error::BatchMismatch {
+				expected: batch.len(),
+				returned: resolved.len(),
+			});
+		}
+		results.extend(resolved);
+	}
+	Ok(results)
+}
+
+/// Rebuild `value`, replacing each string with `visit(path, string)`.
+///
+/// Visits strings, and keys when [`Config::resolve_keys`] is set, in the same
+/// order as the traversal behind [`crate::resolve`].
+fn walk<V, E, F>(
+	value: V,
+	config: &Config,
+	depth: usize,
+	path: &mut Path,
+	visit: &mut F,
+) -> Result<V, Error<E>>
+where
+	V: ResolvableValue,
+	F: FnMut(&Path, String) -> String,
+{
+	if depth >= config.max_depth {
+		return Err(Error::depth_exceeded(config.max_depth));
+	}
+
+	match value.into_kind() {
+		ValueKind::String(s) => Ok(V::from_string(visit(path, s))),
+		ValueKind::Array(arr) => {
+			let mut result = Vec::with_capacity(arr.len());
+			for item in arr {
+				path.push(PathSegment::Index(result.len()));
+				result.push(walk(item, config, depth + 1, path, visit)?);
+				path.pop();
+			}
+			Ok(V::from_array(result))
+		}
+		ValueKind::Map(map) => {
+			let mut result = Vec::with_capacity(map.len());
+			for (key, val) in map {
+				let segment = PathSegment::Key(V::key_to_string(&key));
+				let key = match V::key_into_value(key) {
+					Ok(key_value) if config.resolve_keys => {
+						V::key_from_value(walk(key_value, config, depth + 1, path, visit)?)
+					}
+					Ok(key_value) => V::key_from_value(key_value),
+					Err(key) => key,
+				};
+				path.push(segment);
+				let val = walk(val, config, depth + 1, path, visit)?;
+				result.push((key, val));
+				path.pop();
+			}
+			Ok(V::from_map(result))
+		}
+		ValueKind::Tagged(tag, inner) => {
+			let inner = walk(inner, config, depth + 1, path, visit)?;
+			Ok(V::from_tagged(tag, inner))
+		}
+		ValueKind::Other(other) => Ok(other),
+	}
+}
+
+#[cfg(all(test, feature = "json", feature = "std"))]
+mod tests {
+	use super::*;
+	use alloc::string::ToString;
+	use core::convert::Infallible;
+	use serde_json::json;
+	use std::sync::Mutex;
+
+	/// Expands `${NAME}` one variable per pass, recording every batch.
+	#[derive(Debug, Default)]
+	struct Kv {
+		batches: Mutex<Vec<Vec<Candidate>>>,
+	}
+
+	impl Kv {
+		fn sizes(&self) -> Vec<usize> {
+			self.batches.lock().unwrap().iter().map(Vec::len).collect()
+		}
+	}
+
+	impl BatchResolver for Kv {
+		type Error = Infallible;
+
+		async fn resolve_batch(&self, batch: &[Candidate]) -> Result<Vec<Resolved>, Infallible> {
+			self.batches.lock().unwrap().push(batch.to_vec());
+			Ok(
+				batch
+					.iter()
+					.map(|c| match c.input.as_str() {
+						"${URL}" => Resolved::changed("${HOST}:80"),
+						"${HOST}:80" => Resolved::changed("db:80"),
+						"${PASS}" => Resolved::secret("hunter2"),
+						"${KEY}" => Resolved::changed("key"),
+						_ => Resolved::Unchanged,
+					})
+					.collect(),
+			)
+		}
+	}
+
+	fn input() -> serde_json::Value {
+		json!({
+			"${KEY}": "${PASS}",
+			"list": ["${URL}", 1, "plain"],
+			"nested": { "a": "${PASS}" }
+		})
+	}
+
+	#[tokio::test]
+	async fn test_batches() {
+		let kv = Kv::default();
+		let config = Config::default().batch_size(2);
+		let output = resolve_batched(input(), &kv, &config).await.unwrap();
+
+		assert_eq!(
+			output,
+			json!({
+				"${KEY}": "hunter2",
+				"list": ["${HOST}:80", 1, "plain"],
+				"nested": { "a": "hunter2" }
+			})
+		);
+		assert_eq!(kv.sizes(), [2, 2]);
+		let batches = kv.batches.lock().unwrap();
+		let paths: Vec<_> = batches
+			.iter()
+			.flatten()
+			.map(|c| c.path.to_string())
+			.collect();
+		assert_eq!(paths, ["${KEY}", "list[0]", "list[2]", "nested.a"]);
+	}
+
+	#[tokio::test]
+	async fn test_keys_and_fixpoint() {
+		let kv = Kv::default();
+		let config = Config::default().resolve_keys(true).fixpoint(5);
+		let output = resolve_batched(input(), &kv, &config).await.unwrap();
+
+		assert_eq!(
+			output,
+			json!({
+				"key": "hunter2",
+				"list": ["db:80", 1, "plain"],
+				"nested": { "a": "hunter2" }
+			})
+		);
+		// All strings and keys, then a round for each rewrite of the changed ones
+		assert_eq!(kv.sizes(), [8, 4, 1]);
+		let batches = kv.batches.lock().unwrap();
+		assert_eq!(batches[0][0].path, Path::root());
+		assert_eq!(batches[1][0].input, "key");
+		assert_eq!(batches[2][0].input, "db:80");
+		assert_eq!(batches[2][0].path.to_string(), "list[0]");
+	}
+
+	#[tokio::test]
+	async fn test_mismatch() {
+		let short = |batch: &[Candidate]| {
+			let len = batch.len();
+			async move { Ok::<_, Infallible>(alloc::vec![Resolved::Unchanged; len - 1]) }
+		};
+		let err = crate::json::resolve_batched(input(), &short, &Config::default())
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err,
+			Error::BatchMismatch {
+				expected: 4,
+				returned: 3
+			}
+		));
+		assert_eq!(
+			err.to_string(),
+			"batch resolver returned 3 results for 4 strings"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let kv = Kv::default();
+		let result = resolve_batched(input(), &kv, &Config::default().max_depth(2)).await;
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 2 })));
+		assert!(kv.sizes().is_empty());
+	}
+}
@@ -0,0 +1,272 @@
+/* src/cbor.rs */
+
+//! CBOR support via `ciborium`.
+//!
+//! This module is available with the `cbor` feature and supports `no_std` environments.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use ciborium::Value;
+
+use crate::{BatchResolver, Config, Error, ResolvableValue, Resolver, ValueKind};
+
+/// Resolve all text strings in a CBOR [`Value`].
+///
+/// Tagged values are resolved recursively with their tag preserved. Byte
+/// strings are left untouched. When [`Config::resolve_keys`] is set, only text
+/// map keys are passed to the resolver.
+///
+/// See [`crate::json::resolve`] for detailed documentation.
+pub async fn resolve<R>(
+	value: Value,
+	resolver: &R,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	crate::resolve(value, resolver, config).await
+}
+
+/// Resolve all strings in a CBOR [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+impl ResolvableValue for Value {
+	type Key = Self;
+	type Tag = u64;
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::Text(s) => ValueKind::String(s),
+			Self::Array(arr) => ValueKind::Array(arr),
+			Self::Map(map) => ValueKind::Map(map),
+			Self::Tag(tag, inner) => ValueKind::Tagged(tag, *inner),
+			other => ValueKind::Other(other),
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::Text(s)
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Array(items)
+	}
+
+	#[inline]
+	fn from_map(entries: Vec<(Self, Self)>) -> Self {
+		Self::Map(entries)
+	}
+
+	fn from_tagged(tag: u64, value: Self) -> Self {
+		Self::Tag(tag, Box::new(value))
+	}
+
+	// Only text keys are resolved
+	fn key_into_value(key: Self) -> Result<Self, Self> {
+		match key {
+			Self::Text(_) => Ok(key),
+			other => Err(other),
+		}
+	}
+
+	#[inline]
+	fn key_from_value(value: Self) -> Self {
+		value
+	}
+
+	fn key_to_string(key: &Self) -> String {
+		match key {
+			Self::Text(k) => k.clone(),
+			other => alloc::format!("{other:?}"),
+		}
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::Integer(_) => "integer",
+			Self::Bytes(_) => "bytes",
+			Self::Float(_) => "float",
+			Self::Text(_) => "text",
+			Self::Bool(_) => "bool",
+			Self::Null => "null",
+			Self::Tag(..) => "tag",
+			Self::Array(_) => "array",
+			Self::Map(_) => "map",
+			_ => "unknown",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use alloc::borrow::ToOwned;
+	use alloc::vec;
+	use core::convert::Infallible;
+
+	#[tokio::test]
+	async fn test_resolve_text() {
+		let input = Value::Text("hello".into());
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, Value::Text("HELLO".into()));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_map() {
+		let input = Value::Map(vec![
+			(Value::Text("key".into()), Value::Text("value".into())),
+			(Value::Integer(1.into()), Value::Bytes(vec![1, 2])),
+		]);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		let expected = Value::Map(vec![
+			(Value::Text("key".into()), Value::Text("VALUE".into())),
+			(Value::Integer(1.into()), Value::Bytes(vec![1, 2])),
+		]);
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_text_keys() {
+		let input = Value::Map(vec![
+			(Value::Text("key".into()), Value::Text("value".into())),
+			(Value::Integer(1.into()), Value::Text("one".into())),
+		]);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		let expected = Value::Map(vec![
+			(Value::Text("KEY".into()), Value::Text("VALUE".into())),
+			(Value::Integer(1.into()), Value::Text("ONE".into())),
+		]);
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_tagged() {
+		// Tag 32: URI
+		let input = Value::Tag(32, Box::new(Value::Text("http://{{host}}".into())));
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.replace("{{host}}", "example.com"))) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			Value::Tag(32, Box::new(Value::Text("http://example.com".into())))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let mut value = Value::Text("deep".into());
+		for _ in 0..32 {
+			value = Value::Array(vec![value]);
+		}
+
+		let result = resolve(
+			value,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s)) }
+			},
+			&Config::default().max_depth(32),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 32 })));
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let input = Value::Text("hello".into());
+		let result = resolve(
+			input,
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::Resolver(MyError))));
+	}
+
+	#[tokio::test]
+	async fn test_non_text_unchanged() {
+		let input = Value::Array(vec![
+			Value::Null,
+			Value::Bool(true),
+			Value::Float(1.5),
+			Value::Bytes(vec![0xff]),
+		]);
+		let output = resolve(
+			input.clone(),
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(output, input);
+	}
+}
@@ -0,0 +1,1311 @@
+/* src/de.rs */
+
+//! Resolution during deserialization.
+//!
+//! This module supports `no_std` environments.
+//!
+//! [`ResolvingDeserializer`] wraps any serde [`Deserializer`] and passes every
+//! string through a [`SyncResolver`] before the target type sees it, so a
+//! config type can be loaded straight from JSON, YAML or TOML without an
+//! intermediate value tree.
+//!
+//! Map keys are resolved when [`Config::resolve_keys`] is set. Enum variant
+//! names and ignored values are never resolved. Resolver errors and exceeded
+//! depth limits are reported through the wrapped deserializer's error type via
+//! [`de::Error::custom`].
+//!
+//! With [`Config::coercion`] enabled, strings are parsed into the number,
+//! `bool`, unit or `Option` the target type asks for, whether or not the
+//! resolver changed them; see [`Coercion`] for the accepted literals. A
+//! null-like string only becomes `None` if the `Option`'s inner type rejects
+//! it, so an `Option<String>` keeps the text. That check needs the
+//! `Option`'s own visitor: wrap path-tracking adapters such as
+//! `serde_path_to_error` inside the [`ResolvingDeserializer`], not around it. Coercion relies on
+//! [`Deserializer::deserialize_any`] for those targets, so it only works
+//! with self-describing formats such as JSON, YAML or TOML, not with formats
+//! like bincode or postcard.
+//!
+//! # Example
+//!
+//! ```rust
+//! # #[cfg(feature = "json")] {
+//! use serde::Deserialize;
+//! use serde_resolve::{de, Config, Resolved};
+//!
+//! #[derive(Deserialize)]
+//! struct AppConfig {
+//!     host: String,
+//!     ports: Vec<u16>,
+//! }
+//!
+//! let mut json = serde_json::Deserializer::from_str(r#"{"host": "${HOST}", "ports": [80]}"#);
+//! let config: AppConfig = de::deserialize(
+//!     &mut json,
+//!     &|s: &str| -> Result<Resolved, std::convert::Infallible> {
+//!         Ok(match s {
+//!             "${HOST}" => Resolved::changed("example.com"),
+//!             _ => Resolved::unchanged(),
+//!         })
+//!     },
+//!     &Config::default(),
+//! ).unwrap();
+//!
+//! assert_eq!(config.host, "example.com");
+//! # }
+//! ```
+
+use alloc::string::String;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::value::{
+	BorrowedBytesDeserializer, BorrowedStrDeserializer, BytesDeserializer, EnumAccessDeserializer,
+	MapAccessDeserializer, SeqAccessDeserializer, StrDeserializer, StringDeserializer,
+};
+use serde::de::{self, DeserializeSeed, Deserializer, IntoDeserializer, Visitor};
+
+use crate::{Coercion, Config, Error, Resolved, SyncResolver};
+
+/// Deserialize a `T` from `deserializer`, resolving strings on the way.
+///
+/// # Errors
+///
+/// Returns the deserializer's error if deserialization fails, the resolver
+/// returns an error or the depth limit is exceeded.
+pub fn deserialize<'de, T, D, R>(
+	deserializer: D,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, D::Error>
+where
+	T: de::Deserialize<'de>,
+	D: Deserializer<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	T::deserialize(ResolvingDeserializer::new(deserializer, resolver, config))
+}
+
+/// A [`Deserializer`] that resolves strings produced by another deserializer.
+///
+/// With [`Config::coercion`] enabled, scalar targets are read through the
+/// inner deserializer's [`deserialize_any`](Deserializer::deserialize_any),
+/// which formats that do not describe their own types reject. Leave coercion
+/// off when wrapping such a deserializer.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug)]
+pub struct ResolvingDeserializer<'a, D, R> {
+	inner: D,
+	state: State<'a, R>,
+}
+
+impl<'a, D, R> ResolvingDeserializer<'a, D, R> {
+	/// Wrap `inner`, resolving its strings with `resolver`.
+	#[inline]
+	#[must_use]
+	pub const fn new(inner: D, resolver: &'a R, config: &'a Config) -> Self {
+		Self {
+			inner,
+			state: State {
+				resolver,
+				config,
+				depth: 0,
+			},
+		}
+	}
+
+	/// Unwrap the inner deserializer.
+	#[inline]
+	pub fn into_inner(self) -> D {
+		self.inner
+	}
+}
+
+/// Resolver leaving every string unchanged, for coercing resolved values.
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+pub(crate) struct Unchanged;
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+impl SyncResolver for Unchanged {
+	type Error = core::convert::Infallible;
+
+	#[inline]
+	fn resolve(&self, _input: &str) -> Result<Resolved, Self::Error> {
+		Ok(Resolved::Unchanged)
+	}
+}
+
+/// Shared resolution state, copied into every nested wrapper.
+#[derive(Debug)]
+struct State<'a, R> {
+	resolver: &'a R,
+	config: &'a Config,
+	depth: usize,
+}
+
+impl<R> Clone for State<'_, R> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<R> Copy for State<'_, R> {}
+
+impl<R> State<'_, R>
+where
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	const fn nested(self) -> Self {
+		Self {
+			depth: self.depth + 1,
+			..self
+		}
+	}
+
+	fn check_depth<E: de::Error>(&self) -> Result<(), E> {
+		if self.depth >= self.config.max_depth {
+			return Err(E::custom(Error::<R::Error>::depth_exceeded(
+				self.config.max_depth,
+			)));
+		}
+		Ok(())
+	}
+
+	/// The coercion target for a deserialize call, or [`Target::Any`] when
+	/// coercion is off.
+	fn target(&self, target: Target) -> Target {
+		match self.config.coercion {
+			Coercion::Off => Target::Any,
+			Coercion::Strict | Coercion::Lenient => target,
+		}
+	}
+
+	fn resolve<E: de::Error>(&self, input: &str) -> Result<Resolved, E> {
+		#[cfg(feature = "tracing")]
+		tracing::trace!(depth = self.depth, value = %input, "resolving");
+
+		let resolved =
+			crate::fixpoint::resolve_str_sync(self.resolver, input, self.config).map_err(E::custom)?;
+		#[cfg(feature = "tracing")]
+		crate::trace_resolved(input, &resolved);
+		Ok(resolved)
+	}
+}
+
+/// The kind of value a target type asked the deserializer for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+	Any,
+	Bool,
+	Signed,
+	Unsigned,
+	Float,
+	Unit,
+	Option,
+}
+
+impl Target {
+	const fn is_scalar(self) -> bool {
+		matches!(
+			self,
+			Self::Bool | Self::Signed | Self::Unsigned | Self::Float | Self::Unit
+		)
+	}
+}
+
+/// A scalar parsed from a string.
+pub(crate) enum Scalar {
+	Null,
+	Bool(bool),
+	I64(i64),
+	I128(i128),
+	U64(u64),
+	U128(u128),
+	F64(f64),
+}
+
+impl Scalar {
+	/// Parse `input` as the scalar `target` expects.
+	pub(crate) fn parse(input: &str, target: Target, coercion: Coercion) -> Option<Self> {
+		let input = match coercion {
+			Coercion::Off => return None,
+			Coercion::Strict => input,
+			Coercion::Lenient => input.trim(),
+		};
+		let lenient = coercion == Coercion::Lenient;
+		match target {
+			Target::Any => None,
+			Target::Bool => match input {
+				"true" => Some(Self::Bool(true)),
+				"false" => Some(Self::Bool(false)),
+				_ if !lenient => None,
+				_ if ["true", "yes", "on", "1"]
+					.iter()
+					.any(|s| input.eq_ignore_ascii_case(s)) =>
+				{
+					Some(Self::Bool(true))
+				}
+				_ if ["false", "no", "off", "0"]
+					.iter()
+					.any(|s| input.eq_ignore_ascii_case(s)) =>
+				{
+					Some(Self::Bool(false))
+				}
+				_ => None,
+			},
+			Target::Signed => input
+				.parse()
+				.map(Self::I64)
+				.or_else(|_| input.parse().map(Self::I128))
+				.ok(),
+			Target::Unsigned => input
+				.parse()
+				.map(Self::U64)
+				.or_else(|_| input.parse().map(Self::U128))
+				.ok(),
+			Target::Float => input.parse().map(Self::F64).ok(),
+			Target::Unit | Target::Option => {
+				let null = input == "null"
+					|| (lenient && (input.is_empty() || input == "~" || input.eq_ignore_ascii_case("null")));
+				null.then_some(Self::Null)
+			}
+		}
+	}
+
+	fn visit<'de, V, E>(self, visitor: V) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+		E: de::Error,
+	{
+		match self {
+			Self::Null => visitor.visit_unit(),
+			Self::Bool(v) => visitor.visit_bool(v),
+			Self::I64(v) => visitor.visit_i64(v),
+			Self::I128(v) => visitor.visit_i128(v),
+			Self::U64(v) => visitor.visit_u64(v),
+			Self::U128(v) => visitor.visit_u128(v),
+			Self::F64(v) => visitor.visit_f64(v),
+		}
+	}
+}
+
+/// A string after resolution, keeping its original borrow where possible.
+enum Text<'s, 'de> {
+	Ref(&'s str),
+	Borrowed(&'de str),
+	Owned(String),
+}
+
+impl<'de> Text<'_, 'de> {
+	#[allow(clippy::match_same_arms)] // the borrows have different lifetimes
+	fn as_str(&self) -> &str {
+		match self {
+			Self::Ref(s) => s,
+			Self::Borrowed(s) => s,
+			Self::Owned(s) => s,
+		}
+	}
+
+	fn visit<V, E>(self, visitor: V) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+		E: de::Error,
+	{
+		match self {
+			Self::Ref(s) => visitor.visit_str(s),
+			Self::Borrowed(s) => visitor.visit_borrowed_str(s),
+			Self::Owned(s) => visitor.visit_string(s),
+		}
+	}
+}
+
+macro_rules! forward_deserialize {
+	($($method:ident($($arg:ident: $ty:ty),*) => $target:ident),* $(,)?) => {
+		$(
+			fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+			where
+				V: Visitor<'de>,
+			{
+				self.state.check_depth()?;
+				let target = self.state.target(Target::$target);
+				let visitor = ResolvingVisitor {
+					inner: visitor,
+					state: self.state,
+					target,
+				};
+				// Scalars may arrive as strings, which only `deserialize_any` yields
+				if target.is_scalar() {
+					self.inner.deserialize_any(visitor)
+				} else {
+					self.inner.$method($($arg,)* visitor)
+				}
+			}
+		)*
+	};
+}
+
+impl<'de, D, R> Deserializer<'de> for ResolvingDeserializer<'_, D, R>
+where
+	D: Deserializer<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Error = D::Error;
+
+	forward_deserialize!(
+		deserialize_any() => Any,
+		deserialize_bool() => Bool,
+		deserialize_i8() => Signed,
+		deserialize_i16() => Signed,
+		deserialize_i32() => Signed,
+		deserialize_i64() => Signed,
+		deserialize_i128() => Signed,
+		deserialize_u8() => Unsigned,
+		deserialize_u16() => Unsigned,
+		deserialize_u32() => Unsigned,
+		deserialize_u64() => Unsigned,
+		deserialize_u128() => Unsigned,
+		deserialize_f32() => Float,
+		deserialize_f64() => Float,
+		deserialize_char() => Any,
+		deserialize_str() => Any,
+		deserialize_string() => Any,
+		deserialize_bytes() => Any,
+		deserialize_byte_buf() => Any,
+		deserialize_option() => Option,
+		deserialize_unit() => Unit,
+		deserialize_unit_struct(name: &'static str) => Unit,
+		deserialize_newtype_struct(name: &'static str) => Any,
+		deserialize_seq() => Any,
+		deserialize_tuple(len: usize) => Any,
+		deserialize_tuple_struct(name: &'static str, len: usize) => Any,
+		deserialize_map() => Any,
+		deserialize_struct(name: &'static str, fields: &'static [&'static str]) => Any,
+		deserialize_enum(name: &'static str, variants: &'static [&'static str]) => Any,
+		deserialize_identifier() => Any,
+	);
+
+	fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		self.inner.deserialize_ignored_any(visitor)
+	}
+
+	#[inline]
+	fn is_human_readable(&self) -> bool {
+		self.inner.is_human_readable()
+	}
+}
+
+/// Visitor that resolves strings before forwarding them.
+struct ResolvingVisitor<'a, V, R> {
+	inner: V,
+	state: State<'a, R>,
+	target: Target,
+}
+
+impl<'de, V, R> ResolvingVisitor<'_, V, R>
+where
+	V: Visitor<'de>,
+{
+	/// Forward a resolved string, coercing it into the target scalar.
+	fn visit_text<E>(self, text: Text<'_, 'de>) -> Result<V::Value, E>
+	where
+		E: de::Error,
+	{
+		let coercion = self.state.config.coercion;
+		match (
+			Scalar::parse(text.as_str(), self.target, coercion),
+			self.target,
+		) {
+			// A null-like string is `None` only if the inner type rejects it,
+			// so `Option<String>` keeps the text. Serde exposes that fallback
+			// for `Option` visitors through this hidden hook only.
+			(Some(_), Target::Option) => self
+				.inner
+				.__private_visit_untagged_option(Coerced {
+					text,
+					coercion,
+					marker: PhantomData::<E>,
+				})
+				.map_err(|()| E::custom("cannot coerce a null-like string into this option type")),
+			(Some(scalar), _) => scalar.visit(self.inner),
+			(None, Target::Option) => self.inner.visit_some(Coerced {
+				text,
+				coercion,
+				marker: PhantomData,
+			}),
+			(None, _) => text.visit(self.inner),
+		}
+	}
+}
+
+macro_rules! forward_visit {
+	($($method:ident($ty:ty)),* $(,)?) => {
+		$(
+			#[inline]
+			fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				self.inner.$method(v)
+			}
+		)*
+	};
+}
+
+impl<'de, V, R> Visitor<'de> for ResolvingVisitor<'_, V, R>
+where
+	V: Visitor<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Value = V::Value;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.inner.expecting(formatter)
+	}
+
+	forward_visit!(
+		visit_bool(bool),
+		visit_i8(i8),
+		visit_i16(i16),
+		visit_i32(i32),
+		visit_i64(i64),
+		visit_i128(i128),
+		visit_u8(u8),
+		visit_u16(u16),
+		visit_u32(u32),
+		visit_u64(u64),
+		visit_u128(u128),
+		visit_f32(f32),
+		visit_f64(f64),
+		visit_char(char),
+		visit_bytes(&[u8]),
+		visit_borrowed_bytes(&'de [u8]),
+		visit_byte_buf(alloc::vec::Vec<u8>),
+	);
+
+	fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		match self.state.resolve(v)? {
+			Resolved::Changed(s) | Resolved::Secret(s) => self.visit_text(Text::Owned(s)),
+			Resolved::Unchanged => self.visit_text(Text::Ref(v)),
+		}
+	}
+
+	fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		match self.state.resolve(v)? {
+			Resolved::Changed(s) | Resolved::Secret(s) => self.visit_text(Text::Owned(s)),
+			Resolved::Unchanged => self.visit_text(Text::Borrowed(v)),
+		}
+	}
+
+	fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		match self.state.resolve(&v)? {
+			Resolved::Changed(s) | Resolved::Secret(s) => self.visit_text(Text::Owned(s)),
+			Resolved::Unchanged => self.visit_text(Text::Owned(v)),
+		}
+	}
+
+	#[inline]
+	fn visit_none<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_none()
+	}
+
+	#[inline]
+	fn visit_unit<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_unit()
+	}
+
+	// Options and newtypes are transparent for depth counting.
+	fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		// The content may be a null-like string, so look at it before
+		// committing to `Some`
+		if self.target == Target::Option {
+			return deserializer.deserialize_any(OptionVisitor {
+				inner: self.inner,
+				state: self.state,
+			});
+		}
+		self.inner.visit_some(ResolvingDeserializer {
+			inner: deserializer,
+			state: self.state,
+		})
+	}
+
+	fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		self.inner.visit_newtype_struct(ResolvingDeserializer {
+			inner: deserializer,
+			state: self.state,
+		})
+	}
+
+	fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::SeqAccess<'de>,
+	{
+		self.inner.visit_seq(SeqAccess {
+			inner: seq,
+			state: self.state.nested(),
+		})
+	}
+
+	fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::MapAccess<'de>,
+	{
+		self.inner.visit_map(MapAccess {
+			inner: map,
+			state: self.state.nested(),
+		})
+	}
+
+	fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::EnumAccess<'de>,
+	{
+		self.inner.visit_enum(EnumAccess {
+			inner: data,
+			state: self.state.nested(),
+		})
+	}
+}
+
+/// Visitor for the content of a `Some`, mapping null-like strings to `None`.
+struct OptionVisitor<'a, V, R> {
+	inner: V,
+	state: State<'a, R>,
+}
+
+macro_rules! visit_some_primitive {
+	($($method:ident($ty:ty)),* $(,)?) => {
+		$(
+			#[inline]
+			fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+			where
+				E: de::Error,
+			{
+				self.inner.visit_some(v.into_deserializer())
+			}
+		)*
+	};
+}
+
+impl<'a, V, R> OptionVisitor<'a, V, R> {
+	/// Strings go through the regular resolving path.
+	fn resolving(self) -> ResolvingVisitor<'a, V, R> {
+		ResolvingVisitor {
+			inner: self.inner,
+			state: self.state,
+			target: Target::Option,
+		}
+	}
+}
+
+impl<'de, V, R> Visitor<'de> for OptionVisitor<'_, V, R>
+where
+	V: Visitor<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Value = V::Value;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.inner.expecting(formatter)
+	}
+
+	visit_some_primitive!(
+		visit_bool(bool),
+		visit_i8(i8),
+		visit_i16(i16),
+		visit_i32(i32),
+		visit_i64(i64),
+		visit_i128(i128),
+		visit_u8(u8),
+		visit_u16(u16),
+		visit_u32(u32),
+		visit_u64(u64),
+		visit_u128(u128),
+		visit_f32(f32),
+		visit_f64(f64),
+		visit_char(char),
+	);
+
+	fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.resolving().visit_str(v)
+	}
+
+	fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.resolving().visit_borrowed_str(v)
+	}
+
+	fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.resolving().visit_string(v)
+	}
+
+	fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_some(BytesDeserializer::new(v))
+	}
+
+	fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_some(BorrowedBytesDeserializer::new(v))
+	}
+
+	fn visit_byte_buf<E>(self, v: alloc::vec::Vec<u8>) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_some(BytesDeserializer::new(&v))
+	}
+
+	#[inline]
+	fn visit_none<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_none()
+	}
+
+	#[inline]
+	fn visit_unit<E>(self) -> Result<Self::Value, E>
+	where
+		E: de::Error,
+	{
+		self.inner.visit_none()
+	}
+
+	fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		self.inner.visit_some(ResolvingDeserializer {
+			inner: deserializer,
+			state: self.state,
+		})
+	}
+
+	fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		self.inner.visit_some(ResolvingDeserializer {
+			inner: deserializer,
+			state: self.state,
+		})
+	}
+
+	fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::SeqAccess<'de>,
+	{
+		self.inner.visit_some(SeqAccessDeserializer::new(SeqAccess {
+			inner: seq,
+			state: self.state.nested(),
+		}))
+	}
+
+	fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::MapAccess<'de>,
+	{
+		self.inner.visit_some(MapAccessDeserializer::new(MapAccess {
+			inner: map,
+			state: self.state.nested(),
+		}))
+	}
+
+	fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+	where
+		A: de::EnumAccess<'de>,
+	{
+		self
+			.inner
+			.visit_some(EnumAccessDeserializer::new(EnumAccess {
+				inner: data,
+				state: self.state.nested(),
+			}))
+	}
+}
+
+/// Deserializer for an already resolved string, coercing it on request.
+struct Coerced<'s, 'de, E> {
+	text: Text<'s, 'de>,
+	coercion: Coercion,
+	marker: PhantomData<E>,
+}
+
+macro_rules! coerce_deserialize {
+	($($method:ident($($arg:ident: $ty:ty),*) => $target:ident),* $(,)?) => {
+		$(
+			fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+			where
+				V: Visitor<'de>,
+			{
+				$(let _ = $arg;)*
+				match Scalar::parse(self.text.as_str(), Target::$target, self.coercion) {
+					Some(scalar) => scalar.visit(visitor),
+					None => self.text.visit(visitor),
+				}
+			}
+		)*
+	};
+}
+
+impl<'de, E> Deserializer<'de> for Coerced<'_, 'de, E>
+where
+	E: de::Error,
+{
+	type Error = E;
+
+	coerce_deserialize!(
+		deserialize_bool() => Bool,
+		deserialize_i8() => Signed,
+		deserialize_i16() => Signed,
+		deserialize_i32() => Signed,
+		deserialize_i64() => Signed,
+		deserialize_i128() => Signed,
+		deserialize_u8() => Unsigned,
+		deserialize_u16() => Unsigned,
+		deserialize_u32() => Unsigned,
+		deserialize_u64() => Unsigned,
+		deserialize_u128() => Unsigned,
+		deserialize_f32() => Float,
+		deserialize_f64() => Float,
+		deserialize_unit() => Unit,
+		deserialize_unit_struct(name: &'static str) => Unit,
+	);
+
+	fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+	{
+		self.text.visit(visitor)
+	}
+
+	fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+	{
+		visitor.visit_some(self)
+	}
+
+	fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+	{
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V>(
+		self,
+		name: &'static str,
+		variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, E>
+	where
+		V: Visitor<'de>,
+	{
+		match self.text {
+			Text::Ref(s) => StrDeserializer::new(s).deserialize_enum(name, variants, visitor),
+			Text::Borrowed(s) => {
+				BorrowedStrDeserializer::new(s).deserialize_enum(name, variants, visitor)
+			}
+			Text::Owned(s) => StringDeserializer::new(s).deserialize_enum(name, variants, visitor),
+		}
+	}
+
+	serde::forward_to_deserialize_any! {
+		char str string bytes byte_buf seq tuple tuple_struct map struct
+		identifier ignored_any
+	}
+}
+
+/// Seed that wraps the deserializer it is given.
+struct Seed<'a, S, R> {
+	inner: S,
+	state: State<'a, R>,
+}
+
+impl<'de, S, R> DeserializeSeed<'de> for Seed<'_, S, R>
+where
+	S: DeserializeSeed<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Value = S::Value;
+
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		self.inner.deserialize(ResolvingDeserializer {
+			inner: deserializer,
+			state: self.state,
+		})
+	}
+}
+
+struct SeqAccess<'a, A, R> {
+	inner: A,
+	state: State<'a, R>,
+}
+
+impl<'de, A, R> de::SeqAccess<'de> for SeqAccess<'_, A, R>
+where
+	A: de::SeqAccess<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Error = A::Error;
+
+	fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+	where
+		T: DeserializeSeed<'de>,
+	{
+		self.inner.next_element_seed(Seed {
+			inner: seed,
+			state: self.state,
+		})
+	}
+
+	#[inline]
+	fn size_hint(&self) -> Option<usize> {
+		self.inner.size_hint()
+	}
+}
+
+struct MapAccess<'a, A, R> {
+	inner: A,
+	state: State<'a, R>,
+}
+
+impl<'de, A, R> de::MapAccess<'de> for MapAccess<'_, A, R>
+where
+	A: de::MapAccess<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Error = A::Error;
+
+	fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+	where
+		K: DeserializeSeed<'de>,
+	{
+		if self.state.config.resolve_keys {
+			self.inner.next_key_seed(Seed {
+				inner: seed,
+				state: self.state,
+			})
+		} else {
+			self.inner.next_key_seed(seed)
+		}
+	}
+
+	fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+	where
+		V: DeserializeSeed<'de>,
+	{
+		self.inner.next_value_seed(Seed {
+			inner: seed,
+			state: self.state,
+		})
+	}
+
+	#[inline]
+	fn size_hint(&self) -> Option<usize> {
+		self.inner.size_hint()
+	}
+}
+
+struct EnumAccess<'a, A, R> {
+	inner: A,
+	state: State<'a, R>,
+}
+
+impl<'a, 'de, A, R> de::EnumAccess<'de> for EnumAccess<'a, A, R>
+where
+	A: de::EnumAccess<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Error = A::Error;
+	type Variant = VariantAccess<'a, A::Variant, R>;
+
+	fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+	where
+		V: DeserializeSeed<'de>,
+	{
+		// Variant names are never resolved
+		let (value, variant) = self.inner.variant_seed(seed)?;
+		Ok((
+			value,
+			VariantAccess {
+				inner: variant,
+				state: self.state,
+			},
+		))
+	}
+}
+
+struct VariantAccess<'a, A, R> {
+	inner: A,
+	state: State<'a, R>,
+}
+
+impl<'de, A, R> de::VariantAccess<'de> for VariantAccess<'_, A, R>
+where
+	A: de::VariantAccess<'de>,
+	R: SyncResolver,
+	R::Error: fmt::Display,
+{
+	type Error = A::Error;
+
+	#[inline]
+	fn unit_variant(self) -> Result<(), Self::Error> {
+		self.inner.unit_variant()
+	}
+
+	fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+	where
+		T: DeserializeSeed<'de>,
+	{
+		self.inner.newtype_variant_seed(Seed {
+			inner: seed,
+			state: self.state,
+		})
+	}
+
+	fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		self.inner.tuple_variant(
+			len,
+			ResolvingVisitor {
+				inner: visitor,
+				state: self.state,
+				target: Target::Any,
+			},
+		)
+	}
+
+	fn struct_variant<V>(
+		self,
+		fields: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		self.inner.struct_variant(
+			fields,
+			ResolvingVisitor {
+				inner: visitor,
+				state: self.state,
+				target: Target::Any,
+			},
+		)
+	}
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+	use super::*;
+	use alloc::borrow::ToOwned;
+	use alloc::collections::BTreeMap;
+	use alloc::string::{String, ToString};
+	use alloc::vec;
+	use alloc::vec::Vec;
+	use core::convert::Infallible;
+	use serde::Deserialize;
+
+	#[derive(Debug, Deserialize, PartialEq)]
+	struct AppConfig {
+		name: String,
+		tags: Vec<String>,
+		port: u16,
+		owner: Option<String>,
+		mode: Mode,
+		labels: BTreeMap<String, String>,
+	}
+
+	#[derive(Debug, Deserialize, PartialEq)]
+	enum Mode {
+		Fast,
+		Custom { level: String },
+	}
+
+	#[allow(clippy::unnecessary_wraps)]
+	fn expand(s: &str) -> Result<Resolved, Infallible> {
+		Ok(match s.strip_prefix('$') {
+			Some(rest) => Resolved::changed(rest.to_uppercase()),
+			None => Resolved::unchanged(),
+		})
+	}
+
+	#[allow(clippy::unnecessary_wraps)]
+	fn strip(s: &str) -> Result<Resolved, Infallible> {
+		Ok(
+			s.strip_prefix('$')
+				.map_or_else(Resolved::unchanged, Resolved::changed),
+		)
+	}
+
+	const JSON: &str = r#"{
+		"name": "$app",
+		"tags": ["$a", "b"],
+		"port": 8080,
+		"owner": "$me",
+		"mode": { "Custom": { "level": "$high" } },
+		"labels": { "$key": "$value" }
+	}"#;
+
+	fn expected(key: &str) -> AppConfig {
+		AppConfig {
+			name: "APP".to_owned(),
+			tags: vec!["A".to_owned(), "b".to_owned()],
+			port: 8080,
+			owner: Some("ME".to_owned()),
+			mode: Mode::Custom {
+				level: "HIGH".to_owned(),
+			},
+			labels: BTreeMap::from([(key.to_owned(), "VALUE".to_owned())]),
+		}
+	}
+
+	#[test]
+	fn test_json() {
+		let mut json = serde_json::Deserializer::from_str(JSON);
+		let config: AppConfig = deserialize(&mut json, &expand, &Config::default()).unwrap();
+		assert_eq!(config, expected("$key"));
+	}
+
+	#[test]
+	fn test_resolve_keys() {
+		let mut json = serde_json::Deserializer::from_str(JSON);
+		let config: AppConfig =
+			deserialize(&mut json, &expand, &Config::default().resolve_keys(true)).unwrap();
+		assert_eq!(config, expected("KEY"));
+	}
+
+	#[cfg(feature = "yaml")]
+	#[test]
+	fn test_yaml() {
+		let yaml = "name: $app\ntags: [$a, b]\nport: 8080\nowner: $me\nmode: !Custom\n  level: $high\nlabels:\n  $key: $value\n";
+		let config: AppConfig = deserialize(
+			serde_yaml::Deserializer::from_str(yaml),
+			&expand,
+			&Config::default(),
+		)
+		.unwrap();
+		assert_eq!(config, expected("$key"));
+	}
+
+	#[cfg(feature = "toml")]
+	#[test]
+	fn test_toml() {
+		let toml = "name = \"$app\"\ntags = [\"$a\", \"b\"]\nport = 8080\nowner = \"$me\"\n\n[mode.Custom]\nlevel = \"$high\"\n\n[labels]\n\"$key\" = \"$value\"\n";
+		let config: AppConfig = deserialize(
+			toml::Deserializer::parse(toml).unwrap(),
+			&expand,
+			&Config::default(),
+		)
+		.unwrap();
+		assert_eq!(config, expected("$key"));
+	}
+
+	#[test]
+	fn test_unit_variant_not_resolved() {
+		let mut json = serde_json::Deserializer::from_str(r#""Fast""#);
+		let mode: Mode = deserialize(
+			&mut json,
+			&|_: &str| Ok::<_, Infallible>(Resolved::changed("Slow")),
+			&Config::default(),
+		)
+		.unwrap();
+		assert_eq!(mode, Mode::Fast);
+	}
+
+	#[test]
+	fn test_depth_limit() {
+		let mut json = serde_json::Deserializer::from_str(r#"[["x"]]"#);
+		let err =
+			deserialize::<Vec<Vec<String>>, _, _>(&mut json, &expand, &Config::default().max_depth(2))
+				.unwrap_err();
+		assert!(err.to_string().contains("depth limit (2) exceeded"));
+	}
+
+	#[test]
+	fn test_resolver_error() {
+		let mut json = serde_json::Deserializer::from_str(r#"{"a": "boom"}"#);
+		let err = deserialize::<BTreeMap<String, String>, _, _>(
+			&mut json,
+			&|_: &str| Err::<Resolved, _>("backend down"),
+			&Config::default(),
+		)
+		.unwrap_err();
+		assert!(err.to_string().contains("resolver error: backend down"));
+	}
+
+	#[derive(Debug, Deserialize, PartialEq)]
+	struct Typed {
+		port: u16,
+		offset: i32,
+		ratio: f64,
+		debug: bool,
+		owner: Option<u32>,
+		group: Option<String>,
+	}
+
+	const TYPED: &str = r#"{
+		"port": "$8080",
+		"offset": "-3",
+		"ratio": "0.5",
+		"debug": "$true",
+		"owner": "$null",
+		"group": "$admin"
+	}"#;
+
+	#[test]
+	fn test_coercion_off() {
+		let mut json = serde_json::Deserializer::from_str(TYPED);
+		let err = deserialize::<Typed, _, _>(&mut json, &expand, &Config::default()).unwrap_err();
+		assert!(err.to_string().contains("invalid type: string"));
+	}
+
+	#[test]
+	fn test_coercion_strict() {
+		let mut json = serde_json::Deserializer::from_str(TYPED);
+		let config = Config::default().coercion(Coercion::Strict);
+		let typed: Typed = deserialize(&mut json, &strip, &config).unwrap();
+		assert_eq!(
+			typed,
+			Typed {
+				port: 8080,
+				offset: -3,
+				ratio: 0.5,
+				debug: true,
+				owner: None,
+				group: Some("admin".to_owned()),
+			}
+		);
+		// `offset` and `ratio` are coerced although `strip` left them unchanged
+
+		// Strict mode does not accept loose literals
+		let mut json = serde_json::Deserializer::from_str(r#"{"debug": "yes"}"#);
+		let err = deserialize::<BTreeMap<String, bool>, _, _>(&mut json, &expand, &config).unwrap_err();
+		assert!(err.to_string().contains("invalid type: string \"yes\""));
+	}
+
+	#[test]
+	fn test_coercion_lenient() {
+		let mut json = serde_json::Deserializer::from_str(
+			r#"{"port": " 80 ", "offset": 7, "ratio": "1e3", "debug": "Yes", "owner": "~", "group": ""}"#,
+		);
+		let typed: Typed = deserialize(
+			&mut json,
+			&expand,
+			&Config::default().coercion(Coercion::Lenient),
+		)
+		.unwrap();
+		assert_eq!(
+			typed,
+			Typed {
+				port: 80,
+				offset: 7,
+				ratio: 1000.0,
+				debug: true,
+				owner: None,
+				group: Some(String::new()),
+			}
+		);
+	}
+
+	#[test]
+	fn test_coercion_option_string() {
+		let strict = Config::default().coercion(Coercion::Strict);
+		let mut json = serde_json::Deserializer::from_str(r#"{"a": "null", "b": "$null"}"#);
+		let map: BTreeMap<String, Option<String>> = deserialize(&mut json, &strip, &strict).unwrap();
+		assert_eq!(map["a"].as_deref(), Some("null"));
+		assert_eq!(map["b"].as_deref(), Some("null"));
+
+		let lenient = Config::default().coercion(Coercion::Lenient);
+		let mut json = serde_json::Deserializer::from_str(r#"{"a": "", "b": "~", "c": "NULL"}"#);
+		let map: BTreeMap<String, Option<String>> = deserialize(&mut json, &strip, &lenient).unwrap();
+		assert_eq!(map["a"].as_deref(), Some(""));
+		assert_eq!(map["b"].as_deref(), Some("~"));
+		assert_eq!(map["c"].as_deref(), Some("NULL"));
+
+		let mut json = serde_json::Deserializer::from_str(r#"{"a": "", "b": "~", "c": "NULL"}"#);
+		let map: BTreeMap<String, Option<u8>> = deserialize(&mut json, &strip, &lenient).unwrap();
+		assert!(map.values().all(Option::is_none));
+	}
+
+	#[test]
+	fn test_coercion_nested_option() {
+		#[derive(Debug, Deserialize, PartialEq)]
+		struct Nested {
+			list: Vec<Option<u8>>,
+			map: Option<BTreeMap<String, u8>>,
+			none: Option<Vec<u8>>,
+			mode: Option<Mode>,
+		}
+
+		let mut json = serde_json::Deserializer::from_str(
+			r#"{"list": ["1", "null", null, 2], "map": {"x": "$3"}, "none": "$null", "mode": "Fast"}"#,
+		);
+		let nested: Nested = deserialize(
+			&mut json,
+			&strip,
+			&Config::default().coercion(Coercion::Strict),
+		)
+		.unwrap();
+		assert_eq!(
+			nested,
+			Nested {
+				list: vec![Some(1), None, None, Some(2)],
+				map: Some(BTreeMap::from([("x".to_owned(), 3)])),
+				none: None,
+				mode: Some(Mode::Fast),
+			}
+		);
+	}
+}
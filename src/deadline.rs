@@ -0,0 +1,504 @@
+/* src/deadline.rs */
+
+//! Deadlines and cancellation for async resolution.
+//!
+//! Timeouts need a [`Timer`] set with [`Config::timer`](crate::Config::timer),
+//! so they work on any runtime; [`TokioTimer`] adapts tokio's timer with the
+//! `tokio` feature. Cancellation is signalled through a [`CancelToken`].
+//! Both require the `std` feature.
+
+#[cfg(feature = "std")]
+pub use imp::{CancelToken, Timer};
+
+#[cfg(feature = "tokio")]
+pub use imp::TokioTimer;
+
+pub(crate) use imp::Guard;
+
+#[cfg(feature = "std")]
+mod imp {
+	use alloc::collections::BTreeMap;
+	use alloc::sync::Arc;
+	use core::future::{Future, poll_fn};
+	use core::pin::{Pin, pin};
+	use core::sync::atomic::{AtomicBool, Ordering};
+	use core::task::{Context, Poll, Waker};
+	use core::time::Duration;
+	use std::sync::{Mutex, PoisonError};
+
+	use crate::{BoxFuture, Config, Error, Path};
+
+	/// Runtime-agnostic timer enforcing [`Config::timeout`] and
+	/// [`Config::string_timeout`].
+	///
+	/// There is no implicit default: operations with a timeout but without
+	/// [`Config::timer`] fail with [`Error::MissingTimer`].
+	///
+	/// # Example
+	///
+	/// ```rust
+	/// use serde_resolve::{BoxFuture, Timer};
+	/// use std::time::Duration;
+	///
+	/// #[derive(Debug)]
+	/// struct ThreadTimer;
+	///
+	/// impl Timer for ThreadTimer {
+	///     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+	///         let (tx, rx) = std::sync::mpsc::channel::<()>();
+	///         std::thread::spawn(move || {
+	///             std::thread::sleep(duration);
+	///             let _ = tx.send(());
+	///         });
+	///         // A real timer would wake the task instead of polling
+	///         Box::pin(std::future::poll_fn(move |cx| match rx.try_recv() {
+	///             Err(std::sync::mpsc::TryRecvError::Empty) => {
+	///                 cx.waker().wake_by_ref();
+	///                 std::task::Poll::Pending
+	///             }
+	///             _ => std::task::Poll::Ready(()),
+	///         }))
+	///     }
+	/// }
+	/// ```
+	pub trait Timer: core::fmt::Debug + Send + Sync {
+		/// A future completing after `duration`.
+		fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+	}
+
+	/// [`Timer`] backed by `tokio::time::sleep`.
+	///
+	/// Requires a tokio runtime with the time driver enabled.
+	#[cfg(feature = "tokio")]
+	#[derive(Debug, Clone, Copy, Default)]
+	pub struct TokioTimer;
+
+	#[cfg(feature = "tokio")]
+	impl Timer for TokioTimer {
+		fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+			alloc::boxed::Box::pin(tokio::time::sleep(duration))
+		}
+	}
+
+	/// Signal cancelling resolve operations from outside.
+	///
+	/// Clones share the same signal. Once [`cancel`](Self::cancel) is called,
+	/// every operation configured with the token fails with
+	/// [`Error::Cancelled`] at its current or next string.
+	#[derive(Debug, Clone, Default)]
+	pub struct CancelToken {
+		inner: Arc<CancelState>,
+	}
+
+	#[derive(Debug, Default)]
+	struct CancelState {
+		cancelled: AtomicBool,
+		waiters: Mutex<Waiters>,
+	}
+
+	/// Wakers of pending [`CancelToken::cancelled`] futures, by slot.
+	#[derive(Debug, Default)]
+	struct Waiters {
+		next: u64,
+		slots: BTreeMap<u64, Waker>,
+	}
+
+	impl CancelState {
+		fn waiters(&self) -> std::sync::MutexGuard<'_, Waiters> {
+			self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+		}
+	}
+
+	impl CancelToken {
+		/// Create a token that is not cancelled.
+		#[inline]
+		#[must_use]
+		pub fn new() -> Self {
+			Self::default()
+		}
+
+		/// Cancel all operations using this token.
+		pub fn cancel(&self) {
+			if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+				let slots = core::mem::take(&mut self.inner.waiters().slots);
+				slots.into_values().for_each(Waker::wake);
+			}
+		}
+
+		/// Returns `true` once [`cancel`](Self::cancel) was called.
+		#[inline]
+		#[must_use]
+		pub fn is_cancelled(&self) -> bool {
+			self.inner.cancelled.load(Ordering::SeqCst)
+		}
+
+		/// A future completing once the token is cancelled.
+		///
+		/// Its waker is deregistered when the future is dropped.
+		pub fn cancelled(&self) -> impl Future<Output = ()> + Send + '_ {
+			Cancelled {
+				token: self,
+				slot: None,
+			}
+		}
+
+		/// Number of registered `cancelled` futures.
+		#[cfg(test)]
+		pub(super) fn waiters(&self) -> usize {
+			self.inner.waiters().slots.len()
+		}
+	}
+
+	/// Future returned by [`CancelToken::cancelled`].
+	struct Cancelled<'a> {
+		token: &'a CancelToken,
+		slot: Option<u64>,
+	}
+
+	impl Future for Cancelled<'_> {
+		type Output = ();
+
+		fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+			if self.token.is_cancelled() {
+				return Poll::Ready(());
+			}
+			let mut waiters = self.token.inner.waiters();
+			if let Some(slot) = self.slot {
+				if let Some(waker) = waiters.slots.get_mut(&slot) {
+					waker.clone_from(cx.waker());
+				}
+			} else {
+				let slot = waiters.next;
+				waiters.next += 1;
+				waiters.slots.insert(slot, cx.waker().clone());
+				drop(waiters);
+				self.slot = Some(slot);
+			}
+
+			// Cancelled between the first check and registering the waker
+			if self.token.is_cancelled() {
+				Poll::Ready(())
+			} else {
+				Poll::Pending
+			}
+		}
+	}
+
+	impl Drop for Cancelled<'_> {
+		fn drop(&mut self) {
+			if let Some(slot) = self.slot {
+				self.token.inner.waiters().slots.remove(&slot);
+			}
+		}
+	}
+
+	/// Deadlines and cancellation of one resolve operation.
+	#[derive(Default)]
+	pub(crate) struct Guard {
+		timer: Option<Arc<dyn Timer>>,
+		string_timeout: Option<Duration>,
+		overall: Option<BoxFuture<'static, ()>>,
+		expired: bool,
+		cancel: Option<CancelToken>,
+	}
+
+	impl core::fmt::Debug for Guard {
+		fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+			f.debug_struct("Guard")
+				.field("timer", &self.timer)
+				.field("string_timeout", &self.string_timeout)
+				.field("expired", &self.expired)
+				.field("cancel", &self.cancel)
+				.finish_non_exhaustive()
+		}
+	}
+
+	impl Guard {
+		/// Start the overall deadline of an operation.
+		///
+		/// Fails with [`Error::MissingTimer`] if a timeout is set without a
+		/// timer.
+		pub(crate) fn new<E>(config: &Config) -> Result<Self, Error<E>> {
+			let timer = config.timer.clone();
+			if timer.is_none() && (config.timeout.is_some() || config.string_timeout.is_some()) {
+				return Err(Error::MissingTimer);
+			}
+			let overall = timer
+				.as_ref()
+				.zip(config.timeout)
+				.map(|(timer, timeout)| timer.sleep(timeout));
+			Ok(Self {
+				timer,
+				string_timeout: config.string_timeout,
+				overall,
+				expired: false,
+				cancel: config.cancel.clone(),
+			})
+		}
+
+		/// Run the resolution of the string at `path` within the deadlines.
+		pub(crate) async fn run<T, E>(
+			&mut self,
+			path: &Path,
+			future: impl Future<Output = Result<T, Error<E>>>,
+		) -> Result<T, Error<E>> {
+			if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+				return Err(Error::Cancelled);
+			}
+			if self.expired {
+				return Err(Error::timeout(path.clone()));
+			}
+
+			let mut future = pin!(future);
+			let mut string_deadline = self
+				.timer
+				.as_ref()
+				.zip(self.string_timeout)
+				.map(|(timer, timeout)| timer.sleep(timeout));
+			let mut cancelled = pin!(self.cancel.as_ref().map(CancelToken::cancelled));
+			let overall = &mut self.overall;
+			let expired = &mut self.expired;
+
+			poll_fn(|cx| {
+				if let Poll::Ready(result) = future.as_mut().poll(cx) {
+					return Poll::Ready(result);
+				}
+				if let Some(cancelled) = cancelled.as_mut().as_pin_mut()
+					&& cancelled.poll(cx).is_ready()
+				{
+					return Poll::Ready(Err(Error::Cancelled));
+				}
+				if let Some(deadline) = overall
+					&& deadline.as_mut().poll(cx).is_ready()
+				{
+					*overall = None;
+					*expired = true;
+					return Poll::Ready(Err(Error::timeout(path.clone())));
+				}
+				if let Some(deadline) = &mut string_deadline
+					&& deadline.as_mut().poll(cx).is_ready()
+				{
+					return Poll::Ready(Err(Error::timeout(path.clone())));
+				}
+				Poll::Pending
+			})
+			.await
+		}
+	}
+}
+
+#[cfg(not(feature = "std"))]
+mod imp {
+	use core::future::Future;
+
+	use crate::{Config, Error, Path};
+
+	/// Without `std` there are no deadlines or cancellation.
+	#[derive(Debug, Default)]
+	pub(crate) struct Guard;
+
+	impl Guard {
+		pub(crate) const fn new<E>(_config: &Config) -> Result<Self, Error<E>> {
+			Ok(Self)
+		}
+
+		// Mirrors the signature of the std implementation
+		#[allow(clippy::unused_self, clippy::needless_pass_by_ref_mut)]
+		pub(crate) async fn run<T, E>(
+			&mut self,
+			_path: &Path,
+			future: impl Future<Output = Result<T, Error<E>>>,
+		) -> Result<T, Error<E>> {
+			future.await
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+	use crate::{Config, Error, Path, PathSegment, Resolved};
+	use alloc::sync::Arc;
+	use alloc::vec;
+	use core::convert::Infallible;
+	use core::future::Future;
+	use core::task::Poll;
+	use core::time::Duration;
+	use std::sync::Mutex;
+
+	/// Timer whose sleeps complete only when fired by the test.
+	#[derive(Debug, Default)]
+	struct ManualTimer {
+		fired: Mutex<Vec<Duration>>,
+	}
+
+	impl ManualTimer {
+		fn fire(&self, duration: Duration) {
+			self.fired.lock().unwrap().push(duration);
+		}
+	}
+
+	impl Timer for Arc<ManualTimer> {
+		fn sleep(&self, duration: Duration) -> crate::BoxFuture<'static, ()> {
+			let timer = Self::clone(self);
+			Box::pin(core::future::poll_fn(move |cx| {
+				if timer.fired.lock().unwrap().contains(&duration) {
+					Poll::Ready(())
+				} else {
+					cx.waker().wake_by_ref();
+					Poll::Pending
+				}
+			}))
+		}
+	}
+
+	fn key(name: &str) -> Path {
+		Path::from(vec![PathSegment::Key(name.into())])
+	}
+
+	fn pending() -> impl Future<Output = Result<Resolved, Error<Infallible>>> {
+		core::future::pending()
+	}
+
+	#[tokio::test]
+	async fn test_string_timeout() {
+		let timer = Arc::new(ManualTimer::default());
+		let config = Config::default()
+			.string_timeout(Duration::from_secs(1))
+			.timer(Arc::clone(&timer));
+		let mut guard = Guard::new::<Infallible>(&config).unwrap();
+
+		let ready = async { Ok::<_, Error<Infallible>>(Resolved::changed("x")) };
+		assert!(guard.run(&key("a"), ready).await.is_ok());
+
+		timer.fire(Duration::from_secs(1));
+		let err = guard.run(&key("b"), pending()).await.unwrap_err();
+		assert!(matches!(err, Error::Timeout { path } if path == key("b")));
+	}
+
+	#[tokio::test]
+	async fn test_overall_timeout_sticks() {
+		let timer = Arc::new(ManualTimer::default());
+		let config = Config::default()
+			.timeout(Duration::from_secs(5))
+			.timer(Arc::clone(&timer));
+		let mut guard = Guard::new::<Infallible>(&config).unwrap();
+
+		timer.fire(Duration::from_secs(5));
+		let err = guard.run(&key("a"), pending()).await.unwrap_err();
+		assert!(matches!(err, Error::Timeout { path } if path == key("a")));
+
+		// Later strings fail without being polled
+		let ready = async { Ok::<_, Error<Infallible>>(Resolved::Unchanged) };
+		let err = guard.run(&Path::root(), ready).await.unwrap_err();
+		assert_eq!(err.to_string(), "timed out resolving .");
+	}
+
+	#[tokio::test]
+	async fn test_cancel() {
+		let token = CancelToken::new();
+		let config = Config::default().cancel(token.clone());
+		let mut guard = Guard::new::<Infallible>(&config).unwrap();
+
+		let cancel = token.clone();
+		let blocked = async move {
+			tokio::task::yield_now().await;
+			cancel.cancel();
+			core::future::pending::<Result<Resolved, Error<Infallible>>>().await
+		};
+		let err = guard.run(&key("a"), blocked).await.unwrap_err();
+		assert!(matches!(err, Error::Cancelled));
+		assert!(token.is_cancelled());
+
+		let ready = async { Ok::<_, Error<Infallible>>(Resolved::Unchanged) };
+		assert!(matches!(
+			guard.run(&key("b"), ready).await,
+			Err(Error::Cancelled)
+		));
+	}
+
+	#[tokio::test]
+	async fn test_cancel_from_task() {
+		let token = CancelToken::new();
+		let waiter = token.clone();
+		let task = tokio::spawn(async move { waiter.cancelled().await });
+		tokio::task::yield_now().await;
+		token.cancel();
+		task.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_cancel_waiters_released() {
+		let token = CancelToken::new();
+		let config = Config::default().cancel(token.clone());
+		let mut guard = Guard::new::<Infallible>(&config).unwrap();
+
+		for i in 0..10 {
+			let yielding = async {
+				tokio::task::yield_now().await;
+				Ok::<_, Error<Infallible>>(Resolved::Unchanged)
+			};
+			guard.run(&key("a"), yielding).await.unwrap();
+			assert_eq!(token.waiters(), 0, "after run {i}");
+		}
+
+		let mut waiter = Box::pin(token.cancelled());
+		assert!(poll_once(waiter.as_mut()).is_pending());
+		assert_eq!(token.waiters(), 1);
+		drop(waiter);
+		assert_eq!(token.waiters(), 0);
+	}
+
+	fn poll_once<F: Future>(future: core::pin::Pin<&mut F>) -> Poll<F::Output> {
+		future.poll(&mut core::task::Context::from_waker(
+			core::task::Waker::noop(),
+		))
+	}
+
+	#[test]
+	fn test_timeout_requires_timer() {
+		for config in [
+			Config::default().timeout(Duration::from_secs(1)),
+			Config::default().string_timeout(Duration::from_secs(1)),
+		] {
+			assert!(matches!(
+				Guard::new::<Infallible>(&config),
+				Err(Error::MissingTimer)
+			));
+		}
+		assert!(Guard::new::<Infallible>(&Config::default()).is_ok());
+	}
+
+	#[cfg(all(feature = "json", feature = "tokio"))]
+	#[tokio::test]
+	async fn test_tokio_timer_in_tree() {
+		let resolver = |s: &str| {
+			let slow = s == "slow";
+			async move {
+				if slow {
+					tokio::time::sleep(Duration::from_secs(60)).await;
+				}
+				Ok::<_, Infallible>(Resolved::changed("ok"))
+			}
+		};
+		let config = Config::default()
+			.string_timeout(Duration::from_millis(20))
+			.timer(TokioTimer);
+
+		let value = crate::json::resolve(serde_json::json!({ "a": "fast" }), &resolver, &config)
+			.await
+			.unwrap();
+		assert_eq!(value, serde_json::json!({ "a": "ok" }));
+
+		let result = crate::json::resolve(
+			serde_json::json!({ "a": "fast", "b": ["x", "slow"] }),
+			&resolver,
+			&config,
+		)
+		.await;
+		let Err(Error::Timeout { path }) = result else {
+			panic!("expected timeout");
+		};
+		assert_eq!(path.to_string(), "b[1]");
+	}
+}
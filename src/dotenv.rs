@@ -0,0 +1,318 @@
+/* src/dotenv.rs */
+
+//! Dotenv (`.env`) file support.
+//!
+//! Supports `KEY=value` lines with an optional `export` prefix, bare,
+//! `'single'` and `"double"` quoted values (quoted values may span lines),
+//! `#` comments and trailing ` # comments`. Changed values keep their original
+//! quoting style where possible.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::flat::{self, Document, Entry, FlatError, Line, ParseError};
+use crate::{Config, Resolver};
+
+/// Parse a dotenv file into a [`Document`].
+///
+/// # Errors
+///
+/// Returns an error if a line has no `=` or a quoted value is not terminated.
+pub fn parse(input: &str) -> Result<Document, ParseError> {
+	let mut lines = Vec::new();
+	let mut physical = flat::physical_lines(input).enumerate();
+
+	while let Some((index, (content, newline))) = physical.next() {
+		let line_no = index + 1;
+		let (indent, rest) = flat::split_indent(content);
+
+		if rest.is_empty() || rest.starts_with('#') {
+			lines.push(Line::Other(alloc::format!("{content}{newline}")));
+			continue;
+		}
+
+		let (export, rest) = match rest.strip_prefix("export") {
+			Some(after) if after.starts_with([' ', '\t']) => {
+				let (ws, after) = flat::split_indent(after);
+				(&rest[.."export".len() + ws.len()], after)
+			}
+			_ => ("", rest),
+		};
+
+		let Some(eq) = rest.find('=') else {
+			return Err(ParseError {
+				line: line_no,
+				message: "expected `=`",
+			});
+		};
+		let key = rest[..eq].trim_end();
+		let after_eq = &rest[eq + 1..];
+		let (ws_after, value_text) = flat::split_indent(after_eq);
+		let separator = alloc::format!("{}={ws_after}", &rest[key.len()..eq]);
+
+		let mut raw = alloc::format!("{content}{newline}");
+		let mut line_end = String::from(newline);
+		let (value, quote, suffix) = if let Some(q @ ('"' | '\'')) = value_text.chars().next() {
+			// Quoted values may continue on following physical lines.
+			let mut text = String::from(&value_text[1..]);
+			let close = loop {
+				if let Some(close) = find_closing(&text, q) {
+					break close;
+				}
+				let Some((_, (next, next_newline))) = physical.next() else {
+					return Err(ParseError {
+						line: line_no,
+						message: "unterminated quoted value",
+					});
+				};
+				text.push_str(&line_end);
+				text.push_str(next);
+				raw.push_str(next);
+				raw.push_str(next_newline);
+				line_end = String::from(next_newline);
+			};
+			let suffix = String::from(&text[close + 1..]);
+			let body = &text[..close];
+			let value = if q == '"' {
+				unescape_double(body)
+			} else {
+				String::from(body)
+			};
+			(value, Some(q), suffix)
+		} else {
+			let end = comment_start(value_text).unwrap_or(value_text.len());
+			let value = value_text[..end].trim_end();
+			(
+				String::from(value),
+				None,
+				String::from(&value_text[value.len()..]),
+			)
+		};
+
+		let mut entry = Entry::new(None, String::from(key), value, raw);
+		entry.prefix = alloc::format!("{indent}{export}");
+		entry.separator = separator;
+		entry.suffix = suffix;
+		entry.newline = line_end;
+		entry.quote = quote;
+		lines.push(Line::Entry(entry));
+	}
+
+	Ok(Document::new(lines, encode))
+}
+
+/// Parse, resolve and re-serialize a dotenv file.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input cannot be parsed
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_str<R>(
+	input: &str,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, FlatError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let document = parse(input).map_err(FlatError::Parse)?;
+	let resolved = flat::resolve(document, resolver, config)
+		.await
+		.map_err(FlatError::Resolve)?;
+	Ok(alloc::string::ToString::to_string(&resolved))
+}
+
+fn encode(entry: &Entry, out: &mut String) {
+	out.push_str(&entry.prefix);
+	out.push_str(entry.key());
+	out.push_str(&entry.separator);
+
+	let value = entry.value();
+	let bare_safe = value.trim() == value && !value.contains(['#', '"', '\'', '\\', '\n', '\r']);
+	match entry.quote {
+		None if bare_safe => out.push_str(value),
+		Some('\'') if !value.contains('\'') => {
+			out.push('\'');
+			out.push_str(value);
+			out.push('\'');
+		}
+		_ => {
+			out.push('"');
+			for c in value.chars() {
+				match c {
+					'"' => out.push_str("\\\""),
+					'\\' => out.push_str("\\\\"),
+					'\n' => out.push_str("\\n"),
+					'\r' => out.push_str("\\r"),
+					'\t' => out.push_str("\\t"),
+					c => out.push(c),
+				}
+			}
+			out.push('"');
+		}
+	}
+
+	out.push_str(&entry.suffix);
+	out.push_str(&entry.newline);
+}
+
+/// Byte offset of the closing quote `q` in `text`, honoring escapes in double quotes.
+fn find_closing(text: &str, q: char) -> Option<usize> {
+	let mut escaped = false;
+	for (i, c) in text.char_indices() {
+		match c {
+			'\\' if q == '"' && !escaped => escaped = true,
+			c if c == q && !escaped => return Some(i),
+			_ => escaped = false,
+		}
+	}
+	None
+}
+
+/// Byte offset of a ` #` trailing comment in an unquoted value.
+fn comment_start(text: &str) -> Option<usize> {
+	text
+		.char_indices()
+		.find(|&(i, c)| c == '#' && text[..i].ends_with([' ', '\t']))
+		.map(|(i, _)| i)
+}
+
+fn unescape_double(body: &str) -> String {
+	let mut out = String::with_capacity(body.len());
+	let mut chars = body.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => out.push('\n'),
+			Some('r') => out.push('\r'),
+			Some('t') => out.push('\t'),
+			Some(c @ ('"' | '\\' | '$')) => out.push(c),
+			Some(c) => {
+				out.push('\\');
+				out.push(c);
+			}
+			None => out.push('\\'),
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, Resolved};
+	use alloc::borrow::ToOwned;
+	use alloc::string::ToString;
+	use core::convert::Infallible;
+
+	const INPUT: &str = "# Service settings\n\
+		export DB_HOST=${HOST} # primary\n\
+		DB_PASS='${PASS}'\n\
+		GREETING=\"hello\\n${NAME}\"\n\
+		\n\
+		MULTI=\"line one\n\
+		line two\"\n";
+
+	fn expand(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+		let resolved = s
+			.replace("${HOST}", "db.internal")
+			.replace("${PASS}", "it's secret")
+			.replace("${NAME}", "world");
+		let changed = resolved != s;
+		async move {
+			Ok(if changed {
+				Resolved::changed(resolved)
+			} else {
+				Resolved::unchanged()
+			})
+		}
+	}
+
+	#[test]
+	fn test_parse() {
+		let doc = parse(INPUT).unwrap();
+		assert_eq!(doc.get("DB_HOST"), Some("${HOST}"));
+		assert_eq!(doc.get("DB_PASS"), Some("${PASS}"));
+		assert_eq!(doc.get("GREETING"), Some("hello\n${NAME}"));
+		assert_eq!(doc.get("MULTI"), Some("line one\nline two"));
+		assert_eq!(doc.to_string(), INPUT);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_str() {
+		let output = resolve_str(INPUT, &expand, &Config::default())
+			.await
+			.unwrap();
+
+		let expected = "# Service settings\n\
+			export DB_HOST=db.internal # primary\n\
+			DB_PASS=\"it's secret\"\n\
+			GREETING=\"hello\\nworld\"\n\
+			\n\
+			MULTI=\"line one\n\
+			line two\"\n";
+		assert_eq!(output, expected);
+		assert_eq!(parse(&output).unwrap().get("DB_PASS"), Some("it's secret"));
+	}
+
+	#[tokio::test]
+	async fn test_bare_value_quoted_when_needed() {
+		let output = resolve_str(
+			"A=x\n",
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::changed("two words # not a comment")) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "A=\"two words # not a comment\"\n");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_keys() {
+		let output = resolve_str(
+			"name=value\n",
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "NAME=VALUE\n");
+	}
+
+	#[test]
+	fn test_parse_errors() {
+		assert_eq!(parse("OK=1\nBROKEN\n").unwrap_err().line, 2);
+		assert_eq!(
+			parse("A=\"open\n").unwrap_err().message,
+			"unterminated quoted value"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let result = resolve_str(
+			"A=1\n",
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(
+			result,
+			Err(FlatError::Resolve(Error::Resolver(MyError)))
+		));
+	}
+}
@@ -0,0 +1,216 @@
+/* src/dynamic.rs */
+
+//! Object-safe resolvers.
+//!
+//! [`Resolver`] returns `impl Future`, so it cannot be used as a trait
+//! object. [`DynResolver`] boxes the future and erases the error into
+//! [`DynError`]. Every [`Resolver`] whose error converts into
+//! `Box<dyn Error + Send + Sync>` is a `DynResolver`, and `dyn DynResolver`
+//! is itself a [`Resolver`], so it can be stored as `Box<dyn DynResolver>`,
+//! chosen at runtime and passed anywhere a resolver is accepted.
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+
+use crate::{Resolved, Resolver, Sourced};
+
+/// Owned future returned by [`DynResolver::resolve_boxed`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type BoxError = Box<dyn core::error::Error + Send + Sync>;
+
+/// Type-erased error of a [`DynResolver`].
+///
+/// Displays as, and reports the source of, the underlying error.
+pub struct DynError(BoxError);
+
+impl DynError {
+	/// Wrap an error, or a message such as a `String`.
+	#[inline]
+	pub fn new(error: impl Into<Box<dyn core::error::Error + Send + Sync>>) -> Self {
+		Self(error.into())
+	}
+
+	/// The underlying error, if it has type `T`.
+	#[inline]
+	#[must_use]
+	pub fn downcast_ref<T: core::error::Error + 'static>(&self) -> Option<&T> {
+		self.0.downcast_ref()
+	}
+
+	/// Unwrap the underlying error.
+	#[inline]
+	#[must_use]
+	pub fn into_inner(self) -> Box<dyn core::error::Error + Send + Sync> {
+		self.0
+	}
+}
+
+impl core::fmt::Debug for DynError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		core::fmt::Debug::fmt(&self.0, f)
+	}
+}
+
+impl core::fmt::Display for DynError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		core::fmt::Display::fmt(&self.0, f)
+	}
+}
+
+impl core::error::Error for DynError {
+	fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+		self.0.source()
+	}
+}
+
+/// Object-safe form of [`Resolver`].
+///
+/// Implemented for every [`Resolver`] whose error converts into
+/// `Box<dyn Error + Send + Sync>`, which covers all error types as well as
+/// `String` and `&str` messages.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "json")] {
+/// use serde_resolve::{json, Config, DynResolver, Resolved};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// fn pick(name: &str) -> Box<dyn DynResolver> {
+///     match name {
+///         "upper" => Box::new(|s: &str| {
+///             let upper = s.to_uppercase();
+///             async move { Ok::<_, std::convert::Infallible>(Resolved::changed(upper)) }
+///         }),
+///         _ => Box::new(|_: &str| async { Ok::<_, String>(Resolved::Unchanged) }),
+///     }
+/// }
+///
+/// let resolver = pick("upper");
+/// let value = json::resolve(serde_json::json!(["a"]), &*resolver, &Config::default()).await?;
+/// assert_eq!(value, serde_json::json!(["A"]));
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+pub trait DynResolver: Send + Sync {
+	/// Resolve a string value; see [`Resolver::resolve`].
+	fn resolve_boxed<'a>(&'a self, input: &'a str) -> BoxFuture<'a, Result<Resolved, DynError>>;
+
+	/// Resolve a string value along with its source; see
+	/// [`Resolver::resolve_sourced`].
+	fn resolve_sourced_boxed<'a>(
+		&'a self,
+		input: &'a str,
+	) -> BoxFuture<'a, Result<Sourced, DynError>>;
+}
+
+impl<R> DynResolver for R
+where
+	R: Resolver,
+	R::Error: Into<BoxError>,
+{
+	fn resolve_boxed<'a>(&'a self, input: &'a str) -> BoxFuture<'a, Result<Resolved, DynError>> {
+		Box::pin(async move { self.resolve(input).await.map_err(DynError::new) })
+	}
+
+	fn resolve_sourced_boxed<'a>(
+		&'a self,
+		input: &'a str,
+	) -> BoxFuture<'a, Result<Sourced, DynError>> {
+		Box::pin(async move { self.resolve_sourced(input).await.map_err(DynError::new) })
+	}
+}
+
+impl Resolver for dyn DynResolver + '_ {
+	type Error = DynError;
+
+	async fn resolve(&self, input: &str) -> Result<Resolved, DynError> {
+		self.resolve_boxed(input).await
+	}
+
+	async fn resolve_sourced(&self, input: &str) -> Result<Sourced, DynError> {
+		self.resolve_sourced_boxed(input).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::upper;
+	use alloc::string::{String, ToString};
+	use alloc::vec::Vec;
+
+	fn failing(s: &str) -> impl Future<Output = Result<Resolved, String>> + use<> {
+		let result = alloc::format!("cannot resolve {s}");
+		async move { Err(result) }
+	}
+
+	#[tokio::test]
+	async fn test_boxed_resolvers() {
+		let resolvers: Vec<Box<dyn DynResolver>> = alloc::vec![Box::new(upper), Box::new(failing)];
+
+		let resolved = resolvers[0].resolve_boxed("abc").await.unwrap();
+		assert_eq!(resolved, Resolved::changed("ABC"));
+
+		let sourced: Box<dyn DynResolver> = Box::new(|s: &str| {
+			let sourced = Resolved::changed(s).with_source("env:X");
+			async move { Ok::<_, String>(sourced) }
+		});
+		assert_eq!(
+			Resolver::resolve_sourced(&*sourced, "x").await.unwrap(),
+			Resolved::changed("x").with_source("env:X")
+		);
+		assert_eq!(
+			Resolver::resolve(&*sourced, "x").await.unwrap(),
+			Resolved::changed("x")
+		);
+
+		let err = Resolver::resolve(&*resolvers[1], "x").await.unwrap_err();
+		assert_eq!(err.to_string(), "cannot resolve x");
+		assert_eq!(alloc::format!("{err:?}"), r#""cannot resolve x""#);
+	}
+
+	#[cfg(feature = "std")]
+	#[tokio::test]
+	async fn test_downcast() {
+		let resolver: Box<dyn DynResolver> =
+			Box::new(|_: &str| async { Err::<Resolved, _>(std::io::Error::other("backend down")) });
+		let err = resolver.resolve_boxed("x").await.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<std::io::Error>().unwrap().kind(),
+			std::io::ErrorKind::Other
+		);
+		assert_eq!(err.into_inner().to_string(), "backend down");
+	}
+
+	#[cfg(feature = "json")]
+	#[tokio::test]
+	async fn test_json_and_struct() {
+		#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+		struct Conf {
+			name: String,
+		}
+
+		let resolver: Box<dyn DynResolver> = Box::new(upper);
+		let config = crate::Config::default();
+
+		let value = crate::json::resolve(serde_json::json!({ "a": ["b"] }), &*resolver, &config)
+			.await
+			.unwrap();
+		assert_eq!(value, serde_json::json!({ "a": ["B"] }));
+
+		let conf = crate::resolve_struct(Conf { name: "app".into() }, &*resolver, &config)
+			.await
+			.unwrap();
+		assert_eq!(conf.name, "APP");
+
+		let failing: &dyn DynResolver = &failing;
+		let err = crate::json::resolve(serde_json::json!("x"), failing, &config)
+			.await
+			.unwrap_err();
+		assert_eq!(err.to_string(), "resolver error: cannot resolve x");
+	}
+}
@@ -0,0 +1,477 @@
+/* src/file.rs */
+
+//! Resolver reading file contents.
+//!
+//! This module requires the `file` feature. The [`SyncResolver`] impl reads
+//! with blocking IO; the async [`Resolver`](crate::Resolver) impl reads
+//! through `tokio::fs` with the `tokio` feature and with blocking IO
+//! otherwise.
+//!
+//! [`FileResolver`] replaces strings such as `"file:/run/secrets/db_password"`
+//! with the contents of the named file. Reads are confined to the configured
+//! root directories: paths containing `..` are rejected outright, and the
+//! canonical path, with all symlinks followed, must lie inside a root.
+//!
+//! Relative paths are taken relative to the first root. Strings without the
+//! prefix are left unchanged.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "json")] {
+//! use serde_resolve::file::FileResolver;
+//! use serde_resolve::{json, Config};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = FileResolver::new("/run/secrets").max_size(4096);
+//!
+//! let value = json::resolve(
+//!     serde_json::json!({ "password": "file:/run/secrets/db_password" }),
+//!     &resolver,
+//!     &Config::default(),
+//! ).await?;
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{Resolved, Sourced, SyncResolver};
+
+/// Error type for [`FileResolver`].
+#[derive(Debug)]
+pub enum FileError {
+	/// The path contains `..` or resolves to a location outside every root.
+	OutsideRoots(PathBuf),
+	/// The file is larger than the configured limit.
+	TooLarge {
+		/// The file that was rejected.
+		path: PathBuf,
+		/// The configured limit in bytes.
+		limit: u64,
+	},
+	/// The file is not valid UTF-8.
+	NotUtf8(PathBuf),
+	/// Reading the file failed.
+	Io {
+		/// The file that could not be read.
+		path: PathBuf,
+		/// The underlying error.
+		source: std::io::Error,
+	},
+}
+
+impl core::fmt::Display for FileError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::OutsideRoots(path) => {
+				write!(f, "{} is outside the allowed roots", path.display())
+			}
+			Self::TooLarge { path, limit } => {
+				write!(
+					f,
+					"{} exceeds the size limit ({limit} bytes)",
+					path.display()
+				)
+			}
+			Self::NotUtf8(path) => write!(f, "{} is not valid UTF-8", path.display()),
+			Self::Io { path, source } => write!(f, "failed to read {}: {source}", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for FileError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io { source, .. } => Some(source),
+			Self::OutsideRoots(_) | Self::TooLarge { .. } | Self::NotUtf8(_) => None,
+		}
+	}
+}
+
+/// Resolver replacing prefixed file references with the file contents.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+	prefix: String,
+	roots: Vec<PathBuf>,
+	max_size: u64,
+	trim_newline: bool,
+	secret: bool,
+}
+
+impl FileResolver {
+	/// Create a resolver allowed to read files below `root`.
+	///
+	/// Defaults: prefix `file:`, 1 MiB size limit, trailing newlines trimmed
+	/// and contents returned as [`Resolved::Secret`].
+	#[must_use]
+	pub fn new(root: impl Into<PathBuf>) -> Self {
+		Self {
+			prefix: String::from("file:"),
+			roots: alloc::vec![root.into()],
+			max_size: 1024 * 1024,
+			trim_newline: true,
+			secret: true,
+		}
+	}
+
+	/// Allow reading files below another root directory.
+	#[inline]
+	#[must_use]
+	pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+		self.roots.push(root.into());
+		self
+	}
+
+	/// Set the prefix marking file references.
+	#[inline]
+	#[must_use]
+	pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+		self.prefix = prefix.into();
+		self
+	}
+
+	/// Set the maximum file size in bytes.
+	#[inline]
+	#[must_use]
+	pub const fn max_size(mut self, bytes: u64) -> Self {
+		self.max_size = bytes;
+		self
+	}
+
+	/// Set whether trailing `\n` and `\r\n` are removed from the contents.
+	#[inline]
+	#[must_use]
+	pub const fn trim_newline(mut self, trim: bool) -> Self {
+		self.trim_newline = trim;
+		self
+	}
+
+	/// Set whether contents are returned as [`Resolved::Secret`] rather than
+	/// [`Resolved::Changed`].
+	#[inline]
+	#[must_use]
+	pub const fn secret(mut self, secret: bool) -> Self {
+		self.secret = secret;
+		self
+	}
+
+	/// The referenced path, if `input` carries the prefix.
+	fn reference(&self, input: &str) -> Option<Result<PathBuf, FileError>> {
+		let raw = Path::new(input.strip_prefix(&self.prefix)?);
+		if raw.components().any(|c| matches!(c, Component::ParentDir)) {
+			return Some(Err(FileError::OutsideRoots(raw.to_path_buf())));
+		}
+		Some(Ok(self.locate(raw)))
+	}
+
+	/// Take a relative path relative to the first root.
+	pub(crate) fn locate(&self, path: &Path) -> PathBuf {
+		match self.roots.first() {
+			Some(root) if path.is_relative() => root.join(path),
+			_ => path.to_path_buf(),
+		}
+	}
+
+	/// Ensure the canonical form of `path` lies inside a root.
+	fn check_roots(&self, path: &Path, canonical: &Path) -> Result<(), FileError> {
+		let inside = self.roots.iter().any(|root| {
+			root
+				.canonicalize()
+				.is_ok_and(|root| canonical.starts_with(root))
+		});
+		if inside {
+			Ok(())
+		} else {
+			Err(FileError::OutsideRoots(path.to_path_buf()))
+		}
+	}
+
+	/// Check the size of raw file contents and decode them as UTF-8.
+	fn decode(&self, path: &Path, bytes: Vec<u8>) -> Result<String, FileError> {
+		if bytes.len() as u64 > self.max_size {
+			return Err(self.too_large(path));
+		}
+		String::from_utf8(bytes).map_err(|_| FileError::NotUtf8(path.to_path_buf()))
+	}
+
+	/// Turn decoded file contents into the resolved value.
+	fn finish(&self, path: &Path, mut contents: String) -> Result<Sourced, FileError> {
+		if self.trim_newline {
+			let len = contents.trim_end_matches(['\n', '\r']).len();
+			contents.truncate(len);
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::debug!(path = %path.display(), "read file");
+
+		let resolved = if self.secret {
+			Resolved::Secret(contents)
+		} else {
+			Resolved::Changed(contents)
+		};
+		Ok(resolved.with_source(alloc::format!("file:{}", path.display())))
+	}
+
+	fn too_large(&self, path: &Path) -> FileError {
+		FileError::TooLarge {
+			path: path.to_path_buf(),
+			limit: self.max_size,
+		}
+	}
+
+	/// Read a file with the root and size checks applied, returning its
+	/// canonical path and untrimmed contents.
+	pub(crate) fn read(&self, path: &Path) -> Result<(PathBuf, String), FileError> {
+		let io = |source| FileError::Io {
+			path: path.to_path_buf(),
+			source,
+		};
+		let canonical = path.canonicalize().map_err(io)?;
+		self.check_roots(path, &canonical)?;
+
+		let file = std::fs::File::open(&canonical).map_err(io)?;
+		if file.metadata().map_err(io)?.len() > self.max_size {
+			return Err(self.too_large(path));
+		}
+		// Guard against files growing after the size check
+		let mut bytes = Vec::new();
+		file
+			.take(self.max_size.saturating_add(1))
+			.read_to_end(&mut bytes)
+			.map_err(io)?;
+		let contents = self.decode(&canonical, bytes)?;
+		Ok((canonical, contents))
+	}
+
+	fn read_blocking(&self, path: &Path) -> Result<Sourced, FileError> {
+		let (canonical, contents) = self.read(path)?;
+		self.finish(&canonical, contents)
+	}
+
+	/// Async counterpart of [`read`](Self::read), using `tokio::fs` with the
+	/// `tokio` feature.
+	#[cfg(not(feature = "tokio"))]
+	#[inline]
+	pub(crate) async fn read_async(&self, path: &Path) -> Result<(PathBuf, String), FileError> {
+		self.read(path)
+	}
+
+	/// Async counterpart of [`read`](Self::read), using `tokio::fs` with the
+	/// `tokio` feature.
+	#[cfg(feature = "tokio")]
+	pub(crate) async fn read_async(&self, path: &Path) -> Result<(PathBuf, String), FileError> {
+		use tokio::io::AsyncReadExt;
+
+		let io = |source| FileError::Io {
+			path: path.to_path_buf(),
+			source,
+		};
+		let canonical = tokio::fs::canonicalize(path).await.map_err(io)?;
+		self.check_roots(path, &canonical)?;
+
+		let file = tokio::fs::File::open(&canonical).await.map_err(io)?;
+		if file.metadata().await.map_err(io)?.len() > self.max_size {
+			return Err(self.too_large(path));
+		}
+		let mut bytes = Vec::new();
+		file
+			.take(self.max_size.saturating_add(1))
+			.read_to_end(&mut bytes)
+			.await
+			.map_err(io)?;
+		let contents = self.decode(&canonical, bytes)?;
+		Ok((canonical, contents))
+	}
+}
+
+impl SyncResolver for FileResolver {
+	type Error = FileError;
+
+	fn resolve(&self, input: &str) -> Result<Resolved, FileError> {
+		match self.reference(input) {
+			Some(path) => Ok(self.read_blocking(&path?)?.resolved),
+			None => Ok(Resolved::Unchanged),
+		}
+	}
+}
+
+/// Reads through `tokio::fs` with the `tokio` feature, so it must then run
+/// inside a tokio runtime. Without it, files are read with blocking IO.
+impl crate::Resolver for FileResolver {
+	type Error = FileError;
+
+	async fn resolve(&self, input: &str) -> Result<Resolved, FileError> {
+		Ok(self.resolve_sourced(input).await?.resolved)
+	}
+
+	async fn resolve_sourced(&self, input: &str) -> Result<Sourced, FileError> {
+		match self.reference(input) {
+			Some(path) => {
+				let (canonical, contents) = self.read_async(&path?).await?;
+				self.finish(&canonical, contents)
+			}
+			None => Ok(Resolved::Unchanged.into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::TempDir;
+	use alloc::string::ToString;
+
+	fn read(resolver: &FileResolver, input: &str) -> Result<Resolved, FileError> {
+		SyncResolver::resolve(resolver, input)
+	}
+
+	#[test]
+	fn test_read_and_trim() {
+		let tmp = TempDir::new("file_read");
+		let dir = tmp.path();
+		std::fs::write(dir.join("password"), "hunter2\r\n").unwrap();
+		let resolver = FileResolver::new(dir);
+
+		let resolved = read(
+			&resolver,
+			&alloc::format!("file:{}", dir.join("password").display()),
+		)
+		.unwrap();
+		assert!(resolved.is_secret());
+		assert_eq!(resolved.into_changed().as_deref(), Some("hunter2"));
+
+		// Relative to the first root, without trimming or secrecy
+		let resolver = resolver.trim_newline(false).secret(false);
+		assert_eq!(
+			read(&resolver, "file:password").unwrap(),
+			Resolved::changed("hunter2\r\n")
+		);
+
+		assert_eq!(read(&resolver, "password").unwrap(), Resolved::Unchanged);
+	}
+
+	#[test]
+	fn test_custom_prefix() {
+		let tmp = TempDir::new("file_prefix");
+		let dir = tmp.path();
+		std::fs::write(dir.join("token"), "abc").unwrap();
+		let resolver = FileResolver::new(dir).prefix("@");
+
+		assert_eq!(
+			read(&resolver, "@token").unwrap().into_changed().as_deref(),
+			Some("abc")
+		);
+		assert_eq!(read(&resolver, "file:token").unwrap(), Resolved::Unchanged);
+	}
+
+	#[test]
+	fn test_rejects_escapes() {
+		let tmp = TempDir::new("file_escape");
+		let dir = tmp.path();
+		let inner = dir.join("inner");
+		std::fs::create_dir_all(&inner).unwrap();
+		std::fs::write(dir.join("outside"), "x").unwrap();
+		let resolver = FileResolver::new(&inner);
+
+		assert!(matches!(
+			read(&resolver, "file:../outside"),
+			Err(FileError::OutsideRoots(_))
+		));
+		assert!(matches!(
+			read(
+				&resolver,
+				&alloc::format!("file:{}", dir.join("outside").display())
+			),
+			Err(FileError::OutsideRoots(_))
+		));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_rejects_symlink_escape() {
+		let tmp = TempDir::new("file_symlink");
+		let dir = tmp.path();
+		let inner = dir.join("inner");
+		std::fs::create_dir_all(&inner).unwrap();
+		std::fs::write(dir.join("outside"), "x").unwrap();
+		std::os::unix::fs::symlink(dir.join("outside"), inner.join("link")).unwrap();
+
+		let err = read(&FileResolver::new(&inner), "file:link").unwrap_err();
+		assert!(matches!(err, FileError::OutsideRoots(_)));
+		assert!(err.to_string().ends_with("is outside the allowed roots"));
+	}
+
+	#[test]
+	fn test_size_limit() {
+		let tmp = TempDir::new("file_size");
+		let dir = tmp.path();
+		std::fs::write(dir.join("big"), "0123456789").unwrap();
+
+		let err = read(&FileResolver::new(dir).max_size(4), "file:big").unwrap_err();
+		assert!(matches!(err, FileError::TooLarge { limit: 4, .. }));
+		assert!(read(&FileResolver::new(dir).max_size(10), "file:big").is_ok());
+		assert!(read(&FileResolver::new(dir).max_size(u64::MAX), "file:big").is_ok());
+	}
+
+	#[test]
+	fn test_missing_and_invalid() {
+		let tmp = TempDir::new("file_invalid");
+		let dir = tmp.path();
+		std::fs::write(dir.join("binary"), [0xff, 0xfe]).unwrap();
+		let resolver = FileResolver::new(dir);
+
+		assert!(matches!(
+			read(&resolver, "file:missing"),
+			Err(FileError::Io { .. })
+		));
+		assert!(matches!(
+			read(&resolver, "file:binary"),
+			Err(FileError::NotUtf8(_))
+		));
+	}
+
+	#[cfg(feature = "json")]
+	#[tokio::test]
+	async fn test_async_resolver() {
+		let tmp = TempDir::new("file_async");
+		let dir = tmp.path();
+		std::fs::write(dir.join("user"), "admin\n").unwrap();
+		let value = crate::json::resolve(
+			serde_json::json!({ "user": "file:user", "other": "x" }),
+			&FileResolver::new(dir).secret(false),
+			&crate::Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(value, serde_json::json!({ "user": "admin", "other": "x" }));
+	}
+
+	#[tokio::test]
+	async fn test_async_io() {
+		use crate::Resolver;
+
+		let tmp = TempDir::new("file_async_io");
+		let dir = tmp.path();
+		std::fs::write(dir.join("user"), "admin\n").unwrap();
+		let resolver = FileResolver::new(dir);
+
+		let sourced = Resolver::resolve_sourced(&resolver, "file:user")
+			.await
+			.unwrap();
+		assert!(sourced.source.unwrap().ends_with("user"));
+		assert_eq!(sourced.resolved.into_changed().as_deref(), Some("admin"));
+		let unlimited = FileResolver::new(dir).max_size(u64::MAX);
+		assert!(Resolver::resolve(&unlimited, "file:user").await.is_ok());
+		assert!(matches!(
+			Resolver::resolve(&resolver, "file:../user").await,
+			Err(FileError::OutsideRoots(_))
+		));
+	}
+}
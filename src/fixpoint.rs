@@ -0,0 +1,305 @@
+/* src/fixpoint.rs */
+
+//! Re-resolution of changed strings; see [`Config::fixpoint`].
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Config, Error, REDACTED, Resolved, Resolver, Sourced, SyncResolver};
+
+/// Resolve one string, re-resolving it until it settles if enabled.
+pub(crate) async fn resolve_str<R>(
+	resolver: &R,
+	input: &str,
+	config: &Config,
+) -> Result<Resolved, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	Ok(
+		resolve_str_with(resolver, input, config, false)
+			.await?
+			.resolved,
+	)
+}
+
+/// Like [`resolve_str`], also reporting the first source the resolver gave.
+pub(crate) async fn resolve_str_sourced<R>(
+	resolver: &R,
+	input: &str,
+	config: &Config,
+) -> Result<Sourced, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	resolve_str_with(resolver, input, config, true).await
+}
+
+async fn resolve_str_with<R>(
+	resolver: &R,
+	input: &str,
+	config: &Config,
+	sourced: bool,
+) -> Result<Sourced, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let mut result = pass(resolver, input, sourced).await?;
+	let Some(limit) = config.fixpoint.filter(|_| result.resolved.is_changed()) else {
+		return Ok(result);
+	};
+	let mut fixpoint = Fixpoint::new(input, limit);
+	while let Some(next) = fixpoint.step(result)? {
+		result = pass(resolver, next, sourced).await?;
+	}
+	Ok(fixpoint.finish())
+}
+
+/// A single resolver call, asking for the source only if needed.
+async fn pass<R>(resolver: &R, input: &str, sourced: bool) -> Result<Sourced, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	if sourced {
+		resolver.resolve_sourced(input).await
+	} else {
+		resolver.resolve(input).await.map(Sourced::from)
+	}
+	.map_err(Error::resolver)
+}
+
+/// Blocking counterpart of [`resolve_str`].
+pub(crate) fn resolve_str_sync<R>(
+	resolver: &R,
+	input: &str,
+	config: &Config,
+) -> Result<Resolved, Error<R::Error>>
+where
+	R: SyncResolver + ?Sized,
+{
+	let mut resolved = resolver.resolve(input).map_err(Error::resolver)?;
+	let Some(limit) = config.fixpoint.filter(|_| resolved.is_changed()) else {
+		return Ok(resolved);
+	};
+	let mut fixpoint = Fixpoint::new(input, limit);
+	while let Some(next) = fixpoint.step(resolved.into())? {
+		resolved = resolver.resolve(next).map_err(Error::resolver)?;
+	}
+	Ok(fixpoint.finish().resolved)
+}
+
+/// The values a string went through while being re-resolved.
+pub(crate) struct Fixpoint {
+	/// The original string and each rewrite, with whether it derives from a
+	/// secret.
+	chain: Vec<(String, bool)>,
+	limit: usize,
+	/// The first source reported by the resolver.
+	source: Option<String>,
+	secret: bool,
+}
+
+impl Fixpoint {
+	pub(crate) fn new(input: &str, limit: usize) -> Self {
+		Self {
+			chain: alloc::vec![(input.to_owned(), false)],
+			limit,
+			source: None,
+			secret: false,
+		}
+	}
+
+	fn last(&self) -> &str {
+		self.chain.last().map_or("", |(value, _)| value)
+	}
+
+	/// Take the result of the latest pass, returning the next input if the
+	/// value changed.
+	pub(crate) fn step<E>(&mut self, result: Sourced) -> Result<Option<&str>, Error<E>> {
+		let Sourced { resolved, source } = result;
+		if self.source.is_none() {
+			self.source = source;
+		}
+		self.secret |= resolved.is_secret();
+		let Some(value) = resolved.into_changed() else {
+			return Ok(None);
+		};
+		// A resolver returning its input unchanged has settled, too
+		if value == self.last() {
+			return Ok(None);
+		}
+
+		let repeated = self.chain.iter().any(|(seen, _)| *seen == value);
+		self.chain.push((value, self.secret));
+		// The chain holds the input, the first result and one value per
+		// re-resolution that changed it
+		if repeated || self.chain.len() > self.limit + 2 {
+			let chain = self
+				.chain
+				.drain(..)
+				.map(|(value, secret)| if secret { REDACTED.into() } else { value })
+				.collect();
+			return Err(Error::Cycle { chain });
+		}
+		Ok(Some(self.last()))
+	}
+
+	/// The settled value.
+	pub(crate) fn finish(mut self) -> Sourced {
+		let resolved = if self.chain.len() == 1 {
+			Resolved::Unchanged
+		} else {
+			let value = self.chain.pop().map(|(value, _)| value).unwrap_or_default();
+			if self.secret {
+				Resolved::Secret(value)
+			} else {
+				Resolved::Changed(value)
+			}
+		};
+		Sourced {
+			resolved,
+			source: self.source,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use alloc::string::ToString;
+	use core::convert::Infallible;
+
+	/// Expands `${NAME}` one variable per pass.
+	fn expand(s: &str) -> Sourced {
+		let vars = [
+			("${BASE_URL}", "${SCHEME}://${HOST}"),
+			("${SCHEME}", "https"),
+			("${HOST}", "example.com"),
+			("${TOKEN}", "${KEY}"),
+			("${A}", "${B}"),
+			("${B}", "${A}"),
+		];
+		for (name, value) in vars {
+			if s.contains(name) {
+				let expanded = s.replacen(name, value, 1);
+				return match name {
+					"${TOKEN}" => Resolved::secret(expanded).into(),
+					"${BASE_URL}" => Resolved::changed(expanded).with_source("env:BASE_URL"),
+					_ => Resolved::changed(expanded).into(),
+				};
+			}
+		}
+		Resolved::Unchanged.into()
+	}
+
+	fn env(s: &str) -> Result<Resolved, Infallible> {
+		Ok(expand(s).resolved)
+	}
+
+	#[test]
+	fn test_disabled_by_default() {
+		let resolved = resolve_str_sync(&env, "${BASE_URL}/api", &Config::default()).unwrap();
+		assert_eq!(resolved, Resolved::changed("${SCHEME}://${HOST}/api"));
+	}
+
+	#[test]
+	fn test_settles() {
+		let config = Config::default().fixpoint(5);
+
+		let resolved = resolve_str_sync(&env, "${BASE_URL}/api", &config).unwrap();
+		assert_eq!(
+			resolved.into_changed().as_deref(),
+			Some("https://example.com/api")
+		);
+
+		assert_eq!(
+			resolve_str_sync(&env, "plain", &config).unwrap(),
+			Resolved::Unchanged
+		);
+		let same = |s: &str| Ok::<_, Infallible>(Resolved::changed(s));
+		assert_eq!(
+			resolve_str_sync(&same, "x", &config).unwrap(),
+			Resolved::Unchanged
+		);
+	}
+
+	#[test]
+	fn test_limit_zero_and_one() {
+		// With 0, a changed string is resolved once more only to check it
+		// settled
+		let resolved = resolve_str_sync(&env, "${HOST}", &Config::default().fixpoint(0)).unwrap();
+		assert_eq!(resolved, Resolved::changed("example.com"));
+		let err =
+			resolve_str_sync(&env, "${SCHEME}://${HOST}", &Config::default().fixpoint(0)).unwrap_err();
+		assert!(matches!(err, Error::Cycle { chain } if chain.len() == 3));
+
+		let resolved =
+			resolve_str_sync(&env, "${SCHEME}://${HOST}", &Config::default().fixpoint(1)).unwrap();
+		assert_eq!(resolved, Resolved::changed("https://example.com"));
+	}
+
+	#[test]
+	fn test_limit_and_cycle() {
+		let err = resolve_str_sync(&env, "${BASE_URL}", &Config::default().fixpoint(1)).unwrap_err();
+		let Error::Cycle { chain } = &err else {
+			panic!("expected cycle, got {err}");
+		};
+		assert_eq!(
+			chain,
+			&[
+				"${BASE_URL}",
+				"${SCHEME}://${HOST}",
+				"https://${HOST}",
+				"https://example.com"
+			]
+		);
+
+		let err = resolve_str_sync(&env, "${A}", &Config::default().fixpoint(10)).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			r#"resolution did not settle: "${A}" -> "${B}" -> "${A}""#
+		);
+	}
+
+	#[test]
+	fn test_secret_chain_redacted() {
+		let passes = |s: &str| {
+			Ok::<_, Infallible>(match s {
+				"${TOKEN}" => Resolved::secret("hunter2"),
+				"hunter2" => Resolved::changed("${TOKEN}"),
+				_ => Resolved::Unchanged,
+			})
+		};
+		let err = resolve_str_sync(&passes, "${TOKEN}", &Config::default().fixpoint(10)).unwrap_err();
+		let Error::Cycle { chain } = err else {
+			panic!("expected cycle");
+		};
+		assert_eq!(chain, ["${TOKEN}", REDACTED, REDACTED]);
+
+		let resolved = resolve_str_sync(&env, "${TOKEN}", &Config::default().fixpoint(10)).unwrap();
+		assert!(resolved.is_secret());
+	}
+
+	#[tokio::test]
+	async fn test_async() {
+		fn resolver(s: &str) -> impl Future<Output = Result<Sourced, Infallible>> + use<> {
+			let result = expand(s);
+			async move { Ok(result) }
+		}
+
+		let config = Config::default().fixpoint(3);
+		let resolved = resolve_str(&resolver, "${BASE_URL}", &config)
+			.await
+			.unwrap();
+		assert_eq!(resolved, Resolved::changed("https://example.com"));
+
+		let sourced = resolve_str_sourced(&resolver, "${BASE_URL}", &config)
+			.await
+			.unwrap();
+		assert_eq!(
+			sourced,
+			Resolved::changed("https://example.com").with_source("env:BASE_URL")
+		);
+	}
+}
@@ -0,0 +1,410 @@
+/* src/flat.rs */
+
+//! Shared representation for flat key/value files.
+//!
+//! [`Document`]s are produced by the `properties`, `ini` and `dotenv` modules.
+//! Every line is kept, so writing a document back through its
+//! [`Display`](core::fmt::Display) implementation preserves comments, section
+//! headers and ordering. Only entries whose key or value changed are re-encoded.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::deadline::Guard;
+use crate::{Config, Error, Path, PathSegment, Resolver};
+
+/// A parsed flat key/value file.
+#[derive(Debug, Clone)]
+pub struct Document {
+	lines: Vec<Line>,
+	encode: fn(&Entry, &mut String),
+}
+
+/// A logical line of a [`Document`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+	/// Blank line or comment, kept verbatim.
+	Other(String),
+	/// Section header such as `[database]`.
+	Section {
+		/// Section name without brackets.
+		name: String,
+		/// Original text, including the line terminator.
+		raw: String,
+	},
+	/// Key/value entry.
+	Entry(Entry),
+}
+
+/// A key/value entry of a [`Document`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	section: Option<String>,
+	key: String,
+	value: String,
+	raw: String,
+	changed: bool,
+	pub(crate) prefix: String,
+	pub(crate) separator: String,
+	pub(crate) suffix: String,
+	pub(crate) newline: String,
+	/// Quote character the value was written with, if any.
+	pub(crate) quote: Option<char>,
+}
+
+/// Error returned when a flat file cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+	/// One-based line number.
+	pub line: usize,
+	/// Description of the problem.
+	pub message: &'static str,
+}
+
+impl core::fmt::Display for ParseError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// Error type for resolving flat files from text.
+#[derive(Debug)]
+pub enum FlatError<E> {
+	/// The input could not be parsed.
+	Parse(ParseError),
+	/// Resolution error.
+	Resolve(Error<E>),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for FlatError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Parse(e) => write!(f, "parse error: {e}"),
+			Self::Resolve(e) => write!(f, "resolution error: {e}"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for FlatError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Parse(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+		}
+	}
+}
+
+impl Document {
+	pub(crate) fn new(lines: Vec<Line>, encode: fn(&Entry, &mut String)) -> Self {
+		Self { lines, encode }
+	}
+
+	/// All lines in file order.
+	#[inline]
+	#[must_use]
+	pub fn lines(&self) -> &[Line] {
+		&self.lines
+	}
+
+	/// Iterate over all entries in file order.
+	pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+		self.lines.iter().filter_map(|line| match line {
+			Line::Entry(entry) => Some(entry),
+			Line::Other(_) | Line::Section { .. } => None,
+		})
+	}
+
+	/// Iterate mutably over all entries in file order.
+	pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut Entry> {
+		self.lines.iter_mut().filter_map(|line| match line {
+			Line::Entry(entry) => Some(entry),
+			Line::Other(_) | Line::Section { .. } => None,
+		})
+	}
+
+	/// Value of the first entry named `key` outside of any section.
+	#[must_use]
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.get_in(None, key)
+	}
+
+	/// Value of the first entry named `key` in `section`.
+	///
+	/// `None` refers to entries before the first section header.
+	#[must_use]
+	pub fn get_in(&self, section: Option<&str>, key: &str) -> Option<&str> {
+		self
+			.entries()
+			.find(|e| e.section() == section && e.key() == key)
+			.map(Entry::value)
+	}
+}
+
+impl core::fmt::Display for Document {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		let mut buf = String::new();
+		for line in &self.lines {
+			match line {
+				Line::Other(raw) | Line::Section { raw, .. } => f.write_str(raw)?,
+				Line::Entry(entry) if entry.changed => {
+					buf.clear();
+					(self.encode)(entry, &mut buf);
+					f.write_str(&buf)?;
+				}
+				Line::Entry(entry) => f.write_str(&entry.raw)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Entry {
+	pub(crate) fn new(section: Option<String>, key: String, value: String, raw: String) -> Self {
+		Self {
+			section,
+			key,
+			value,
+			raw,
+			changed: false,
+			prefix: String::new(),
+			separator: String::new(),
+			suffix: String::new(),
+			newline: String::new(),
+			quote: None,
+		}
+	}
+
+	/// Section this entry belongs to, if any.
+	#[inline]
+	#[must_use]
+	pub fn section(&self) -> Option<&str> {
+		self.section.as_deref()
+	}
+
+	/// Decoded key.
+	#[inline]
+	#[must_use]
+	pub fn key(&self) -> &str {
+		&self.key
+	}
+
+	/// Decoded value.
+	#[inline]
+	#[must_use]
+	pub fn value(&self) -> &str {
+		&self.value
+	}
+
+	/// Replace the key; the entry is re-encoded when written.
+	pub fn set_key(&mut self, key: impl Into<String>) {
+		self.key = key.into();
+		self.changed = true;
+	}
+
+	/// Replace the value; the entry is re-encoded when written.
+	pub fn set_value(&mut self, value: impl Into<String>) {
+		self.value = value.into();
+		self.changed = true;
+	}
+
+	/// Returns `true` if the key or value was replaced since parsing.
+	#[inline]
+	#[must_use]
+	pub const fn is_changed(&self) -> bool {
+		self.changed
+	}
+}
+
+/// Resolve all values (and optionally keys) in a [`Document`].
+///
+/// Entries sit at depth 1, or depth 2 inside a section, for the purpose of
+/// [`Config::max_depth`].
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve<R>(
+	mut document: Document,
+	resolver: &R,
+	config: &Config,
+) -> Result<Document, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	if config.max_depth == 0 {
+		return Err(Error::depth_exceeded(config.max_depth));
+	}
+
+	let mut guard = Guard::new(config)?;
+	for entry in document.entries_mut() {
+		let depth = if entry.section.is_some() { 2 } else { 1 };
+		if depth >= config.max_depth {
+			return Err(Error::depth_exceeded(config.max_depth));
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::trace!(depth, section = ?entry.section, key = %entry.key, "resolving");
+
+		let mut path: Path = entry
+			.section
+			.iter()
+			.map(|section| PathSegment::Key(section.clone()))
+			.collect();
+		if config.resolve_keys
+			&& let Some(new_key) = guard
+				.run(
+					&path,
+					crate::fixpoint::resolve_str(resolver, &entry.key, config),
+				)
+				.await?
+				.into_changed()
+		{
+			entry.set_key(new_key);
+		}
+
+		path.push(PathSegment::Key(entry.key.clone()));
+		let resolved = guard
+			.run(
+				&path,
+				crate::fixpoint::resolve_str(resolver, &entry.value, config),
+			)
+			.await?;
+		#[cfg(feature = "tracing")]
+		crate::trace_resolved(&entry.value, &resolved);
+
+		if let Some(new_value) = resolved.into_changed() {
+			entry.set_value(new_value);
+		}
+	}
+
+	Ok(document)
+}
+
+/// Split `input` into physical lines, separating each line's terminator.
+pub(crate) fn physical_lines(input: &str) -> impl Iterator<Item = (&str, &str)> {
+	input.split_inclusive('\n').map(|line| {
+		let content = line.strip_suffix('\n').unwrap_or(line);
+		let content = content.strip_suffix('\r').unwrap_or(content);
+		(content, &line[content.len()..])
+	})
+}
+
+/// Split `text` into leading whitespace and the rest.
+pub(crate) fn split_indent(text: &str) -> (&str, &str) {
+	let rest = text.trim_start();
+	(&text[..text.len() - rest.len()], rest)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use alloc::borrow::ToOwned;
+	use alloc::string::ToString;
+	use alloc::vec;
+	use core::convert::Infallible;
+
+	fn encode_plain(entry: &Entry, out: &mut String) {
+		out.push_str(&entry.key);
+		out.push('=');
+		out.push_str(&entry.value);
+		out.push_str(&entry.newline);
+	}
+
+	fn document() -> Document {
+		let mut entry = Entry::new(None, "a".into(), "one".into(), "a = one\n".into());
+		entry.newline = "\n".into();
+		let mut nested = Entry::new(
+			Some("s".into()),
+			"b".into(),
+			"two".into(),
+			"b = two\n".into(),
+		);
+		nested.newline = "\n".into();
+		Document::new(
+			vec![
+				Line::Other("# comment\n".into()),
+				Line::Entry(entry),
+				Line::Section {
+					name: "s".into(),
+					raw: "[s]\n".into(),
+				},
+				Line::Entry(nested),
+			],
+			encode_plain,
+		)
+	}
+
+	#[tokio::test]
+	async fn test_resolve_values() {
+		let output = resolve(
+			document(),
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output.get("a"), Some("ONE"));
+		assert_eq!(output.get_in(Some("s"), "b"), Some("TWO"));
+		assert_eq!(output.to_string(), "# comment\na=ONE\n[s]\nb=TWO\n");
+	}
+
+	#[tokio::test]
+	async fn test_unchanged_kept_verbatim() {
+		let output = resolve(
+			document(),
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert!(output.entries().all(|e| !e.is_changed()));
+		assert_eq!(output.to_string(), "# comment\na = one\n[s]\nb = two\n");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_keys() {
+		let output = resolve(
+			document(),
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output.get("A"), Some("ONE"));
+		assert_eq!(output.get("a"), None);
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let result = resolve(
+			document(),
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&Config::default().max_depth(2),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 2 })));
+	}
+
+	#[test]
+	fn test_physical_lines() {
+		let lines: Vec<_> = physical_lines("a\r\nb\nc").collect();
+		assert_eq!(lines, vec![("a", "\r\n"), ("b", "\n"), ("c", "")]);
+	}
+}
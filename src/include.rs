@@ -0,0 +1,590 @@
+/* src/include.rs */
+
+//! Include directives splicing other files into a configuration tree.
+//!
+//! This module requires the `include` feature. Included YAML and TOML files
+//! additionally require the `yaml` and `toml` features.
+//!
+//! A string such as `"!include db.yaml"`, or a YAML value tagged
+//! `!include db.yaml`, is replaced by the parsed contents of that file. The
+//! format follows the extension (`.json`, `.yaml`/`.yml`, `.toml`) and the
+//! result is a [`serde_json::Value`] regardless of the source formats.
+//!
+//! Relative include paths are based on the directory of the including file.
+//! Files are read through a [`FileResolver`], so its roots and size limit
+//! apply to every include. Cycles are rejected, as is nesting deeper than
+//! [`Includer::max_depth`].
+//!
+//! [`Includer::read`] and [`Includer::expand`] use blocking IO;
+//! [`Includer::load`] reads through `tokio::fs` with the `tokio` feature and
+//! with blocking IO otherwise.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use serde_resolve::file::FileResolver;
+//! use serde_resolve::include::Includer;
+//! use serde_resolve::{Config, Resolved};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! // app.json: { "name": "app", "database": "!include db/main.yaml" }
+//! let includer = Includer::new(FileResolver::new("/etc/app"));
+//!
+//! let resolver = |s: &str| {
+//!     let result = s.strip_prefix("$").map(|var| Resolved::changed(var.to_uppercase()));
+//!     async move { Ok::<_, std::convert::Infallible>(result.unwrap_or(Resolved::Unchanged)) }
+//! };
+//! let value = includer.load("app.json", &resolver, &Config::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::Error;
+use crate::file::{FileError, FileResolver};
+use crate::{Config, Resolver};
+
+/// Error type for include expansion.
+#[derive(Debug)]
+pub enum IncludeError<E = Infallible> {
+	/// A file could not be read or is outside the allowed roots.
+	File(FileError),
+	/// A file is not valid in the format its extension names.
+	Parse {
+		/// The file that failed to parse.
+		path: PathBuf,
+		/// The parser error message.
+		message: String,
+	},
+	/// A file has no extension naming a supported format.
+	UnknownFormat(PathBuf),
+	/// A file includes itself, directly or indirectly.
+	///
+	/// Holds the include chain, ending with the repeated file.
+	Cycle(Vec<PathBuf>),
+	/// Includes are nested deeper than the configured limit.
+	DepthExceeded {
+		/// The configured limit that was exceeded.
+		limit: usize,
+	},
+	/// Resolving the expanded tree failed.
+	Resolve(Error<E>),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for IncludeError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::File(e) => write!(f, "include failed: {e}"),
+			Self::Parse { path, message } => {
+				write!(f, "failed to parse {}: {message}", path.display())
+			}
+			Self::UnknownFormat(path) => {
+				write!(f, "unsupported include format: {}", path.display())
+			}
+			Self::Cycle(chain) => {
+				f.write_str("include cycle: ")?;
+				for (i, path) in chain.iter().enumerate() {
+					if i > 0 {
+						f.write_str(" -> ")?;
+					}
+					write!(f, "{}", path.display())?;
+				}
+				Ok(())
+			}
+			Self::DepthExceeded { limit } => write!(f, "include depth limit ({limit}) exceeded"),
+			Self::Resolve(e) => write!(f, "{e}"),
+		}
+	}
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for IncludeError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::File(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+			Self::Parse { .. } | Self::UnknownFormat(_) | Self::Cycle(_) | Self::DepthExceeded { .. } => {
+				None
+			}
+		}
+	}
+}
+
+impl IncludeError {
+	/// Carry an expansion error over to a resolver error type.
+	fn widen<E>(self) -> IncludeError<E> {
+		match self {
+			Self::File(e) => IncludeError::File(e),
+			Self::Parse { path, message } => IncludeError::Parse { path, message },
+			Self::UnknownFormat(path) => IncludeError::UnknownFormat(path),
+			Self::Cycle(chain) => IncludeError::Cycle(chain),
+			Self::DepthExceeded { limit } => IncludeError::DepthExceeded { limit },
+			Self::Resolve(e) => IncludeError::Resolve(e.widen()),
+		}
+	}
+}
+
+impl<E> From<FileError> for IncludeError<E> {
+	fn from(e: FileError) -> Self {
+		Self::File(e)
+	}
+}
+
+/// Loader expanding include directives.
+///
+/// See the [module documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct Includer {
+	files: FileResolver,
+	directive: String,
+	max_depth: usize,
+}
+
+impl Includer {
+	/// Create an includer reading files through `files`.
+	///
+	/// Only the roots and size limit of `files` are used. Defaults: directive
+	/// `!include `, at most 8 nested includes.
+	#[must_use]
+	pub fn new(files: FileResolver) -> Self {
+		Self {
+			files,
+			directive: String::from("!include "),
+			max_depth: 8,
+		}
+	}
+
+	/// Set the prefix marking include strings. Default: `!include `.
+	///
+	/// YAML values tagged `!include` are recognized regardless.
+	#[inline]
+	#[must_use]
+	pub fn directive(mut self, directive: impl Into<String>) -> Self {
+		self.directive = directive.into();
+		self
+	}
+
+	/// Set the maximum include nesting depth. Default: 8.
+	///
+	/// A limit of 0 rejects every include.
+	#[inline]
+	#[must_use]
+	pub const fn max_depth(mut self, limit: usize) -> Self {
+		self.max_depth = limit;
+		self
+	}
+
+	/// Read a file and expand its includes, without resolving strings.
+	///
+	/// A relative `path` is taken relative to the first root.
+	///
+	/// # Errors
+	///
+	/// Returns an error if any file cannot be read or parsed, or on a cycle
+	/// or excessive nesting.
+	pub fn read(&self, path: impl AsRef<Path>) -> Result<Value, IncludeError> {
+		let path = normalize(&self.files.locate(path.as_ref()));
+		self.include(&path, &mut Vec::new())
+	}
+
+	/// Expand the includes in an in-memory tree.
+	///
+	/// Relative include paths are based on `base`, itself taken relative to
+	/// the first root when relative.
+	///
+	/// # Errors
+	///
+	/// Returns an error if any file cannot be read or parsed, or on a cycle
+	/// or excessive nesting.
+	pub fn expand(&self, mut value: Value, base: impl AsRef<Path>) -> Result<Value, IncludeError> {
+		let base = normalize(&self.files.locate(base.as_ref()));
+		self.walk(&mut value, &base, &mut Vec::new())?;
+		Ok(value)
+	}
+
+	/// Read a file, expand its includes and resolve the resulting tree.
+	///
+	/// Reads through `tokio::fs` with the `tokio` feature, so it must then
+	/// run inside a tokio runtime. Without it, files are read with blocking
+	/// IO.
+	///
+	/// # Errors
+	///
+	/// Returns an error if expansion fails as for [`read`](Self::read), or
+	/// if resolution fails.
+	pub async fn load<R>(
+		&self,
+		path: impl AsRef<Path>,
+		resolver: &R,
+		config: &Config,
+	) -> Result<Value, IncludeError<R::Error>>
+	where
+		R: Resolver + ?Sized,
+	{
+		let path = normalize(&self.files.locate(path.as_ref()));
+		let value = self
+			.include_async(&path, &mut Vec::new())
+			.await
+			.map_err(IncludeError::widen)?;
+		crate::resolve(value, resolver, config)
+			.await
+			.map_err(IncludeError::Resolve)
+	}
+
+	/// Read, parse and expand one file. `stack` holds the including files.
+	fn include(&self, path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, IncludeError> {
+		self.check_depth(stack)?;
+		let (canonical, text) = self.files.read(path)?;
+		let (mut value, base) = self.enter(canonical, &text, stack)?;
+		self.walk(&mut value, &base, stack)?;
+		stack.pop();
+		Ok(value)
+	}
+
+	/// Async counterpart of [`include`](Self::include).
+	fn include_async<'a>(
+		&'a self,
+		path: &'a Path,
+		stack: &'a mut Vec<PathBuf>,
+	) -> crate::BoxFuture<'a, Result<Value, IncludeError>> {
+		alloc::boxed::Box::pin(async move {
+			self.check_depth(stack)?;
+			let (canonical, text) = self.files.read_async(path).await?;
+			let (mut value, base) = self.enter(canonical, &text, stack)?;
+			self.walk_async(&mut value, &base, stack).await?;
+			stack.pop();
+			Ok(value)
+		})
+	}
+
+	fn check_depth(&self, stack: &[PathBuf]) -> Result<(), IncludeError> {
+		if stack.len() > self.max_depth {
+			return Err(IncludeError::DepthExceeded {
+				limit: self.max_depth,
+			});
+		}
+		Ok(())
+	}
+
+	/// Parse a file just read and push it onto `stack`, returning its value
+	/// and the directory its includes are relative to.
+	fn enter(
+		&self,
+		canonical: PathBuf,
+		text: &str,
+		stack: &mut Vec<PathBuf>,
+	) -> Result<(Value, PathBuf), IncludeError> {
+		if stack.contains(&canonical) {
+			let mut chain = core::mem::take(stack);
+			chain.push(canonical);
+			return Err(IncludeError::Cycle(chain));
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::debug!(path = %canonical.display(), depth = stack.len(), "including file");
+
+		let value = self.parse(&canonical, text)?;
+		let base = canonical
+			.parent()
+			.map(Path::to_path_buf)
+			.unwrap_or_default();
+		stack.push(canonical);
+		Ok((value, base))
+	}
+
+	/// The file an include string points to.
+	fn target(&self, s: &str, base: &Path) -> Option<PathBuf> {
+		let target = s.strip_prefix(self.directive.as_str())?;
+		Some(normalize(&base.join(target.trim())))
+	}
+
+	fn walk(
+		&self,
+		value: &mut Value,
+		base: &Path,
+		stack: &mut Vec<PathBuf>,
+	) -> Result<(), IncludeError> {
+		match value {
+			Value::String(s) => {
+				if let Some(path) = self.target(s, base) {
+					*value = self.include(&path, stack)?;
+				}
+			}
+			Value::Array(items) => {
+				for item in items {
+					self.walk(item, base, stack)?;
+				}
+			}
+			Value::Object(map) => {
+				for item in map.values_mut() {
+					self.walk(item, base, stack)?;
+				}
+			}
+			Value::Null | Value::Bool(_) | Value::Number(_) => {}
+		}
+		Ok(())
+	}
+
+	/// Async counterpart of [`walk`](Self::walk).
+	fn walk_async<'a>(
+		&'a self,
+		value: &'a mut Value,
+		base: &'a Path,
+		stack: &'a mut Vec<PathBuf>,
+	) -> crate::BoxFuture<'a, Result<(), IncludeError>> {
+		alloc::boxed::Box::pin(async move {
+			match value {
+				Value::String(s) => {
+					if let Some(path) = self.target(s, base) {
+						*value = self.include_async(&path, stack).await?;
+					}
+				}
+				Value::Array(items) => {
+					for item in items {
+						self.walk_async(item, base, stack).await?;
+					}
+				}
+				Value::Object(map) => {
+					for item in map.values_mut() {
+						self.walk_async(item, base, stack).await?;
+					}
+				}
+				Value::Null | Value::Bool(_) | Value::Number(_) => {}
+			}
+			Ok(())
+		})
+	}
+
+	// Only YAML tags need the directive
+	#[cfg_attr(not(feature = "yaml"), allow(clippy::unused_self))]
+	fn parse(&self, path: &Path, text: &str) -> Result<Value, IncludeError> {
+		let parse_error = |message: String| IncludeError::Parse {
+			path: path.to_path_buf(),
+			message,
+		};
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("json") => serde_json::from_str(text).map_err(|e| parse_error(e.to_string())),
+			#[cfg(feature = "yaml")]
+			Some("yaml" | "yml") => {
+				let mut value: serde_yaml::Value =
+					serde_yaml::from_str(text).map_err(|e| parse_error(e.to_string()))?;
+				self.untag(&mut value);
+				serde_json::to_value(value).map_err(|e| parse_error(e.to_string()))
+			}
+			#[cfg(feature = "toml")]
+			Some("toml") => toml::from_str(text).map_err(|e| parse_error(e.to_string())),
+			_ => Err(IncludeError::UnknownFormat(path.to_path_buf())),
+		}
+	}
+
+	/// Rewrite YAML `!include` tags into directive strings.
+	#[cfg(feature = "yaml")]
+	fn untag(&self, value: &mut serde_yaml::Value) {
+		use serde_yaml::Value as Yaml;
+
+		match value {
+			Yaml::Tagged(tagged) => {
+				if let (true, Yaml::String(target)) = (tagged.tag == "!include", &tagged.value) {
+					*value = Yaml::String(alloc::format!("{}{target}", self.directive));
+				} else {
+					self.untag(&mut tagged.value);
+				}
+			}
+			Yaml::Sequence(items) => {
+				for item in items {
+					self.untag(item);
+				}
+			}
+			Yaml::Mapping(map) => {
+				for (_, item) in map.iter_mut() {
+					self.untag(item);
+				}
+			}
+			Yaml::Null | Yaml::Bool(_) | Yaml::Number(_) | Yaml::String(_) => {}
+		}
+	}
+}
+
+/// Remove `.` and `..` components without touching the filesystem, so the
+/// root check sees where an include really points.
+fn normalize(path: &Path) -> PathBuf {
+	let mut out = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				out.pop();
+			}
+			other => out.push(other),
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::TempDir;
+	use alloc::string::ToString;
+	use serde_json::json;
+
+	fn write(dir: &Path, name: &str, contents: &str) {
+		let path = dir.join(name);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, contents).unwrap();
+	}
+
+	#[test]
+	fn test_nested_relative_includes() {
+		let tmp = TempDir::new("include_nested");
+		let dir = tmp.path();
+		write(
+			dir,
+			"app.json",
+			r#"{ "name": "app", "db": "!include db/main.json" }"#,
+		);
+		write(
+			dir,
+			"db/main.json",
+			r#"{ "host": "localhost", "pool": "!include pool.json" }"#,
+		);
+		write(dir, "db/pool.json", r#"[1, 2, "!include ../size.json"]"#);
+		write(dir, "size.json", "10");
+
+		let value = Includer::new(FileResolver::new(dir))
+			.read("app.json")
+			.unwrap();
+		assert_eq!(
+			value,
+			json!({ "name": "app", "db": { "host": "localhost", "pool": [1, 2, 10] } })
+		);
+	}
+
+	#[cfg(all(feature = "yaml", feature = "toml"))]
+	#[test]
+	fn test_mixed_formats() {
+		let tmp = TempDir::new("include_formats");
+		let dir = tmp.path();
+		write(
+			dir,
+			"app.yaml",
+			"server: !include server.toml\nplain: \"!include list.yml\"\n",
+		);
+		write(dir, "server.toml", "port = 8080\nhost = \"0.0.0.0\"\n");
+		write(dir, "list.yml", "- a\n- !other b\n");
+
+		let value = Includer::new(FileResolver::new(dir))
+			.read("app.yaml")
+			.unwrap();
+		assert_eq!(
+			value,
+			json!({
+				"server": { "port": 8080, "host": "0.0.0.0" },
+				"plain": ["a", { "!other": "b" }],
+			})
+		);
+	}
+
+	#[test]
+	fn test_cycle_and_depth() {
+		let tmp = TempDir::new("include_cycle");
+		let dir = tmp.path();
+		write(dir, "a.json", r#"{ "b": "!include b.json" }"#);
+		write(dir, "b.json", r#"{ "a": "!include ./a.json" }"#);
+		let includer = Includer::new(FileResolver::new(dir));
+
+		let err = includer.read("a.json").unwrap_err();
+		let IncludeError::Cycle(chain) = &err else {
+			panic!("expected cycle, got {err}");
+		};
+		assert_eq!(chain.len(), 3);
+		assert_eq!(chain[0], chain[2]);
+		assert!(err.to_string().starts_with("include cycle: "));
+
+		write(dir, "c.json", r#"{ "d": "!include d.json" }"#);
+		write(dir, "d.json", "1");
+		assert_eq!(
+			includer.clone().max_depth(1).read("c.json").unwrap(),
+			json!({ "d": 1 })
+		);
+		assert!(matches!(
+			includer.max_depth(0).read("c.json"),
+			Err(IncludeError::DepthExceeded { limit: 0 })
+		));
+	}
+
+	#[test]
+	fn test_sandbox_and_errors() {
+		let tmp = TempDir::new("include_sandbox");
+		let dir = tmp.path();
+		write(dir, "outside.json", "1");
+		write(dir, "root/escape.json", r#"["!include ../outside.json"]"#);
+		write(dir, "root/broken.json", "{");
+		write(dir, "root/notes.txt", "hi");
+		let includer = Includer::new(FileResolver::new(dir.join("root")));
+
+		assert!(matches!(
+			includer.read("escape.json"),
+			Err(IncludeError::File(FileError::OutsideRoots(_)))
+		));
+		assert!(matches!(
+			includer.read("broken.json"),
+			Err(IncludeError::Parse { .. })
+		));
+		assert!(matches!(
+			includer.read("notes.txt"),
+			Err(IncludeError::UnknownFormat(_))
+		));
+	}
+
+	#[test]
+	fn test_expand_in_memory() {
+		let tmp = TempDir::new("include_expand");
+		let dir = tmp.path();
+		write(dir, "conf/part.json", r#"{ "x": 1 }"#);
+		let includer = Includer::new(FileResolver::new(dir)).directive("@include:");
+
+		let value = includer
+			.expand(
+				json!({ "part": "@include: part.json", "keep": "!include part.json" }),
+				"conf",
+			)
+			.unwrap();
+		assert_eq!(
+			value,
+			json!({ "part": { "x": 1 }, "keep": "!include part.json" })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_load_resolves_included_strings() {
+		let tmp = TempDir::new("include_load");
+		let dir = tmp.path();
+		write(
+			dir,
+			"app.json",
+			r#"{ "db": "!include db.json", "mode": "$mode" }"#,
+		);
+		write(dir, "db.json", r#"{ "host": "$host" }"#);
+
+		use crate::Resolved;
+
+		fn resolver(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+			let result = s.strip_prefix('$').map_or(Resolved::Unchanged, |var| {
+				Resolved::changed(var.to_uppercase())
+			});
+			async move { Ok(result) }
+		}
+
+		let value = Includer::new(FileResolver::new(dir))
+			.load("app.json", &resolver, &Config::default())
+			.await
+			.unwrap();
+		assert_eq!(value, json!({ "db": { "host": "HOST" }, "mode": "MODE" }));
+	}
+}
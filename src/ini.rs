@@ -0,0 +1,193 @@
+/* src/ini.rs */
+
+//! INI file support.
+//!
+//! Supports `[section]` headers, `key = value` and `key: value` entries and
+//! whole-line `;` or `#` comments. Values are taken verbatim after trimming;
+//! no quote or escape processing is applied.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::flat::{self, Document, Entry, FlatError, Line, ParseError};
+use crate::{Config, Resolver};
+
+/// Parse an INI file into a [`Document`].
+///
+/// Entries before the first section header have no section.
+///
+/// # Errors
+///
+/// Returns an error if a section header is not closed or an entry has no
+/// `=` or `:` separator.
+pub fn parse(input: &str) -> Result<Document, ParseError> {
+	let mut lines = Vec::new();
+	let mut section: Option<String> = None;
+
+	for (index, (content, newline)) in flat::physical_lines(input).enumerate() {
+		let (indent, rest) = flat::split_indent(content);
+		let raw = alloc::format!("{content}{newline}");
+
+		if rest.is_empty() || rest.starts_with([';', '#']) {
+			lines.push(Line::Other(raw));
+			continue;
+		}
+
+		if let Some(header) = rest.strip_prefix('[') {
+			let Some(end) = header.find(']') else {
+				return Err(ParseError {
+					line: index + 1,
+					message: "unterminated section header",
+				});
+			};
+			let name = String::from(header[..end].trim());
+			section = Some(name.clone());
+			lines.push(Line::Section { name, raw });
+			continue;
+		}
+
+		let Some(sep) = rest.find(['=', ':']) else {
+			return Err(ParseError {
+				line: index + 1,
+				message: "expected `=` or `:`",
+			});
+		};
+		let key = rest[..sep].trim_end();
+		let (ws_after, value_text) = flat::split_indent(&rest[sep + 1..]);
+		let value = value_text.trim_end();
+
+		let mut entry = Entry::new(section.clone(), String::from(key), String::from(value), raw);
+		entry.prefix = String::from(indent);
+		entry.separator = String::from(&rest[key.len()..sep + 1 + ws_after.len()]);
+		entry.suffix = String::from(&value_text[value.len()..]);
+		entry.newline = String::from(newline);
+		lines.push(Line::Entry(entry));
+	}
+
+	Ok(Document::new(lines, encode))
+}
+
+/// Parse, resolve and re-serialize an INI file.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input cannot be parsed
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_str<R>(
+	input: &str,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, FlatError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let document = parse(input).map_err(FlatError::Parse)?;
+	let resolved = flat::resolve(document, resolver, config)
+		.await
+		.map_err(FlatError::Resolve)?;
+	Ok(alloc::string::ToString::to_string(&resolved))
+}
+
+fn encode(entry: &Entry, out: &mut String) {
+	out.push_str(&entry.prefix);
+	out.push_str(entry.key());
+	out.push_str(&entry.separator);
+	out.push_str(entry.value());
+	out.push_str(&entry.suffix);
+	out.push_str(&entry.newline);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, Resolved};
+	use alloc::borrow::ToOwned;
+	use alloc::string::ToString;
+	use core::convert::Infallible;
+
+	const INPUT: &str = "; global settings\r\n\
+		name = {{APP}}\r\n\
+		\r\n\
+		[database]\r\n\
+		# connection\r\n\
+		host: {{DB_HOST}}\r\n\
+		port=5432\r\n";
+
+	fn expand(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+		let resolved = s
+			.replace("{{APP}}", "billing")
+			.replace("{{DB_HOST}}", "db.internal");
+		let changed = resolved != s;
+		async move {
+			Ok(if changed {
+				Resolved::changed(resolved)
+			} else {
+				Resolved::unchanged()
+			})
+		}
+	}
+
+	#[test]
+	fn test_parse() {
+		let doc = parse(INPUT).unwrap();
+		assert_eq!(doc.get("name"), Some("{{APP}}"));
+		assert_eq!(doc.get_in(Some("database"), "host"), Some("{{DB_HOST}}"));
+		assert_eq!(doc.get_in(Some("database"), "port"), Some("5432"));
+		assert_eq!(doc.get("port"), None);
+		assert_eq!(doc.to_string(), INPUT);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_str() {
+		let output = resolve_str(INPUT, &expand, &Config::default())
+			.await
+			.unwrap();
+
+		let expected = "; global settings\r\n\
+			name = billing\r\n\
+			\r\n\
+			[database]\r\n\
+			# connection\r\n\
+			host: db.internal\r\n\
+			port=5432\r\n";
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_keys() {
+		let output = resolve_str(
+			"[s]\nkey = value\n",
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "[s]\nKEY = VALUE\n");
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let result = resolve_str(
+			"[s]\nkey = value\n",
+			&expand,
+			&Config::default().max_depth(2),
+		)
+		.await;
+		assert!(matches!(
+			result,
+			Err(FlatError::Resolve(Error::DepthExceeded { limit: 2 }))
+		));
+	}
+
+	#[test]
+	fn test_parse_errors() {
+		assert_eq!(parse("[open\n").unwrap_err().line, 1);
+		assert_eq!(parse("a = 1\nnot an entry\n").unwrap_err().line, 2);
+	}
+}
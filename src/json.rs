@@ -4,11 +4,17 @@
 //!
 //! This module is available with the `json` feature and supports `no_std` environments.
 
-#[cfg(feature = "tracing")]
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::convert::Infallible;
 use serde_json::{Map, Value};
 
-use crate::{Config, Error, Resolver};
+use crate::{BatchResolver, Config, Error, ResolvableValue, Resolver, ScalarResolver, ValueKind};
+
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "stream")]
+pub mod stream;
 
 /// Resolve all strings in a JSON [`Value`].
 ///
@@ -54,50 +60,109 @@ pub async fn resolve<R>(
 	config: &Config,
 ) -> Result<Value, Error<R::Error>>
 where
-	R: Resolver,
+	R: Resolver + ?Sized,
 {
-	#[cfg(feature = "tracing")]
-	let mut path = Vec::new();
-
-	resolve_recursive(
-		value,
-		resolver,
-		config,
-		0,
-		#[cfg(feature = "tracing")]
-		&mut path,
-	)
-	.await
+	crate::resolve(value, resolver, config).await
 }
 
-impl_resolve_recursive!(
-		Value,
-		Value::String,
-		Value::Array,
-		Value::Object,
-		Map::with_capacity,
-		|k: &alloc::string::String| k.clone(),
-		resolver, config, depth, path, key,
-		{
-				match resolver.resolve(&key).await.map_err(crate::Error::resolver)? {
-						crate::Resolved::Changed(new_key) => new_key,
-						crate::Resolved::Unchanged => key,
-				}
-		},
-		{
-				other @ (Value::Null | Value::Bool(_) | Value::Number(_)) => Ok(other),
+/// Resolve all strings in a JSON [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+/// Resolve all strings in a JSON [`Value`], and all other scalars with
+/// `scalars`.
+///
+/// Numbers, booleans and nulls are passed to `scalars` with their path.
+/// See [`crate::resolve_with_scalars`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_with_scalars`].
+pub async fn resolve_with_scalars<R, S>(
+	value: Value,
+	resolver: &R,
+	scalars: &S,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+	S: ScalarResolver<Value> + ?Sized,
+	S::Error: Into<R::Error>,
+{
+	crate::resolve_with_scalars(value, resolver, scalars, config).await
+}
+
+impl ResolvableValue for Value {
+	type Key = String;
+	type Tag = Infallible;
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::String(s) => ValueKind::String(s),
+			Self::Array(arr) => ValueKind::Array(arr),
+			Self::Object(map) => ValueKind::Map(map.into_iter().collect()),
+			other @ (Self::Null | Self::Bool(_) | Self::Number(_)) => ValueKind::Other(other),
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::String(s)
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Array(items)
+	}
+
+	fn from_map(entries: Vec<(String, Self)>) -> Self {
+		Self::Object(entries.into_iter().collect::<Map<_, _>>())
+	}
+
+	fn from_tagged(tag: Infallible, _value: Self) -> Self {
+		match tag {}
+	}
+
+	#[inline]
+	fn key_into_value(key: String) -> Result<Self, String> {
+		Ok(Self::String(key))
+	}
+
+	fn key_from_value(value: Self) -> String {
+		match value {
+			Self::String(s) => s,
+			other => other.to_string(),
+		}
+	}
+
+	#[inline]
+	fn key_to_string(key: &String) -> String {
+		key.clone()
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::Null => "null",
+			Self::Bool(_) => "bool",
+			Self::Number(_) => "number",
+			Self::String(_) => "string",
+			Self::Array(_) => "array",
+			Self::Object(_) => "object",
 		}
-);
-
-#[cfg(feature = "tracing")]
-fn value_type_name(value: &Value) -> &'static str {
-	match value {
-		Value::Null => "null",
-		Value::Bool(_) => "bool",
-		Value::Number(_) => "number",
-		Value::String(_) => "string",
-		Value::Array(_) => "array",
-		Value::Object(_) => "object",
 	}
 }
 
@@ -105,7 +170,9 @@ fn value_type_name(value: &Value) -> &'static str {
 mod tests {
 	use super::*;
 	use crate::Resolved;
+	use alloc::borrow::ToOwned;
 	use alloc::string::ToString;
+	use alloc::vec;
 	use core::convert::Infallible;
 
 	#[tokio::test]
@@ -317,6 +384,36 @@ mod tests {
 		assert!(matches!(result, Err(Error::DepthExceeded { limit: 0 })));
 	}
 
+	#[tokio::test]
+	async fn test_fixpoint() {
+		let resolver = |s: &str| {
+			let result = match s {
+				"${URL}" => Resolved::changed("${SCHEME}://${HOST}/api"),
+				_ if s.contains("${SCHEME}") => Resolved::changed(s.replace("${SCHEME}", "https")),
+				_ if s.contains("${HOST}") => Resolved::changed(s.replace("${HOST}", "example.com")),
+				_ if s.contains("${LOOP}") => Resolved::changed(s.replace("${LOOP}", "[${LOOP}]")),
+				_ => Resolved::Unchanged,
+			};
+			async move { Ok::<_, Infallible>(result) }
+		};
+		let config = Config::default().fixpoint(8);
+
+		let result = resolve(
+			serde_json::json!({ "api": ["${URL}", "${HOST}"] }),
+			&resolver,
+			&config,
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			result,
+			serde_json::json!({ "api": ["https://example.com/api", "example.com"] })
+		);
+
+		let result = resolve(serde_json::json!({ "a": "${LOOP}" }), &resolver, &config).await;
+		assert!(matches!(result, Err(Error::Cycle { chain }) if chain.len() == 11));
+	}
+
 	#[tokio::test]
 	async fn test_nested_arrays() {
 		let input = serde_json::json!([["a", "b"], ["c", "d"]]);
@@ -333,4 +430,33 @@ mod tests {
 
 		assert_eq!(output, serde_json::json!([["A", "B"], ["C", "D"]]));
 	}
-}
\ No newline at end of file
+
+	#[tokio::test]
+	async fn test_resolve_with_scalars() {
+		let input = serde_json::json!({ "timeout_ms": 1500, "debug": null, "tags": [true, "x"] });
+		let scalars = |path: &crate::Path, value: &Value| {
+			let replaced = match (path.to_string().as_str(), value) {
+				("timeout_ms", Value::Number(n)) => n.as_u64().map(|ms| Value::from(ms / 1000)),
+				("debug", Value::Null) => Some(Value::Bool(false)),
+				_ => None,
+			};
+			async move { Ok::<_, Infallible>(replaced) }
+		};
+
+		let output = resolve_with_scalars(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&scalars,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			output,
+			serde_json::json!({ "timeout_ms": 1, "debug": false, "tags": [true, "X"] })
+		);
+	}
+}
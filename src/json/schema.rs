@@ -0,0 +1,853 @@
+/* src/json/schema.rs */
+
+//! JSON Schema guided resolution.
+//!
+//! This module requires the `schema` feature. Deriving schemas from Rust types
+//! additionally requires the `schemars` feature.
+//!
+//! Only strings whose schema sets [`KEYWORD`] (`"x-resolve": true`) are passed
+//! to the resolver. The keyword is inherited, so marking an object or array
+//! makes everything below it templatable until a nested schema sets
+//! `"x-resolve": false`. When several schemas apply to a value, the nearest
+//! ones setting the keyword decide, and `false` wins among them: a `$ref`
+//! target or an `allOf`, `anyOf` or `oneOf` branch is further away than the
+//! schema naming it.
+//!
+//! When [`Config::coercion`] is enabled, a resolved string whose schema
+//! declares a `type` other than `"string"` is converted into that type, e.g.
+//! `"8080"` into `8080` for `"type": "integer"`. The final value is then
+//! validated against the whole schema.
+//!
+//! Subschemas are found through `properties`, `patternProperties`,
+//! `additionalProperties`, `items`, `prefixItems`, `additionalItems`, local
+//! `$ref`s and the branches of `allOf`, `anyOf` and `oneOf`. Map keys are never
+//! resolved.
+//!
+//! # Example
+//!
+//! ```rust
+//! use serde_json::json;
+//! use serde_resolve::json::schema::{self, Schema};
+//! use serde_resolve::{Coercion, Config, Resolved};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let schema = Schema::new(json!({
+//!     "type": "object",
+//!     "properties": {
+//!         "port": { "type": "integer", "x-resolve": true },
+//!         "name": { "type": "string" }
+//!     }
+//! }))?;
+//!
+//! let value = schema::resolve(
+//!     json!({ "port": "${PORT}", "name": "${NAME}" }),
+//!     &schema,
+//!     &|_: &str| async { Ok::<_, std::convert::Infallible>(Resolved::changed("8080")) },
+//!     &Config::default().coercion(Coercion::Strict),
+//! ).await?;
+//!
+//! assert_eq!(value, json!({ "port": 8080, "name": "${NAME}" }));
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use fancy_regex::Regex;
+use serde_json::{Map, Number, Value};
+
+use crate::de::{Scalar, Target};
+use crate::deadline::Guard;
+use crate::{Config, Error, Path, PathSegment, REDACTED, Resolver};
+
+/// Schema keyword marking strings as templatable.
+pub const KEYWORD: &str = "x-resolve";
+
+/// A compiled JSON Schema.
+#[derive(Debug)]
+pub struct Schema {
+	root: Value,
+	validator: jsonschema::Validator,
+	/// Compiled `patternProperties` patterns, by source.
+	patterns: BTreeMap<String, Regex>,
+}
+
+impl Schema {
+	/// Compile a JSON Schema document.
+	///
+	/// # Errors
+	///
+	/// Returns an error if `schema` is not a valid JSON Schema.
+	pub fn new(schema: Value) -> Result<Self, InvalidSchema> {
+		let validator = jsonschema::validator_for(&schema).map_err(|e| InvalidSchema {
+			message: e.to_string(),
+		})?;
+		let mut patterns = BTreeMap::new();
+		collect_patterns(&schema, &mut patterns);
+		Ok(Self {
+			root: schema,
+			validator,
+			patterns,
+		})
+	}
+
+	/// Derive the schema of `T` via `schemars`.
+	///
+	/// Mark fields as templatable with
+	/// `#[schemars(extend("x-resolve" = true))]`.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the generated schema does not compile.
+	#[cfg(feature = "schemars")]
+	pub fn for_type<T: schemars::JsonSchema>() -> Result<Self, InvalidSchema> {
+		Self::new(schemars::schema_for!(T).to_value())
+	}
+
+	/// The schema document.
+	#[inline]
+	#[must_use]
+	pub const fn as_value(&self) -> &Value {
+		&self.root
+	}
+
+	/// Whether `key` matches the `patternProperties` pattern `pattern`.
+	fn matches(&self, pattern: &str, key: &str) -> bool {
+		self
+			.patterns
+			.get(pattern)
+			.is_some_and(|regex| regex.is_match(key).unwrap_or(false))
+	}
+
+	/// Validate `value` against the schema.
+	///
+	/// # Errors
+	///
+	/// Returns every violation found.
+	pub fn validate(&self, value: &Value) -> Result<(), Vec<Violation>> {
+		self.check(value, &[])
+	}
+
+	/// Validate `value`, keeping the values at `secrets` out of the messages.
+	fn check(&self, value: &Value, secrets: &[Path]) -> Result<(), Vec<Violation>> {
+		let violations: Vec<Violation> = self
+			.validator
+			.iter_errors(value)
+			.map(|e| {
+				let path: Path = e
+					.instance_path()
+					.iter()
+					.map(|segment| match segment {
+						jsonschema::paths::LocationSegment::Property(key) => PathSegment::Key(key.into_owned()),
+						jsonschema::paths::LocationSegment::Index(index) => PathSegment::Index(index),
+					})
+					.collect();
+				// Violations on a secret or any value containing one quote it
+				let message = if secrets
+					.iter()
+					.any(|secret| secret.segments().starts_with(path.segments()))
+				{
+					e.masked_with(REDACTED).to_string()
+				} else {
+					e.to_string()
+				};
+				Violation { path, message }
+			})
+			.collect();
+		if violations.is_empty() {
+			Ok(())
+		} else {
+			Err(violations)
+		}
+	}
+}
+
+/// Compile the `patternProperties` patterns found anywhere in `value`.
+///
+/// Patterns that fail to compile are left out and never match.
+fn collect_patterns(value: &Value, patterns: &mut BTreeMap<String, Regex>) {
+	match value {
+		Value::Object(map) => {
+			if let Some(Value::Object(properties)) = map.get("patternProperties") {
+				for pattern in properties.keys() {
+					if !patterns.contains_key(pattern)
+						&& let Ok(regex) = Regex::new(pattern)
+					{
+						patterns.insert(pattern.clone(), regex);
+					}
+				}
+			}
+			map.values().for_each(|v| collect_patterns(v, patterns));
+		}
+		Value::Array(items) => items.iter().for_each(|v| collect_patterns(v, patterns)),
+		_ => {}
+	}
+}
+
+/// A schema that failed to compile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSchema {
+	/// Description of the problem.
+	pub message: String,
+}
+
+impl core::fmt::Display for InvalidSchema {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "invalid schema: {}", self.message)
+	}
+}
+
+impl std::error::Error for InvalidSchema {}
+
+/// A value that does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+	/// Location of the offending value.
+	pub path: Path,
+	/// Description of the problem.
+	pub message: String,
+}
+
+impl core::fmt::Display for Violation {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "`{}`: {}", self.path, self.message)
+	}
+}
+
+/// Error type for schema guided resolution.
+#[derive(Debug)]
+pub enum SchemaError<E> {
+	/// The schema failed to compile.
+	Schema(InvalidSchema),
+	/// Serialization of the input failed.
+	Serialize(serde_json::Error),
+	/// Resolution error.
+	Resolve(Error<E>),
+	/// The resolved value does not match the schema.
+	Invalid(Vec<Violation>),
+	/// Deserialization of the resolved value failed.
+	Deserialize(serde_json::Error),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for SchemaError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Schema(e) => write!(f, "{e}"),
+			Self::Serialize(e) => write!(f, "serialization error: {e}"),
+			Self::Resolve(e) => write!(f, "resolution error: {e}"),
+			Self::Invalid(violations) => {
+				write!(f, "schema validation failed")?;
+				for (i, violation) in violations.iter().enumerate() {
+					let sep = if i == 0 { ": " } else { "; " };
+					write!(f, "{sep}{violation}")?;
+				}
+				Ok(())
+			}
+			Self::Deserialize(e) => write!(f, "deserialization error: {e}"),
+		}
+	}
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for SchemaError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Schema(e) => Some(e),
+			Self::Serialize(e) | Self::Deserialize(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+			Self::Invalid(_) => None,
+		}
+	}
+}
+
+/// Resolve the strings `schema` marks as templatable in a JSON [`Value`].
+///
+/// See the [module documentation](self) for details.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - The resolved value does not match the schema
+pub async fn resolve<R>(
+	value: Value,
+	schema: &Schema,
+	resolver: &R,
+	config: &Config,
+) -> Result<Value, SchemaError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let node = Node::new(&schema.root, [&schema.root], false);
+	let mut walk = Walk {
+		guard: Guard::new(config).map_err(SchemaError::Resolve)?,
+		..Walk::default()
+	};
+	let resolved = resolve_recursive(value, node, schema, resolver, config, 0, &mut walk)
+		.await
+		.map_err(SchemaError::Resolve)?;
+	schema
+		.check(&resolved, &walk.secrets)
+		.map_err(SchemaError::Invalid)?;
+	Ok(resolved)
+}
+
+/// Resolve a serializable value against the schema of `T` and deserialize
+/// the result into `T`.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The schema of `T` does not compile
+/// - Serialization fails
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - The resolved value does not match the schema
+/// - Deserialization fails
+#[cfg(feature = "schemars")]
+pub async fn resolve_into<S, T, R>(
+	value: S,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, SchemaError<R::Error>>
+where
+	S: serde::Serialize,
+	T: schemars::JsonSchema + serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	let schema = Schema::for_type::<T>().map_err(SchemaError::Schema)?;
+	let json = serde_json::to_value(value).map_err(SchemaError::Serialize)?;
+	let resolved = resolve(json, &schema, resolver, config).await?;
+	serde_json::from_value(resolved).map_err(SchemaError::Deserialize)
+}
+
+/// The schemas that apply to a value.
+struct Node<'s> {
+	schemas: Vec<&'s Value>,
+	resolve: bool,
+}
+
+impl<'s> Node<'s> {
+	/// Collect `schemas` and everything they reference or combine.
+	///
+	/// Schemas are visited level by level, so [`KEYWORD`] is taken from the
+	/// nearest level setting it.
+	fn new(root: &'s Value, schemas: impl IntoIterator<Item = &'s Value>, inherited: bool) -> Self {
+		let mut node = Self {
+			schemas: Vec::new(),
+			resolve: inherited,
+		};
+		let mut explicit = None;
+		let mut level: Vec<&'s Value> = schemas.into_iter().collect();
+		while !level.is_empty() {
+			let mut next = Vec::new();
+			let mut resolve = None;
+			for schema in level {
+				// Guard against `$ref` cycles
+				if !schema.is_object() || node.schemas.iter().any(|s| core::ptr::eq(*s, schema)) {
+					continue;
+				}
+				node.schemas.push(schema);
+
+				if let Some(keyword) = schema.get(KEYWORD).and_then(Value::as_bool) {
+					resolve = Some(resolve.unwrap_or(true) && keyword);
+				}
+				if let Some(target) = schema
+					.get("$ref")
+					.and_then(Value::as_str)
+					.and_then(|r| r.strip_prefix('#'))
+					.and_then(|pointer| root.pointer(pointer))
+				{
+					next.push(target);
+				}
+				for keyword in ["allOf", "anyOf", "oneOf"] {
+					if let Some(branches) = schema.get(keyword).and_then(Value::as_array) {
+						next.extend(branches);
+					}
+				}
+			}
+			explicit = explicit.or(resolve);
+			level = next;
+		}
+
+		if let Some(resolve) = explicit {
+			node.resolve = resolve;
+		}
+		node
+	}
+
+	fn property(&self, schema: &'s Schema, key: &str) -> Self {
+		let schemas = self.schemas.iter().flat_map(|s| {
+			let named = s
+				.get("properties")
+				.and_then(|properties| properties.get(key));
+			let patterns = s
+				.get("patternProperties")
+				.and_then(Value::as_object)
+				.into_iter()
+				.flatten()
+				.filter(|(pattern, _)| schema.matches(pattern, key))
+				.map(|(_, sub)| sub);
+			let mut matched: Vec<&'s Value> = named.into_iter().chain(patterns).collect();
+			if matched.is_empty() {
+				matched.extend(s.get("additionalProperties"));
+			}
+			matched
+		});
+		Self::new(&schema.root, schemas, self.resolve)
+	}
+
+	fn item(&self, schema: &'s Schema, index: usize) -> Self {
+		let schemas = self.schemas.iter().filter_map(|s| {
+			let prefix = s
+				.get("prefixItems")
+				.or_else(|| s.get("items").filter(|items| items.is_array()));
+			match prefix.and_then(Value::as_array) {
+				Some(prefix) => prefix.get(index).or_else(|| {
+					s.get("additionalItems")
+						.or_else(|| s.get("items").filter(|items| !items.is_array()))
+				}),
+				None => s.get("items"),
+			}
+		});
+		Self::new(&schema.root, schemas, self.resolve)
+	}
+
+	/// Types declared by the schemas, or `None` if a string is allowed.
+	fn coerced_types(&self) -> Option<Vec<&'s str>> {
+		let mut types = Vec::new();
+		for schema in &self.schemas {
+			match schema.get("type") {
+				Some(Value::String(ty)) => types.push(ty.as_str()),
+				Some(Value::Array(list)) => types.extend(list.iter().filter_map(Value::as_str)),
+				_ => {}
+			}
+		}
+		(!types.is_empty() && !types.contains(&"string")).then_some(types)
+	}
+
+	/// Convert a resolved string into the first declared type it parses as.
+	fn coerce(&self, s: String, config: &Config) -> Value {
+		let Some(types) = self.coerced_types() else {
+			return Value::String(s);
+		};
+		let scalar = |target| Scalar::parse(&s, target, config.coercion);
+		let number = |scalar| match scalar {
+			Some(Scalar::I64(v)) => Some(Value::from(v)),
+			Some(Scalar::U64(v)) => Some(Value::from(v)),
+			Some(Scalar::F64(v)) => Number::from_f64(v).map(Value::Number),
+			_ => None,
+		};
+		let coerced = types.iter().find_map(|ty| match *ty {
+			"null" => scalar(Target::Unit).map(|_| Value::Null),
+			"boolean" => match scalar(Target::Bool) {
+				Some(Scalar::Bool(v)) => Some(Value::Bool(v)),
+				_ => None,
+			},
+			"integer" => number(scalar(Target::Signed)).or_else(|| number(scalar(Target::Unsigned))),
+			"number" => number(scalar(Target::Signed))
+				.or_else(|| number(scalar(Target::Unsigned)))
+				.or_else(|| number(scalar(Target::Float))),
+			_ => None,
+		});
+		coerced.unwrap_or(Value::String(s))
+	}
+}
+
+/// Traversal state threaded through the recursion.
+#[derive(Default)]
+struct Walk {
+	path: Path,
+	/// Paths resolved to secrets, whose values must not appear in violations.
+	secrets: Vec<Path>,
+	/// Deadlines and cancellation of the operation.
+	guard: Guard,
+}
+
+fn resolve_recursive<'a, R>(
+	value: Value,
+	node: Node<'a>,
+	schema: &'a Schema,
+	resolver: &'a R,
+	config: &'a Config,
+	depth: usize,
+	walk: &'a mut Walk,
+) -> Pin<Box<dyn Future<Output = Result<Value, Error<R::Error>>> + Send + 'a>>
+where
+	R: Resolver + ?Sized,
+{
+	Box::pin(async move {
+		if depth >= config.max_depth {
+			return Err(Error::depth_exceeded(config.max_depth));
+		}
+
+		match value {
+			Value::String(s) if node.resolve => {
+				#[cfg(feature = "tracing")]
+				tracing::trace!(depth, path = %walk.path, "resolving");
+
+				let resolved = walk
+					.guard
+					.run(
+						&walk.path,
+						crate::fixpoint::resolve_str(resolver, &s, config),
+					)
+					.await?;
+				#[cfg(feature = "tracing")]
+				crate::trace_resolved(&s, &resolved);
+
+				if resolved.is_secret() {
+					walk.secrets.push(walk.path.clone());
+				}
+				Ok(node.coerce(resolved.into_changed().unwrap_or(s), config))
+			}
+
+			Value::Array(arr) => {
+				let mut result = Vec::with_capacity(arr.len());
+				for item in arr {
+					let child = node.item(schema, result.len());
+					walk.path.push(PathSegment::Index(result.len()));
+					let res =
+						resolve_recursive(item, child, schema, resolver, config, depth + 1, walk).await?;
+					result.push(res);
+					walk.path.pop();
+				}
+				Ok(Value::Array(result))
+			}
+
+			Value::Object(map) => {
+				let mut result = Map::with_capacity(map.len());
+				for (key, val) in map {
+					let child = node.property(schema, &key);
+					walk.path.push(PathSegment::Key(key.clone()));
+					let resolved =
+						resolve_recursive(val, child, schema, resolver, config, depth + 1, walk).await?;
+					result.insert(key, resolved);
+					walk.path.pop();
+				}
+				Ok(Value::Object(result))
+			}
+
+			other => Ok(other),
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::upper;
+	use crate::{Coercion, Resolved};
+	use core::convert::Infallible;
+	use serde_json::json;
+
+	fn port(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+		let resolved = match s {
+			"${PORT}" => Resolved::changed("8080"),
+			"${DEBUG}" => Resolved::changed("true"),
+			"${NONE}" => Resolved::changed("null"),
+			_ => Resolved::unchanged(),
+		};
+		async move { Ok(resolved) }
+	}
+
+	#[tokio::test]
+	async fn test_only_marked_strings() {
+		let schema = Schema::new(json!({
+			"type": "object",
+			"properties": {
+				"a": { "type": "string", "x-resolve": true },
+				"b": { "type": "string" },
+				"nested": {
+					"x-resolve": true,
+					"properties": {
+						"off": { "x-resolve": false }
+					}
+				}
+			}
+		}))
+		.unwrap();
+
+		let output = resolve(
+			json!({
+				"a": "a",
+				"b": "b",
+				"nested": { "c": "c", "list": ["d"], "off": "e" }
+			}),
+			&schema,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			json!({
+				"a": "A",
+				"b": "b",
+				"nested": { "c": "C", "list": ["D"], "off": "e" }
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_explicit_false_wins() {
+		let schema = Schema::new(json!({
+			"$defs": {
+				"templated": { "x-resolve": true }
+			},
+			"type": "object",
+			"x-resolve": true,
+			"properties": {
+				"off": { "x-resolve": false, "allOf": [{ "x-resolve": true }] },
+				"ref": { "x-resolve": false, "$ref": "#/$defs/templated" },
+				"either": { "anyOf": [{ "x-resolve": true }, { "x-resolve": false }] },
+				"on": { "allOf": [{ "$ref": "#/$defs/templated" }] }
+			}
+		}))
+		.unwrap();
+
+		let output = resolve(
+			json!({ "off": "a", "ref": "b", "either": "c", "on": "d" }),
+			&schema,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			json!({ "off": "a", "ref": "b", "either": "c", "on": "D" })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_pattern_properties() {
+		let schema = Schema::new(json!({
+			"type": "object",
+			"properties": {
+				"env_static": { "x-resolve": false }
+			},
+			"patternProperties": {
+				"^env_": { "x-resolve": true },
+				"^raw_": { "type": "string" }
+			},
+			"additionalProperties": { "x-resolve": true }
+		}))
+		.unwrap();
+
+		let output = resolve(
+			json!({ "env_name": "a", "env_static": "b", "raw_name": "c", "other": "d" }),
+			&schema,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		// `raw_name` matches a pattern, so `additionalProperties` does not apply
+		assert_eq!(
+			output,
+			json!({ "env_name": "A", "env_static": "b", "raw_name": "c", "other": "D" })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_refs_and_items() {
+		let schema = Schema::new(json!({
+			"$defs": {
+				"templated": { "type": "string", "x-resolve": true }
+			},
+			"type": "object",
+			"properties": {
+				"tuple": {
+					"prefixItems": [{ "type": "string" }, { "$ref": "#/$defs/templated" }],
+					"items": { "$ref": "#/$defs/templated" }
+				},
+				"either": {
+					"anyOf": [{ "type": "integer" }, { "$ref": "#/$defs/templated" }]
+				}
+			},
+			"additionalProperties": { "$ref": "#/$defs/templated" }
+		}))
+		.unwrap();
+
+		let output = resolve(
+			json!({
+				"tuple": ["a", "b", "c"],
+				"either": "d",
+				"extra": "e"
+			}),
+			&schema,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			json!({
+				"tuple": ["a", "B", "C"],
+				"either": "D",
+				"extra": "E"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_coercion() {
+		let schema = Schema::new(json!({
+			"type": "object",
+			"x-resolve": true,
+			"properties": {
+				"port": { "type": "integer", "minimum": 1 },
+				"debug": { "type": "boolean" },
+				"backup": { "type": ["integer", "null"] },
+				"ratio": { "type": "number" },
+				"name": { "type": ["string", "integer"] }
+			}
+		}))
+		.unwrap();
+
+		let output = resolve(
+			json!({
+				"port": "${PORT}",
+				"debug": "${DEBUG}",
+				"backup": "${NONE}",
+				"ratio": "${PORT}",
+				"name": "${PORT}"
+			}),
+			&schema,
+			&port,
+			&Config::default().coercion(Coercion::Strict),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			json!({
+				"port": 8080,
+				"debug": true,
+				"backup": null,
+				"ratio": 8080,
+				"name": "8080"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_validation() {
+		let schema = Schema::new(json!({
+			"type": "object",
+			"properties": {
+				"ports": {
+					"type": "array",
+					"items": { "type": "integer", "x-resolve": true }
+				}
+			}
+		}))
+		.unwrap();
+
+		// Without coercion the resolved string fails validation
+		let result = resolve(
+			json!({ "ports": [80, "${PORT}"] }),
+			&schema,
+			&port,
+			&Config::default(),
+		)
+		.await;
+
+		let Err(SchemaError::Invalid(violations)) = result else {
+			panic!("expected validation error");
+		};
+		assert_eq!(violations.len(), 1);
+		assert_eq!(violations[0].path.to_string(), "ports[1]");
+		assert_eq!(
+			violations[0].to_string(),
+			"`ports[1]`: \"8080\" is not of type \"integer\""
+		);
+	}
+
+	#[tokio::test]
+	async fn test_secret_violation_masked() {
+		let schema = Schema::new(json!({
+			"type": "object",
+			"properties": {
+				"pin": { "type": "integer", "x-resolve": true }
+			}
+		}))
+		.unwrap();
+
+		let result = resolve(
+			json!({ "pin": "${PIN}" }),
+			&schema,
+			&|_: &str| async { Ok::<_, Infallible>(Resolved::secret("12ab")) },
+			&Config::default().coercion(Coercion::Strict),
+		)
+		.await;
+
+		let Err(SchemaError::Invalid(violations)) = result else {
+			panic!("expected validation error");
+		};
+		assert_eq!(violations[0].path.to_string(), "pin");
+		assert!(!violations[0].message.contains("12ab"));
+		assert!(violations[0].message.contains(REDACTED));
+	}
+
+	#[test]
+	fn test_invalid_schema() {
+		let err = Schema::new(json!({ "type": "nonsense" })).unwrap_err();
+		assert!(err.to_string().starts_with("invalid schema: "));
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let schema = Schema::new(json!({ "x-resolve": true })).unwrap();
+		let result = resolve(
+			json!([[["deep"]]]),
+			&schema,
+			&upper,
+			&Config::default().max_depth(3),
+		)
+		.await;
+		assert!(matches!(
+			result,
+			Err(SchemaError::Resolve(Error::DepthExceeded { limit: 3 }))
+		));
+	}
+
+	#[cfg(feature = "schemars")]
+	#[tokio::test]
+	async fn test_resolve_into() {
+		#[derive(Debug, PartialEq, serde::Deserialize, schemars::JsonSchema)]
+		struct Server {
+			#[schemars(extend("x-resolve" = true))]
+			port: u16,
+			name: String,
+		}
+
+		let server: Server = resolve_into(
+			json!({ "port": "${PORT}", "name": "${PORT}" }),
+			&port,
+			&Config::default().coercion(Coercion::Strict),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			server,
+			Server {
+				port: 8080,
+				name: "${PORT}".into(),
+			}
+		);
+	}
+}
@@ -0,0 +1,729 @@
+/* src/json/stream.rs */
+
+//! Streaming JSON resolution.
+//!
+//! This module requires the `stream` feature. The async variants additionally
+//! require the `tokio` feature.
+//!
+//! Input is read in chunks and written back as it is scanned, so no
+//! [`Value`](serde_json::Value) is ever built and memory use is bounded by the
+//! longest single string. Everything except changed strings is copied through
+//! byte for byte, including whitespace and number formatting.
+//!
+//! Depth is counted as in [`crate::json::resolve`]: the root value is at depth
+//! 0 and every container adds one level.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use std::io::{Read, Write};
+
+use crate::deadline::Guard;
+use crate::{Config, Error, Path, PathSegment, Resolver};
+
+/// Size of the read buffer.
+const CHUNK: usize = 64 * 1024;
+
+/// Position and description of malformed input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+	/// Byte offset of the offending input.
+	pub offset: u64,
+	/// Description of the problem.
+	pub message: &'static str,
+}
+
+impl core::fmt::Display for SyntaxError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "{} at byte {}", self.message, self.offset)
+	}
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Error type for streaming resolution.
+#[derive(Debug)]
+pub enum StreamError<E> {
+	/// Reading or writing failed.
+	Io(std::io::Error),
+	/// The input is not valid JSON.
+	Syntax(SyntaxError),
+	/// Resolution error.
+	Resolve(Error<E>),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for StreamError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "io error: {e}"),
+			Self::Syntax(e) => write!(f, "syntax error: {e}"),
+			Self::Resolve(e) => write!(f, "resolution error: {e}"),
+		}
+	}
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for StreamError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Syntax(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+		}
+	}
+}
+
+/// Resolve a single JSON document read from `reader` into `writer`.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Reading or writing fails
+/// - The input is not a single valid JSON value
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+///
+/// # Example
+///
+/// ```rust
+/// use serde_resolve::json::stream;
+/// use serde_resolve::{Config, Resolved};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let input = br#"[ {"name": "a"}, {"name": "b"} ]"#;
+/// let mut output = Vec::new();
+///
+/// stream::resolve_reader(
+///     &input[..],
+///     &mut output,
+///     &|s: &str| {
+///         let s = s.to_uppercase();
+///         async move { Ok::<_, std::convert::Infallible>(Resolved::changed(s)) }
+///     },
+///     &Config::default(),
+/// ).await?;
+///
+/// assert_eq!(output, br#"[ {"name": "A"}, {"name": "B"} ]"#);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve_reader<I, O, R>(
+	reader: I,
+	writer: O,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: Read,
+	O: Write,
+	R: Resolver + ?Sized,
+{
+	transcode(reader, writer, Transcoder::new(false), resolver, config).await
+}
+
+/// Resolve newline-delimited JSON read from `reader` into `writer`.
+///
+/// Each line holds one JSON value, resolved independently with its own depth
+/// count. Blank lines are copied through.
+///
+/// # Errors
+///
+/// See [`resolve_reader`].
+pub async fn resolve_ndjson_reader<I, O, R>(
+	reader: I,
+	writer: O,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: Read,
+	O: Write,
+	R: Resolver + ?Sized,
+{
+	transcode(reader, writer, Transcoder::new(true), resolver, config).await
+}
+
+/// Async variant of [`resolve_reader`].
+///
+/// # Errors
+///
+/// See [`resolve_reader`].
+#[cfg(feature = "tokio")]
+pub async fn resolve_async_reader<I, O, R>(
+	reader: I,
+	writer: O,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: tokio::io::AsyncRead + Unpin,
+	O: tokio::io::AsyncWrite + Unpin,
+	R: Resolver + ?Sized,
+{
+	transcode_async(reader, writer, Transcoder::new(false), resolver, config).await
+}
+
+/// Async variant of [`resolve_ndjson_reader`].
+///
+/// # Errors
+///
+/// See [`resolve_reader`].
+#[cfg(feature = "tokio")]
+pub async fn resolve_ndjson_async_reader<I, O, R>(
+	reader: I,
+	writer: O,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: tokio::io::AsyncRead + Unpin,
+	O: tokio::io::AsyncWrite + Unpin,
+	R: Resolver + ?Sized,
+{
+	transcode_async(reader, writer, Transcoder::new(true), resolver, config).await
+}
+
+async fn transcode<I, O, R>(
+	mut reader: I,
+	mut writer: O,
+	mut transcoder: Transcoder,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: Read,
+	O: Write,
+	R: Resolver + ?Sized,
+{
+	let mut buf = vec![0; CHUNK];
+	let mut out = Vec::new();
+	let mut guard = Guard::new(config).map_err(StreamError::Resolve)?;
+	loop {
+		let n = match reader.read(&mut buf) {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(StreamError::Io(e)),
+		};
+		transcoder
+			.feed(&buf[..n], &mut out, resolver, config, &mut guard)
+			.await?;
+		writer.write_all(&out).map_err(StreamError::Io)?;
+		out.clear();
+	}
+	transcoder.finish().map_err(StreamError::Syntax)?;
+	writer.flush().map_err(StreamError::Io)
+}
+
+#[cfg(feature = "tokio")]
+async fn transcode_async<I, O, R>(
+	mut reader: I,
+	mut writer: O,
+	mut transcoder: Transcoder,
+	resolver: &R,
+	config: &Config,
+) -> Result<(), StreamError<R::Error>>
+where
+	I: tokio::io::AsyncRead + Unpin,
+	O: tokio::io::AsyncWrite + Unpin,
+	R: Resolver + ?Sized,
+{
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	let mut buf = vec![0; CHUNK];
+	let mut out = Vec::new();
+	let mut guard = Guard::new(config).map_err(StreamError::Resolve)?;
+	loop {
+		let n = reader.read(&mut buf).await.map_err(StreamError::Io)?;
+		if n == 0 {
+			break;
+		}
+		transcoder
+			.feed(&buf[..n], &mut out, resolver, config, &mut guard)
+			.await?;
+		writer.write_all(&out).await.map_err(StreamError::Io)?;
+		out.clear();
+	}
+	transcoder.finish().map_err(StreamError::Syntax)?;
+	writer.flush().await.map_err(StreamError::Io)
+}
+
+/// What the scanner expects next in the innermost context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+	Value,
+	ValueOrEnd,
+	Key,
+	KeyOrEnd,
+	Colon,
+	CommaOrEnd,
+	Done,
+}
+
+/// Problem found by the scanner, without position.
+#[derive(Clone, Copy)]
+enum Fault {
+	Syntax(&'static str),
+	Depth,
+}
+
+/// Incremental JSON scanner that copies input to output.
+struct Transcoder {
+	/// Open containers, `true` for objects.
+	stack: Vec<bool>,
+	/// Location of the current value; keys are pushed once read.
+	path: Path,
+	expect: Expect,
+	/// Raw bytes of the string literal being read, including quotes.
+	literal: Vec<u8>,
+	in_string: bool,
+	escaped: bool,
+	/// Raw bytes of the number or literal name being read.
+	scalar: Vec<u8>,
+	ndjson: bool,
+	offset: u64,
+}
+
+impl Transcoder {
+	const fn new(ndjson: bool) -> Self {
+		Self {
+			stack: Vec::new(),
+			path: Path::root(),
+			expect: Expect::Value,
+			literal: Vec::new(),
+			in_string: false,
+			escaped: false,
+			scalar: Vec::new(),
+			ndjson,
+			offset: 0,
+		}
+	}
+
+	async fn feed<R>(
+		&mut self,
+		chunk: &[u8],
+		out: &mut Vec<u8>,
+		resolver: &R,
+		config: &Config,
+		guard: &mut Guard,
+	) -> Result<(), StreamError<R::Error>>
+	where
+		R: Resolver + ?Sized,
+	{
+		for &byte in chunk {
+			let completed = self
+				.push(byte, out, config.max_depth)
+				.map_err(|fault| self.error(fault, config))?;
+			if let Some(is_key) = completed {
+				self
+					.emit_string(is_key, out, resolver, config, guard)
+					.await?;
+			}
+			self.offset += 1;
+		}
+		Ok(())
+	}
+
+	/// Validate end of input and flush a trailing scalar.
+	fn finish(&mut self) -> Result<(), SyntaxError> {
+		if !self.scalar.is_empty() {
+			self
+				.end_scalar()
+				.map_err(|_| self.syntax("invalid literal"))?;
+		}
+		match self.expect {
+			Expect::Done => Ok(()),
+			Expect::Value if self.ndjson && self.stack.is_empty() && !self.in_string => Ok(()),
+			_ => Err(self.syntax("unexpected end of input")),
+		}
+	}
+
+	fn syntax(&self, message: &'static str) -> SyntaxError {
+		SyntaxError {
+			offset: self.offset,
+			message,
+		}
+	}
+
+	fn error<E>(&self, fault: Fault, config: &Config) -> StreamError<E> {
+		match fault {
+			Fault::Syntax(message) => StreamError::Syntax(self.syntax(message)),
+			Fault::Depth => StreamError::Resolve(Error::depth_exceeded(config.max_depth)),
+		}
+	}
+
+	/// Scan one byte. Returns `Some(is_key)` when a string literal is complete.
+	fn push(&mut self, byte: u8, out: &mut Vec<u8>, max_depth: usize) -> Result<Option<bool>, Fault> {
+		if self.in_string {
+			self.literal.push(byte);
+			if self.escaped {
+				self.escaped = false;
+			} else if byte == b'\\' {
+				self.escaped = true;
+			} else if byte == b'"' {
+				self.in_string = false;
+				let is_key = matches!(self.expect, Expect::Key | Expect::KeyOrEnd);
+				self.expect = if is_key {
+					Expect::Colon
+				} else {
+					self.after_value()
+				};
+				return Ok(Some(is_key));
+			}
+			return Ok(None);
+		}
+
+		if !self.scalar.is_empty() {
+			if is_scalar_byte(byte) {
+				self.scalar.push(byte);
+				out.push(byte);
+				return Ok(None);
+			}
+			self.end_scalar()?;
+		}
+
+		let at_value = matches!(self.expect, Expect::Value | Expect::ValueOrEnd);
+		match byte {
+			b'\n' if self.ndjson && self.expect == Expect::Done => self.expect = Expect::Value,
+			b' ' | b'\t' | b'\r' | b'\n' => {}
+			b'"' if at_value => {
+				self.check_depth(max_depth)?;
+				self.begin_string();
+				return Ok(None);
+			}
+			b'"' if matches!(self.expect, Expect::Key | Expect::KeyOrEnd) => {
+				self.begin_string();
+				return Ok(None);
+			}
+			b'{' | b'[' if at_value => {
+				self.check_depth(max_depth)?;
+				let object = byte == b'{';
+				self.stack.push(object);
+				if !object {
+					self.path.push(PathSegment::Index(0));
+				}
+				self.expect = if object {
+					Expect::KeyOrEnd
+				} else {
+					Expect::ValueOrEnd
+				};
+			}
+			b'}'
+				if matches!(self.expect, Expect::KeyOrEnd | Expect::CommaOrEnd)
+					&& self.stack.last() == Some(&true) =>
+			{
+				// A key is on the path unless the object is empty
+				if self.expect == Expect::CommaOrEnd {
+					self.path.pop();
+				}
+				self.stack.pop();
+				self.expect = self.after_value();
+			}
+			b']'
+				if matches!(self.expect, Expect::ValueOrEnd | Expect::CommaOrEnd)
+					&& self.stack.last() == Some(&false) =>
+			{
+				self.path.pop();
+				self.stack.pop();
+				self.expect = self.after_value();
+			}
+			b',' if self.expect == Expect::CommaOrEnd => {
+				let previous = self.path.pop();
+				self.expect = if self.stack.last() == Some(&true) {
+					Expect::Key
+				} else {
+					if let Some(PathSegment::Index(index)) = previous {
+						self.path.push(PathSegment::Index(index + 1));
+					}
+					Expect::Value
+				};
+			}
+			b':' if self.expect == Expect::Colon => self.expect = Expect::Value,
+			b if at_value && is_scalar_byte(b) => {
+				self.check_depth(max_depth)?;
+				self.scalar.push(b);
+			}
+			_ if self.expect == Expect::Done => return Err(Fault::Syntax("trailing characters")),
+			_ => return Err(Fault::Syntax("unexpected character")),
+		}
+
+		out.push(byte);
+		Ok(None)
+	}
+
+	fn begin_string(&mut self) {
+		self.in_string = true;
+		self.literal.clear();
+		self.literal.push(b'"');
+	}
+
+	fn check_depth(&self, max_depth: usize) -> Result<(), Fault> {
+		if self.stack.len() >= max_depth {
+			Err(Fault::Depth)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// State after a complete value in the current context.
+	fn after_value(&self) -> Expect {
+		if self.stack.is_empty() {
+			Expect::Done
+		} else {
+			Expect::CommaOrEnd
+		}
+	}
+
+	fn end_scalar(&mut self) -> Result<(), Fault> {
+		serde_json::from_slice::<serde::de::IgnoredAny>(&self.scalar)
+			.map_err(|_| Fault::Syntax("invalid literal"))?;
+		self.scalar.clear();
+		self.expect = self.after_value();
+		Ok(())
+	}
+
+	async fn emit_string<R>(
+		&mut self,
+		is_key: bool,
+		out: &mut Vec<u8>,
+		resolver: &R,
+		config: &Config,
+		guard: &mut Guard,
+	) -> Result<(), StreamError<R::Error>>
+	where
+		R: Resolver + ?Sized,
+	{
+		let s: alloc::string::String = serde_json::from_slice(&self.literal)
+			.map_err(|_| StreamError::Syntax(self.syntax("invalid string")))?;
+		if is_key && !config.resolve_keys {
+			out.extend_from_slice(&self.literal);
+			self.path.push(PathSegment::Key(s));
+			return Ok(());
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::trace!(
+			offset = self.offset,
+			depth = self.stack.len(),
+			is_key,
+			"resolving"
+		);
+
+		let resolved = guard
+			.run(
+				&self.path,
+				crate::fixpoint::resolve_str(resolver, &s, config),
+			)
+			.await
+			.map_err(StreamError::Resolve)?;
+		#[cfg(feature = "tracing")]
+		crate::trace_resolved(&s, &resolved);
+
+		let s = if let Some(new_s) = resolved.into_changed() {
+			serde_json::to_writer(&mut *out, &new_s)
+				.map_err(|e| StreamError::Io(std::io::Error::other(e)))?;
+			new_s
+		} else {
+			out.extend_from_slice(&self.literal);
+			s
+		};
+		if is_key {
+			self.path.push(PathSegment::Key(s));
+		}
+		Ok(())
+	}
+}
+
+/// Bytes that may appear in numbers and `true`/`false`/`null`.
+const fn is_scalar_byte(byte: u8) -> bool {
+	matches!(byte, b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'-' | b'+' | b'.')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use crate::testing::{Expired, upper};
+	use core::convert::Infallible;
+
+	/// Reader returning one byte per call, to exercise chunk boundaries.
+	struct Trickle<'a>(&'a [u8]);
+
+	impl Read for Trickle<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+			let Some((&first, rest)) = self.0.split_first() else {
+				return Ok(0);
+			};
+			buf[0] = first;
+			self.0 = rest;
+			Ok(1)
+		}
+	}
+
+	async fn run(
+		input: &str,
+		config: &Config,
+	) -> Result<std::string::String, StreamError<Infallible>> {
+		let mut output = Vec::new();
+		resolve_reader(Trickle(input.as_bytes()), &mut output, &upper, config).await?;
+		Ok(std::string::String::from_utf8(output).unwrap())
+	}
+
+	#[tokio::test]
+	async fn test_preserves_layout() {
+		let input =
+			"{\n  \"name\": \"app\",\n  \"ports\": [80, 4.4e3],\n  \"on\": true, \"x\": null\n}\n";
+		let output = run(input, &Config::default()).await.unwrap();
+
+		assert_eq!(
+			output,
+			"{\n  \"name\": \"APP\",\n  \"ports\": [80, 4.4e3],\n  \"on\": true, \"x\": null\n}\n"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_escapes() {
+		let output = run(r#"["a\nb", "é"]"#, &Config::default()).await.unwrap();
+		assert_eq!(output, r#"["A\nB", "É"]"#);
+
+		let mut output = Vec::new();
+		resolve_reader(
+			&br#"["A\"x"]"#[..],
+			&mut output,
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(output, br#"["A\"x"]"#);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_keys() {
+		let input = r#"{"key": {"inner": "v"}}"#;
+
+		let output = run(input, &Config::default()).await.unwrap();
+		assert_eq!(output, r#"{"key": {"inner": "V"}}"#);
+
+		let output = run(input, &Config::default().resolve_keys(true))
+			.await
+			.unwrap();
+		assert_eq!(output, r#"{"KEY": {"INNER": "V"}}"#);
+	}
+
+	#[tokio::test]
+	async fn test_depth_matches_tree() {
+		let input = r#"{"a": [1, {"b": "c"}], "d": "e"}"#;
+		for max_depth in 0..6 {
+			let config = Config::default().max_depth(max_depth);
+			let streamed = run(input, &config).await;
+			let tree = crate::json::resolve(serde_json::from_str(input).unwrap(), &upper, &config).await;
+			assert_eq!(streamed.is_ok(), tree.is_ok(), "max_depth = {max_depth}");
+			if let Err(e) = streamed {
+				assert!(matches!(
+					e,
+					StreamError::Resolve(Error::DepthExceeded { .. })
+				));
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_ndjson() {
+		let input = "{\"a\": \"x\"}\n\n[\"y\"]\n\"z\"\n42\n";
+		let mut output = Vec::new();
+		resolve_ndjson_reader(
+			Trickle(input.as_bytes()),
+			&mut output,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, b"{\"a\": \"X\"}\n\n[\"Y\"]\n\"Z\"\n42\n");
+	}
+
+	#[tokio::test]
+	async fn test_syntax_errors() {
+		let cases = [
+			(r#"{"a" 1}"#, 5),
+			(r#"["a"] ["b"]"#, 6),
+			(r#"[1, }"#, 4),
+			(r#"["open"#, 6),
+			("[tru]", 4),
+			("", 0),
+		];
+		for (input, offset) in cases {
+			match run(input, &Config::default()).await {
+				Err(StreamError::Syntax(e)) => assert_eq!(e.offset, offset, "{input}"),
+				other => panic!("{input}: expected syntax error, got {other:?}"),
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_timeout_path() {
+		let input = r#"{"a": [1, {"b": "c"}], "d": {}, "e": ["f"]}"#;
+		let config = Config::default()
+			.string_timeout(core::time::Duration::from_secs(1))
+			.timer(Expired);
+		// Keys are resolved at the path of their object
+		for (hang, resolve_keys, expected) in [
+			("c", false, "a[1].b"),
+			("f", false, "e[0]"),
+			("e", true, "."),
+		] {
+			let resolver = |s: &str| {
+				let hangs = s == hang;
+				async move {
+					if hangs {
+						core::future::pending::<()>().await;
+					}
+					Ok::<_, Infallible>(Resolved::unchanged())
+				}
+			};
+			let mut output = Vec::new();
+			let config = config.clone().resolve_keys(resolve_keys);
+			let result = resolve_reader(Trickle(input.as_bytes()), &mut output, &resolver, &config).await;
+			let Err(StreamError::Resolve(Error::Timeout { path })) = result else {
+				panic!("expected timeout");
+			};
+			assert_eq!(path.to_string(), expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let mut output = Vec::new();
+		let result = resolve_reader(
+			&b"[\"a\"]"[..],
+			&mut output,
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(
+			result,
+			Err(StreamError::Resolve(Error::Resolver(MyError)))
+		));
+	}
+
+	#[cfg(feature = "tokio")]
+	#[tokio::test]
+	async fn test_async_reader() {
+		let mut output = Vec::new();
+		resolve_ndjson_async_reader(
+			&b"[\"a\"]\n[\"b\"]\n"[..],
+			&mut output,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, b"[\"A\"]\n[\"B\"]\n");
+	}
+}
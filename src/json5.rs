@@ -0,0 +1,614 @@
+/* src/json5.rs */
+
+//! JSON5 and JSONC support via `json5` crate.
+//!
+//! This module requires the `std` feature.
+//!
+//! Three entry points are provided:
+//! - [`resolve_str`] parses the text into a [`serde_json::Value`], resolves it
+//!   with [`crate::json::resolve`] and serializes the result as pretty JSON.
+//! - [`resolve_batched`] does the same with a [`BatchResolver`].
+//! - [`resolve_preserving`] rewrites only the string literals in place, keeping
+//!   comments, trailing commas, quoting style and whitespace intact.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::deadline::Guard;
+use crate::{BatchResolver, Config, Error, Path, PathSegment, Resolver, json};
+
+/// Error type for JSON5 resolution.
+#[derive(Debug)]
+pub enum Json5Error<E> {
+	/// The input is not valid JSON5.
+	Parse(::json5::Error),
+	/// Resolution error.
+	Resolve(Error<E>),
+	/// Serialization error.
+	Serialize(serde_json::Error),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Json5Error<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Parse(e) => write!(f, "parse error: {e}"),
+			Self::Resolve(e) => write!(f, "resolution error: {e}"),
+			Self::Serialize(e) => write!(f, "serialization error: {e}"),
+		}
+	}
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Json5Error<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Parse(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+			Self::Serialize(e) => Some(e),
+		}
+	}
+}
+
+/// Resolve all strings in a JSON5/JSONC document and serialize it as JSON.
+///
+/// Comments and trailing commas are dropped. Use [`resolve_preserving`] to keep
+/// the original layout.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input is not valid JSON5
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - Serialization fails
+pub async fn resolve_str<R>(
+	input: &str,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, Json5Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let value: serde_json::Value = ::json5::from_str(input).map_err(Json5Error::Parse)?;
+	let resolved = json::resolve(value, resolver, config)
+		.await
+		.map_err(Json5Error::Resolve)?;
+	serde_json::to_string_pretty(&resolved).map_err(Json5Error::Serialize)
+}
+
+/// Resolve all strings in a JSON5/JSONC document with a [`BatchResolver`] and
+/// serialize it as JSON.
+///
+/// See [`crate::resolve_batched`] for details. Comments and trailing commas
+/// are dropped.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input is not valid JSON5
+/// - The resolver returns an error or the wrong number of results
+/// - The depth limit is exceeded
+/// - Serialization fails
+pub async fn resolve_batched<B>(
+	input: &str,
+	resolver: &B,
+	config: &Config,
+) -> Result<String, Json5Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	let value: serde_json::Value = ::json5::from_str(input).map_err(Json5Error::Parse)?;
+	let resolved = json::resolve_batched(value, resolver, config)
+		.await
+		.map_err(Json5Error::Resolve)?;
+	serde_json::to_string_pretty(&resolved).map_err(Json5Error::Serialize)
+}
+
+/// Resolve all strings in a JSON5/JSONC document, preserving its layout.
+///
+/// Only string literals handed to the resolver are rewritten; everything else,
+/// including `// line` and `/* block */` comments, is copied verbatim. Changed
+/// strings are written back as double-quoted JSON strings. Unquoted JSON5 keys
+/// are passed to the resolver when [`Config::resolve_keys`] is set.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input is not valid JSON5
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+///
+/// # Example
+///
+/// ```rust
+/// use serde_resolve::{json5, Config, Resolved};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let input = r#"{
+///     // Greeting shown on start-up
+///     greeting: 'Hello {{name}}',
+/// }"#;
+///
+/// let output = json5::resolve_preserving(
+///     input,
+///     &|s: &str| {
+///         let s = s.replace("{{name}}", "World");
+///         async move { Ok::<_, std::convert::Infallible>(Resolved::changed(s)) }
+///     },
+///     &Config::default(),
+/// ).await?;
+///
+/// assert!(output.contains("// Greeting shown on start-up"));
+/// assert!(output.contains(r#"greeting: "Hello World","#));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve_preserving<R>(
+	input: &str,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, Json5Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	// Validate up front so the scanner below can assume well-formed input.
+	::json5::from_str::<serde::de::IgnoredAny>(input).map_err(Json5Error::Parse)?;
+
+	let bytes = input.as_bytes();
+	let mut out = String::with_capacity(input.len());
+	let mut stack: Vec<Frame> = Vec::new();
+	let mut path = Path::root();
+	let mut pos = 0;
+	let mut guard = Guard::new(config).map_err(Json5Error::Resolve)?;
+
+	while pos < bytes.len() {
+		let in_key = matches!(
+			stack.last(),
+			Some(Frame::Object {
+				expect_key: true,
+				..
+			})
+		);
+
+		match bytes[pos] {
+			b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+				let end = input[pos..].find('\n').map_or(bytes.len(), |i| pos + i);
+				out.push_str(&input[pos..end]);
+				pos = end;
+			}
+			b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+				let end = input[pos + 2..]
+					.find("*/")
+					.map_or(bytes.len(), |i| pos + 2 + i + 2);
+				out.push_str(&input[pos..end]);
+				pos = end;
+			}
+			c @ (b'{' | b'[') => {
+				check_depth(stack.len(), config)?;
+				if c == b'{' {
+					stack.push(Frame::Object {
+						expect_key: true,
+						keyed: false,
+					});
+				} else {
+					stack.push(Frame::Array);
+					path.push(PathSegment::Index(0));
+				}
+				out.push(char::from(c));
+				pos += 1;
+			}
+			c @ (b'}' | b']') => {
+				if let Some(Frame::Object { keyed: true, .. } | Frame::Array) = stack.pop() {
+					path.pop();
+				}
+				out.push(char::from(c));
+				pos += 1;
+			}
+			b':' => {
+				if let Some(Frame::Object { expect_key, .. }) = stack.last_mut() {
+					*expect_key = false;
+				}
+				out.push(':');
+				pos += 1;
+			}
+			b',' => {
+				match stack.last_mut() {
+					Some(Frame::Object { expect_key, keyed }) => {
+						*expect_key = true;
+						if core::mem::take(keyed) {
+							path.pop();
+						}
+					}
+					Some(Frame::Array) => {
+						if let Some(PathSegment::Index(index)) = path.pop() {
+							path.push(PathSegment::Index(index + 1));
+						}
+					}
+					None => {}
+				}
+				out.push(',');
+				pos += 1;
+			}
+			b'"' | b'\'' => {
+				let end = string_end(bytes, pos);
+				let literal = &input[pos..end];
+				let text: String = ::json5::from_str(literal).map_err(Json5Error::Parse)?;
+				if in_key && !config.resolve_keys {
+					out.push_str(literal);
+					enter_key(&mut stack, &mut path, text);
+				} else {
+					if !in_key {
+						check_depth(stack.len(), config)?;
+					}
+					let resolved = write_resolved(
+						&mut out, literal, &text, &path, resolver, config, &mut guard,
+					)
+					.await?;
+					if in_key {
+						enter_key(&mut stack, &mut path, resolved.unwrap_or(text));
+					}
+				}
+				pos = end;
+			}
+			c if in_key && is_ident_char(c) => {
+				let end = bytes[pos..]
+					.iter()
+					.position(|&b| !is_ident_char(b))
+					.map_or(bytes.len(), |i| pos + i);
+				let ident = &input[pos..end];
+				let key = if config.resolve_keys {
+					write_resolved(&mut out, ident, ident, &path, resolver, config, &mut guard).await?
+				} else {
+					out.push_str(ident);
+					None
+				};
+				enter_key(&mut stack, &mut path, key.unwrap_or_else(|| ident.into()));
+				pos = end;
+			}
+			_ => {
+				let ch = input[pos..].chars().next().unwrap_or_default();
+				if in_key || ch.is_whitespace() {
+					out.push(ch);
+					pos += ch.len_utf8();
+				} else {
+					// A number or literal such as `true`, `null` or `Infinity`
+					check_depth(stack.len(), config)?;
+					let end = input[pos..]
+						.find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '}' | '/'))
+						.map_or(bytes.len(), |i| pos + i);
+					out.push_str(&input[pos..end]);
+					pos = end;
+				}
+			}
+		}
+	}
+
+	Ok(out)
+}
+
+/// Container being scanned by [`resolve_preserving`].
+enum Frame {
+	/// `keyed` is set while the current member's key is on the path.
+	Object { expect_key: bool, keyed: bool },
+	/// The current element's index is on the path.
+	Array,
+}
+
+/// Put the key just read on the path.
+fn enter_key(stack: &mut [Frame], path: &mut Path, key: String) {
+	if let Some(Frame::Object { keyed, .. }) = stack.last_mut() {
+		*keyed = true;
+		path.push(PathSegment::Key(key));
+	}
+}
+
+fn check_depth<E>(depth: usize, config: &Config) -> Result<(), Json5Error<E>> {
+	if depth >= config.max_depth {
+		return Err(Json5Error::Resolve(Error::depth_exceeded(config.max_depth)));
+	}
+	Ok(())
+}
+
+/// Resolve `text` and write either the original token or the new JSON string.
+///
+/// Returns the new string if the resolver changed it.
+async fn write_resolved<R>(
+	out: &mut String,
+	original: &str,
+	text: &str,
+	path: &Path,
+	resolver: &R,
+	config: &Config,
+	guard: &mut Guard,
+) -> Result<Option<String>, Json5Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let resolved = guard
+		.run(path, crate::fixpoint::resolve_str(resolver, text, config))
+		.await
+		.map_err(Json5Error::Resolve)?;
+	#[cfg(feature = "tracing")]
+	crate::trace_resolved(text, &resolved);
+
+	let changed = resolved.into_changed();
+	match &changed {
+		Some(new_s) => {
+			let quoted = serde_json::to_string(new_s).map_err(Json5Error::Serialize)?;
+			out.push_str(&quoted);
+		}
+		None => out.push_str(original),
+	}
+	Ok(changed)
+}
+
+/// Byte offset just past the string literal starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+	let quote = bytes[start];
+	let mut pos = start + 1;
+	while pos < bytes.len() {
+		match bytes[pos] {
+			b'\\' => pos += 2,
+			b if b == quote => return pos + 1,
+			_ => pos += 1,
+		}
+	}
+	bytes.len()
+}
+
+/// Whether `b` can be part of an unquoted JSON5 key.
+fn is_ident_char(b: u8) -> bool {
+	b.is_ascii_alphanumeric() || matches!(b, b'_' | b'$' | b'\\') || !b.is_ascii()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use crate::testing::Expired;
+	use alloc::borrow::ToOwned;
+	use core::convert::Infallible;
+
+	const INPUT: &str = r#"{
+	// Database settings
+	database: {
+		host: '{{DB_HOST}}', /* resolved at startup */
+		"port": 5432,
+	},
+	tags: ["{{ENV}}", 'static',],
+}"#;
+
+	/// Resolves `{{NAME}}` placeholders to `name`.
+	struct Templates;
+
+	impl Resolver for Templates {
+		type Error = Infallible;
+
+		async fn resolve(&self, input: &str) -> Result<Resolved, Self::Error> {
+			if input.contains("{{") {
+				Ok(Resolved::changed(
+					input.replace("{{", "").replace("}}", "").to_lowercase(),
+				))
+			} else {
+				Ok(Resolved::unchanged())
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_resolve_str() {
+		let output = resolve_str(INPUT, &Templates, &Config::default())
+			.await
+			.unwrap();
+
+		let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+		assert_eq!(value["database"]["host"], "db_host");
+		assert_eq!(value["database"]["port"], 5432);
+		assert_eq!(value["tags"], serde_json::json!(["env", "static"]));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_batched() {
+		let upper = |batch: &[crate::Candidate]| {
+			let resolved = batch
+				.iter()
+				.map(|c| Resolved::changed(c.input.to_uppercase()))
+				.collect();
+			async move { Ok::<_, Infallible>(resolved) }
+		};
+		let output = resolve_batched(INPUT, &upper, &Config::default())
+			.await
+			.unwrap();
+
+		let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+		assert_eq!(value["database"]["host"], "{{DB_HOST}}");
+		assert_eq!(value["tags"], serde_json::json!(["{{ENV}}", "STATIC"]));
+	}
+
+	#[tokio::test]
+	async fn test_timeout_path() {
+		let config = Config::default()
+			.string_timeout(core::time::Duration::from_secs(1))
+			.timer(Expired);
+		// Keys are resolved at the path of their object
+		for (hang, resolve_keys, expected) in [
+			("{{DB_HOST}}", false, "database.host"),
+			("static", false, "tags[1]"),
+			("port", true, "database"),
+		] {
+			let resolver = |s: &str| {
+				let hangs = s == hang;
+				async move {
+					if hangs {
+						core::future::pending::<()>().await;
+					}
+					Ok::<_, Infallible>(Resolved::unchanged())
+				}
+			};
+			let config = config.clone().resolve_keys(resolve_keys);
+			let result = resolve_preserving(INPUT, &resolver, &config).await;
+			let Err(Json5Error::Resolve(Error::Timeout { path })) = result else {
+				panic!("expected timeout");
+			};
+			assert_eq!(path.to_string(), expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_resolve_preserving() {
+		let output = resolve_preserving(INPUT, &Templates, &Config::default())
+			.await
+			.unwrap();
+
+		let expected = r#"{
+	// Database settings
+	database: {
+		host: "db_host", /* resolved at startup */
+		"port": 5432,
+	},
+	tags: ["env", 'static',],
+}"#;
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_preserving_escapes() {
+		let input = r#"{ "a": "line\nbreak \"{{X}}\"", 'b': 'it\'s' }"#;
+		let output = resolve_preserving(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.replace("{{X}}", "x"))) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		let value: serde_json::Value = ::json5::from_str(&output).unwrap();
+		assert_eq!(value["a"], "line\nbreak \"x\"");
+		assert_eq!(value["b"], "it's");
+	}
+
+	#[tokio::test]
+	async fn test_preserving_keys() {
+		let input = "{ unquoted: 1, 'quoted': 2, /* note */ }";
+		let resolver = |s: &str| {
+			let s = s.to_owned();
+			async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+		};
+
+		let untouched = resolve_preserving(input, &resolver, &Config::default())
+			.await
+			.unwrap();
+		assert_eq!(untouched, input);
+
+		let output = resolve_preserving(input, &resolver, &Config::default().resolve_keys(true))
+			.await
+			.unwrap();
+		assert_eq!(output, r#"{ "UNQUOTED": 1, "QUOTED": 2, /* note */ }"#);
+	}
+
+	#[tokio::test]
+	async fn test_preserving_depth_limit() {
+		let input = r#"{ "a": { "b": ["c"] } }"#;
+		let result = resolve_preserving(input, &Templates, &Config::default().max_depth(2)).await;
+		assert!(matches!(
+			result,
+			Err(Json5Error::Resolve(Error::DepthExceeded { limit: 2 }))
+		));
+
+		let tree = resolve_str(input, &Templates, &Config::default().max_depth(2)).await;
+		assert!(matches!(
+			tree,
+			Err(Json5Error::Resolve(Error::DepthExceeded { limit: 2 }))
+		));
+	}
+
+	#[tokio::test]
+	async fn test_preserving_depth_matches_tree() {
+		let input = "{ a: [1, { b: 'c' }], d: null, e: -1.5e3, f: true, g: 'h' }";
+		for max_depth in 0..6 {
+			let config = Config::default().max_depth(max_depth);
+			let preserved = resolve_preserving(input, &Templates, &config).await;
+			let tree = resolve_str(input, &Templates, &config).await;
+			assert_eq!(preserved.is_ok(), tree.is_ok(), "max_depth = {max_depth}");
+			if let Err(e) = preserved {
+				assert!(matches!(
+					e,
+					Json5Error::Resolve(Error::DepthExceeded { .. })
+				));
+			}
+		}
+
+		let scalar = resolve_preserving("42", &Templates, &Config::default().max_depth(0)).await;
+		assert!(matches!(
+			scalar,
+			Err(Json5Error::Resolve(Error::DepthExceeded { limit: 0 }))
+		));
+	}
+
+	#[tokio::test]
+	async fn test_preserving_fixpoint() {
+		let expand = |s: &str| {
+			let resolved = match s {
+				"${URL}" => Resolved::changed("${HOST}/api"),
+				"${HOST}/api" => Resolved::changed("db/api"),
+				_ => Resolved::unchanged(),
+			};
+			async move { Ok::<_, Infallible>(resolved) }
+		};
+		let input = "{ url: '${URL}' /* expanded */ }";
+
+		let once = resolve_preserving(input, &expand, &Config::default())
+			.await
+			.unwrap();
+		assert_eq!(once, r#"{ url: "${HOST}/api" /* expanded */ }"#);
+
+		let settled = resolve_preserving(input, &expand, &Config::default().fixpoint(4))
+			.await
+			.unwrap();
+		assert_eq!(settled, r#"{ url: "db/api" /* expanded */ }"#);
+	}
+
+	#[tokio::test]
+	async fn test_preserving_deadlines() {
+		let stuck = |_: &str| core::future::pending::<Result<Resolved, Infallible>>();
+		let config = Config::default()
+			.string_timeout(core::time::Duration::from_secs(1))
+			.timer(Expired);
+		let result = resolve_preserving(INPUT, &stuck, &config).await;
+		assert!(matches!(
+			result,
+			Err(Json5Error::Resolve(Error::Timeout { .. }))
+		));
+
+		let token = crate::CancelToken::new();
+		token.cancel();
+		let result = resolve_preserving(INPUT, &Templates, &Config::default().cancel(token)).await;
+		assert!(matches!(result, Err(Json5Error::Resolve(Error::Cancelled))));
+	}
+
+	#[tokio::test]
+	async fn test_parse_error() {
+		let result = resolve_preserving("{ a: ", &Templates, &Config::default()).await;
+		assert!(matches!(result, Err(Json5Error::Parse(_))));
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let result = resolve_preserving(
+			"['hello']",
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(
+			result,
+			Err(Json5Error::Resolve(Error::Resolver(MyError)))
+		));
+	}
+}
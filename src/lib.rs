@@ -9,8 +9,23 @@
 //! - `json`: JSON support via serde_json (no_std compatible)
 //! - `yaml`: YAML support via serde_yaml (requires std)
 //! - `toml`: TOML support via toml crate (requires std)
+//! - `msgpack`: MessagePack support via rmpv (requires std)
+//! - `cbor`: CBOR support via ciborium (no_std compatible)
+//! - `ron`: RON support via ron crate (no_std compatible)
+//! - `json5`: JSON5/JSONC text support via json5 crate (requires std)
+//! - `properties`, `ini`, `dotenv`: Flat key/value file support (no_std compatible)
+//! - `stream`: Streaming JSON/NDJSON over `std::io` (requires std)
+//! - `tokio`: Async reader/writer support via tokio (requires std)
+//! - `schema`: JSON Schema guided resolution and validation (requires std)
+//! - `schemars`: Schemas derived from Rust types via schemars
+//! - `file`: Resolver reading sandboxed file contents (requires std)
+//! - `include`: Include directives splicing JSON/YAML/TOML files (requires std)
+//! - `secrecy`: Re-export of secrecy with serde support (no_std compatible)
 //! - `tracing`: Debug logging
 //!
+//! The [`text`] module resolves raw JSON, YAML or TOML text with format
+//! detection whenever any of those formats is enabled.
+//!
 //! ## Example
 //!
 //! ```rust
@@ -48,10 +63,11 @@
 extern crate alloc;
 
 use alloc::string::String;
+use alloc::vec::Vec;
 use core::future::Future;
 
-/// A segment in a value path, used for tracing.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A segment in a value path.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PathSegment {
 	/// Object/Map key
 	Key(String),
@@ -59,113 +75,104 @@ pub enum PathSegment {
 	Index(usize),
 }
 
-#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
-macro_rules! impl_resolve_recursive {
-    (
-        $value_type:ty,
-        $variant_string:path,
-        $variant_array:path,
-        $variant_object:path,
-        $map_constructor:expr,
-        $key_to_string:expr,
-        $resolver:ident, $config:ident, $depth:ident, $path:ident, $key:ident,
-        $resolve_key_logic:block,
-        { $($other_arms:tt)* }
-    ) => {
-        fn resolve_recursive<'a, R>(
-            value: $value_type,
-            $resolver: &'a R,
-            $config: &'a Config,
-            $depth: usize,
-            #[cfg(feature = "tracing")] $path: &'a mut alloc::vec::Vec<crate::PathSegment>,
-        ) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = Result<$value_type, crate::Error<R::Error>>> + Send + 'a>>
-        where
-            R: crate::Resolver,
-        {
-            alloc::boxed::Box::pin(async move {
-                if $depth >= $config.max_depth {
-                    return Err(crate::Error::depth_exceeded($config.max_depth));
-                }
-
-                #[cfg(feature = "tracing")]
-                tracing::trace!(depth = $depth, path = ?$path, value_type = ?value_type_name(&value), "resolving");
-
-                match value {
-                    $variant_string(s) => {
-                        match $resolver.resolve(&s).await.map_err(crate::Error::resolver)? {
-                            crate::Resolved::Changed(new_s) => {
-                                #[cfg(feature = "tracing")]
-                                tracing::trace!(original = %s, resolved = %new_s, "string changed");
-                                Ok($variant_string(new_s))
-                            }
-                            crate::Resolved::Unchanged => {
-                                #[cfg(feature = "tracing")]
-                                tracing::trace!(value = %s, "string unchanged");
-                                Ok($variant_string(s))
-                            }
-                        }
-                    }
-
-                    $variant_array(arr) => {
-                        let mut result = alloc::vec::Vec::with_capacity(arr.len());
-                        for (_i, item) in arr.into_iter().enumerate() {
-                            #[cfg(feature = "tracing")]
-                            $path.push(crate::PathSegment::Index(_i));
-
-                            let res = resolve_recursive(
-                                item,
-                                $resolver,
-                                $config,
-                                $depth + 1,
-                                #[cfg(feature = "tracing")] $path
-                            ).await?;
-                            result.push(res);
-
-                            #[cfg(feature = "tracing")]
-                            $path.pop();
-                        }
-                        Ok($variant_array(result))
-                    }
-
-                    $variant_object(map) => {
-                        let mut result = $map_constructor(map.len());
-                        for ($key, val) in map {
-                            // Helper to get key string for tracing
-                            #[cfg(feature = "tracing")]
-                            let key_str = ($key_to_string)(&$key);
-
-                            // Optionally resolve keys
-                            let resolved_key = if $config.resolve_keys {
-                                $resolve_key_logic
-                            } else {
-                                $key
-                            };
-
-                            #[cfg(feature = "tracing")]
-                            $path.push(crate::PathSegment::Key(key_str));
-
-                            let resolved_val = resolve_recursive(
-                                val,
-                                $resolver,
-                                $config,
-                                $depth + 1,
-                                #[cfg(feature = "tracing")] $path
-                            ).await?;
-                            result.insert(resolved_key, resolved_val);
-
-                            #[cfg(feature = "tracing")]
-                            $path.pop();
-                        }
-                        Ok($variant_object(result))
-                    }
-
-                    $($other_arms)*
-                }
-            })
-        }
-    }
+/// Location of a value within a document, such as `servers[1].port`.
+///
+/// The root path displays as `.`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Path {
+	segments: Vec<PathSegment>,
+}
+
+impl Path {
+	/// The root path.
+	#[inline]
+	#[must_use]
+	pub const fn root() -> Self {
+		Self {
+			segments: Vec::new(),
+		}
+	}
+
+	/// Segments from the root down.
+	#[inline]
+	#[must_use]
+	pub fn segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	/// Returns `true` for the root path.
+	#[inline]
+	#[must_use]
+	pub fn is_root(&self) -> bool {
+		self.segments.is_empty()
+	}
+
+	/// Append a segment.
+	#[inline]
+	pub fn push(&mut self, segment: PathSegment) {
+		self.segments.push(segment);
+	}
+
+	/// Remove the last segment.
+	#[inline]
+	pub fn pop(&mut self) -> Option<PathSegment> {
+		self.segments.pop()
+	}
+}
+
+impl From<Vec<PathSegment>> for Path {
+	#[inline]
+	fn from(segments: Vec<PathSegment>) -> Self {
+		Self { segments }
+	}
+}
+
+impl FromIterator<PathSegment> for Path {
+	fn from_iter<I: IntoIterator<Item = PathSegment>>(iter: I) -> Self {
+		Self {
+			segments: iter.into_iter().collect(),
+		}
+	}
+}
+
+impl core::fmt::Display for Path {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		if self.segments.is_empty() {
+			return f.write_str(".");
+		}
+		for (i, segment) in self.segments.iter().enumerate() {
+			match segment {
+				PathSegment::Key(key) if i == 0 => f.write_str(key)?,
+				PathSegment::Key(key) => write!(f, ".{key}")?,
+				PathSegment::Index(index) => write!(f, "[{index}]")?,
+			}
+		}
+		Ok(())
+	}
 }
 
+pub mod de;
+
+mod batch;
+mod deadline;
+mod dynamic;
+mod fixpoint;
+mod value;
+
+#[cfg(test)]
+mod testing;
+
+pub use batch::{BatchResolver, Candidate, resolve_batched};
+#[cfg(feature = "tokio")]
+pub use deadline::TokioTimer;
+#[cfg(feature = "std")]
+pub use deadline::{CancelToken, Timer};
+pub use dynamic::{BoxFuture, DynError, DynResolver};
+pub use value::{
+	Change, Origin, Provenance, Report, ResolvableValue, ScalarResolver, ValueKind, resolve,
+	resolve_with_provenance, resolve_with_report, resolve_with_scalars,
+};
+
 #[cfg(feature = "json")]
 pub mod json;
 
@@ -175,11 +182,71 @@ pub mod yaml;
 #[cfg(feature = "toml")]
 pub mod toml;
 
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+
+#[cfg(feature = "ron")]
+pub mod ron;
+
+#[cfg(feature = "json5")]
+pub mod json5;
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+pub mod text;
+
+#[cfg(any(feature = "properties", feature = "ini", feature = "dotenv"))]
+pub mod flat;
+
+#[cfg(feature = "properties")]
+pub mod properties;
+
+pub mod scheme;
+
+#[cfg(feature = "std")]
+pub mod retry;
+
+#[cfg(feature = "std")]
+pub mod limit;
+
+#[cfg(feature = "file")]
+pub mod file;
+
+#[cfg(feature = "include")]
+pub mod include;
+
+#[cfg(feature = "ini")]
+pub mod ini;
+
+#[cfg(feature = "dotenv")]
+pub mod dotenv;
+
+/// Re-export of [`secrecy`](https://docs.rs/secrecy) with serde support.
+///
+/// Declare fields that receive [`Resolved::Secret`] values as
+/// [`secrecy::SecretString`] to keep them redacted after deserialization.
+#[cfg(feature = "secrecy")]
+pub use secrecy;
+
+/// Placeholder shown instead of secret values.
+pub const REDACTED: &str = "[REDACTED]";
+
 /// Result of resolving a single string.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// The [`Debug`](core::fmt::Debug) output of [`Resolved::Secret`] hides the
+/// value.
+#[derive(Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Resolved {
 	/// The string was transformed to a new value.
 	Changed(String),
+	/// The string was transformed to a sensitive value.
+	///
+	/// Secrets are used like [`Resolved::Changed`] values, but are never
+	/// logged and are shown as [`REDACTED`] in reports and errors.
+	Secret(String),
 	/// The string should remain unchanged (skip transformation).
 	Unchanged,
 }
@@ -191,6 +258,12 @@ impl Resolved {
 		Self::Changed(s.into())
 	}
 
+	/// Create a `Secret` variant.
+	#[inline]
+	pub fn secret(s: impl Into<String>) -> Self {
+		Self::Secret(s.into())
+	}
+
 	/// Create an `Unchanged` variant.
 	#[inline]
 	#[must_use]
@@ -198,11 +271,32 @@ impl Resolved {
 		Self::Unchanged
 	}
 
-	/// Returns `true` if this is `Changed`.
+	/// Annotate this result with the source it came from, such as
+	/// `env:DB_HOST` or `file:/run/secrets/db`.
+	///
+	/// Return the [`Sourced`] result from [`Resolver::resolve_sourced`] (or
+	/// from a resolver closure) to have [`resolve_with_provenance`] record it.
+	#[inline]
+	#[must_use]
+	pub fn with_source(self, source: impl Into<String>) -> Sourced {
+		Sourced {
+			resolved: self,
+			source: Some(source.into()),
+		}
+	}
+
+	/// Returns `true` if this is `Changed` or `Secret`.
 	#[inline]
 	#[must_use]
 	pub const fn is_changed(&self) -> bool {
-		matches!(self, Self::Changed(_))
+		matches!(self, Self::Changed(_) | Self::Secret(_))
+	}
+
+	/// Returns `true` if this is `Secret`.
+	#[inline]
+	#[must_use]
+	pub const fn is_secret(&self) -> bool {
+		matches!(self, Self::Secret(_))
 	}
 
 	/// Returns `true` if this is `Unchanged`.
@@ -211,6 +305,36 @@ impl Resolved {
 	pub const fn is_unchanged(&self) -> bool {
 		matches!(self, Self::Unchanged)
 	}
+
+	/// The new value of a `Changed` or `Secret` result.
+	#[inline]
+	#[must_use]
+	pub fn into_changed(self) -> Option<String> {
+		match self {
+			Self::Changed(s) | Self::Secret(s) => Some(s),
+			Self::Unchanged => None,
+		}
+	}
+}
+
+impl core::fmt::Debug for Resolved {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Changed(s) => f.debug_tuple("Changed").field(s).finish(),
+			Self::Secret(_) => f.debug_tuple("Secret").field(&REDACTED).finish(),
+			Self::Unchanged => f.write_str("Unchanged"),
+		}
+	}
+}
+
+/// Log the outcome of resolving `original`, hiding secrets.
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_resolved(original: &str, resolved: &Resolved) {
+	match resolved {
+		Resolved::Changed(new_s) => tracing::trace!(original, resolved = %new_s, "string changed"),
+		Resolved::Secret(_) => tracing::trace!(original, resolved = REDACTED, "string changed"),
+		Resolved::Unchanged => tracing::trace!(value = original, "string unchanged"),
+	}
 }
 
 impl From<String> for Resolved {
@@ -227,6 +351,28 @@ impl<'a> From<&'a str> for Resolved {
 	}
 }
 
+/// A [`Resolved`] result together with the source it came from.
+///
+/// Created with [`Resolved::with_source`], or from a plain [`Resolved`]
+/// without a source. Sources are recorded by [`resolve_with_provenance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sourced {
+	/// The result.
+	pub resolved: Resolved,
+	/// Description of the source, such as `env:DB_HOST`.
+	pub source: Option<String>,
+}
+
+impl From<Resolved> for Sourced {
+	#[inline]
+	fn from(resolved: Resolved) -> Self {
+		Self {
+			resolved,
+			source: None,
+		}
+	}
+}
+
 /// Configuration for resolve operations.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -239,6 +385,56 @@ pub struct Config {
 	///
 	/// When `true`, object keys are also passed to the resolver.
 	pub resolve_keys: bool,
+
+	/// How strings are coerced into the scalar type a field expects.
+	/// Default: [`Coercion::Off`].
+	///
+	/// Only applies when deserializing into a target type, i.e. [`de`] and
+	/// `resolve_struct`.
+	pub coercion: Coercion,
+
+	/// Maximum number of times re-resolving a string may change it again.
+	/// Default: `None`.
+	///
+	/// With `Some(limit)`, a changed string is passed to the resolver again
+	/// until it comes back unchanged, so `${BASE_URL}/api` can expand through
+	/// `${SCHEME}://${HOST}/api`. A string still changing after `limit`
+	/// re-resolutions, or producing a value seen before, fails with
+	/// [`Error::Cycle`]. With 0, resolved strings must already be settled.
+	pub fixpoint: Option<usize>,
+
+	/// Maximum number of strings passed to a [`BatchResolver`] in one call.
+	/// Default: 100.
+	///
+	/// Only applies to [`resolve_batched`]; values below 1 are treated as 1.
+	pub batch_size: usize,
+
+	/// Deadline for a whole resolve operation. Default: `None`.
+	///
+	/// Exceeding it fails with [`Error::Timeout`] for the string being
+	/// resolved at that moment. Requires [`Config::timer`]; without one the
+	/// operation fails with [`Error::MissingTimer`].
+	#[cfg(feature = "std")]
+	pub timeout: Option<core::time::Duration>,
+
+	/// Deadline for resolving a single string, including all
+	/// [`fixpoint`](Config::fixpoint) passes. Default: `None`.
+	///
+	/// Exceeding it fails with [`Error::Timeout`]. Requires
+	/// [`Config::timer`]; without one the operation fails with
+	/// [`Error::MissingTimer`].
+	#[cfg(feature = "std")]
+	pub string_timeout: Option<core::time::Duration>,
+
+	/// Timer enforcing the timeouts, e.g. [`TokioTimer`] with the `tokio`
+	/// feature. Default: `None`.
+	#[cfg(feature = "std")]
+	pub timer: Option<alloc::sync::Arc<dyn Timer>>,
+
+	/// Signal cancelling the operation with [`Error::Cancelled`].
+	/// Default: `None`.
+	#[cfg(feature = "std")]
+	pub cancel: Option<CancelToken>,
 }
 
 impl Default for Config {
@@ -246,6 +442,17 @@ impl Default for Config {
 		Self {
 			max_depth: 32,
 			resolve_keys: false,
+			coercion: Coercion::Off,
+			fixpoint: None,
+			batch_size: 100,
+			#[cfg(feature = "std")]
+			timeout: None,
+			#[cfg(feature = "std")]
+			string_timeout: None,
+			#[cfg(feature = "std")]
+			timer: None,
+			#[cfg(feature = "std")]
+			cancel: None,
 		}
 	}
 }
@@ -274,6 +481,67 @@ impl Config {
 		self
 	}
 
+	/// Set the coercion mode.
+	#[inline]
+	#[must_use]
+	pub fn coercion(mut self, coercion: Coercion) -> Self {
+		self.coercion = coercion;
+		self
+	}
+
+	/// Re-resolve changed strings until they settle, letting them change at
+	/// most `limit` more times.
+	#[inline]
+	#[must_use]
+	pub fn fixpoint(mut self, limit: usize) -> Self {
+		self.fixpoint = Some(limit);
+		self
+	}
+
+	/// Set the maximum number of strings per [`BatchResolver`] call.
+	#[inline]
+	#[must_use]
+	pub fn batch_size(mut self, size: usize) -> Self {
+		self.batch_size = size;
+		self
+	}
+
+	/// Set the deadline for the whole operation.
+	#[cfg(feature = "std")]
+	#[inline]
+	#[must_use]
+	pub fn timeout(mut self, timeout: core::time::Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	/// Set the deadline for resolving a single string.
+	#[cfg(feature = "std")]
+	#[inline]
+	#[must_use]
+	pub fn string_timeout(mut self, timeout: core::time::Duration) -> Self {
+		self.string_timeout = Some(timeout);
+		self
+	}
+
+	/// Set the timer enforcing the timeouts.
+	#[cfg(feature = "std")]
+	#[inline]
+	#[must_use]
+	pub fn timer(mut self, timer: impl Timer + 'static) -> Self {
+		self.timer = Some(alloc::sync::Arc::new(timer));
+		self
+	}
+
+	/// Set the token cancelling the operation.
+	#[cfg(feature = "std")]
+	#[inline]
+	#[must_use]
+	pub fn cancel(mut self, token: CancelToken) -> Self {
+		self.cancel = Some(token);
+		self
+	}
+
 	/// Disable depth limiting.
 	///
 	/// # Warning
@@ -288,8 +556,38 @@ impl Config {
 	}
 }
 
+/// Coercion of strings into the scalar type a target field expects.
+///
+/// Resolved values are usually strings, so `port: "${PORT}"` stays a string
+/// even when `port` is a `u16`. With coercion enabled, a string deserialized
+/// into a number, a `bool`, `()` or an `Option` is parsed first; strings that
+/// do not parse are passed on unchanged and fail with the usual type error.
+/// Strings the resolver left unchanged are coerced as well, so a source whose
+/// fields are all strings can be loaded into a typed target. Null-like
+/// strings become `None` only in an `Option` whose inner type rejects them;
+/// an `Option<String>` keeps them.
+///
+/// Coercion needs a self-describing input format: when deserializing with
+/// [`de::ResolvingDeserializer`], scalar targets are read with
+/// `deserialize_any`, which formats such as bincode or postcard do not
+/// support.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Coercion {
+	/// Strings are never coerced.
+	#[default]
+	Off,
+	/// Only exact literals are coerced: `true`, `false`, `null` and numbers as
+	/// accepted by [`str::parse`].
+	Strict,
+	/// Like [`Coercion::Strict`], but surrounding whitespace is ignored,
+	/// booleans also accept `yes`/`no`, `on`/`off` and `1`/`0` in any case, and
+	/// `~`, empty strings and `NULL` count as null.
+	Lenient,
+}
+
 /// Error type for resolve operations.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error<E> {
 	/// The resolver returned an error.
 	Resolver(E),
@@ -298,6 +596,32 @@ pub enum Error<E> {
 		/// The configured limit that was exceeded.
 		limit: usize,
 	},
+	/// Re-resolving a string did not settle; see [`Config::fixpoint`].
+	Cycle {
+		/// The original string followed by each rewrite, ending with the
+		/// repeated value or the one exceeding the limit. Values derived from
+		/// secrets are shown as [`REDACTED`].
+		chain: Vec<String>,
+	},
+	/// A deadline passed; see [`Config::timeout`] and
+	/// [`Config::string_timeout`].
+	Timeout {
+		/// Location of the string being resolved.
+		path: Path,
+	},
+	/// The operation was cancelled through [`Config::cancel`].
+	Cancelled,
+	/// [`Config::timeout`] or [`Config::string_timeout`] is set without
+	/// [`Config::timer`].
+	MissingTimer,
+	/// A [`BatchResolver`] returned a different number of results than it
+	/// was given strings.
+	BatchMismatch {
+		/// Number of strings in the batch.
+		expected: usize,
+		/// Number of results returned.
+		returned: usize,
+	},
 }
 
 impl<E: core::fmt::Display> core::fmt::Display for Error<E> {
@@ -305,6 +629,25 @@ impl<E: core::fmt::Display> core::fmt::Display for Error<E> {
 		match self {
 			Self::Resolver(e) => write!(f, "resolver error: {e}"),
 			Self::DepthExceeded { limit } => write!(f, "depth limit ({limit}) exceeded"),
+			Self::Cycle { chain } => {
+				f.write_str("resolution did not settle: ")?;
+				for (i, value) in chain.iter().enumerate() {
+					if i > 0 {
+						f.write_str(" -> ")?;
+					}
+					write!(f, "{value:?}")?;
+				}
+				Ok(())
+			}
+			Self::Timeout { path } => write!(f, "timed out resolving {path}"),
+			Self::Cancelled => f.write_str("resolution cancelled"),
+			Self::MissingTimer => f.write_str("timeout configured without a timer"),
+			Self::BatchMismatch { expected, returned } => {
+				write!(
+					f,
+					"batch resolver returned {returned} results for {expected} strings"
+				)
+			}
 		}
 	}
 }
@@ -314,7 +657,12 @@ impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		match self {
 			Self::Resolver(e) => Some(e),
-			Self::DepthExceeded { .. } => None,
+			Self::DepthExceeded { .. }
+			| Self::Cycle { .. }
+			| Self::Timeout { .. }
+			| Self::Cancelled
+			| Self::MissingTimer
+			| Self::BatchMismatch { .. } => None,
 		}
 	}
 }
@@ -332,12 +680,37 @@ impl<E> Error<E> {
 	pub fn depth_exceeded(limit: usize) -> Self {
 		Self::DepthExceeded { limit }
 	}
+
+	/// Create a timeout error.
+	#[inline]
+	#[must_use]
+	pub fn timeout(path: Path) -> Self {
+		Self::Timeout { path }
+	}
+}
+
+impl Error<core::convert::Infallible> {
+	/// Convert an error that cannot come from a resolver into one carrying
+	/// the resolver error type `E`.
+	#[must_use]
+	pub fn widen<E>(self) -> Error<E> {
+		match self {
+			Self::Resolver(never) => match never {},
+			Self::DepthExceeded { limit } => Error::DepthExceeded { limit },
+			Self::Cycle { chain } => Error::Cycle { chain },
+			Self::Timeout { path } => Error::Timeout { path },
+			Self::Cancelled => Error::Cancelled,
+			Self::MissingTimer => Error::MissingTimer,
+			Self::BatchMismatch { expected, returned } => Error::BatchMismatch { expected, returned },
+		}
+	}
 }
 
 /// Trait for async string resolvers.
 ///
 /// Implementors decide:
-/// - Which strings to transform ([`Resolved::Changed`])
+/// - Which strings to transform ([`Resolved::Changed`], or
+///   [`Resolved::Secret`] for sensitive values)
 /// - Which strings to skip ([`Resolved::Unchanged`])
 /// - When to abort with an error ([`Err`])
 ///
@@ -369,57 +742,180 @@ pub trait Resolver: Send + Sync {
 	/// # Returns
 	///
 	/// - `Ok(Resolved::Changed(new_value))` - Use the transformed value
+	/// - `Ok(Resolved::Secret(new_value))` - Use the transformed value, keeping it
+	///   out of logs, reports and errors
 	/// - `Ok(Resolved::Unchanged)` - Keep the original value
 	/// - `Err(e)` - Abort the entire resolve operation
 	fn resolve(&self, input: &str) -> impl Future<Output = Result<Resolved, Self::Error>> + Send;
+
+	/// Resolve a string value, along with the source it came from.
+	///
+	/// Called instead of [`Resolver::resolve`] by [`resolve_with_provenance`].
+	/// The default reports no source; override it to return
+	/// [`Resolved::with_source`] results.
+	fn resolve_sourced(
+		&self,
+		input: &str,
+	) -> impl Future<Output = Result<Sourced, Self::Error>> + Send {
+		async move { self.resolve(input).await.map(Sourced::from) }
+	}
 }
 
-impl<F, Fut, E> Resolver for F
+/// Closures may return either [`Resolved`] or [`Sourced`] results.
+impl<F, Fut, T, E> Resolver for F
 where
 	F: Fn(&str) -> Fut + Send + Sync,
-	Fut: Future<Output = Result<Resolved, E>> + Send,
+	Fut: Future<Output = Result<T, E>> + Send,
+	T: Into<Sourced>,
 	E: Send,
 {
 	type Error = E;
 
 	#[inline]
 	fn resolve(&self, input: &str) -> impl Future<Output = Result<Resolved, Self::Error>> + Send {
+		let fut = self(input);
+		async move { fut.await.map(|result| result.into().resolved) }
+	}
+
+	#[inline]
+	fn resolve_sourced(
+		&self,
+		input: &str,
+	) -> impl Future<Output = Result<Sourced, Self::Error>> + Send {
+		let fut = self(input);
+		async move { fut.await.map(Into::into) }
+	}
+}
+
+/// Trait for synchronous string resolvers.
+///
+/// Used where awaiting is not possible, such as inside a serde
+/// [`Deserializer`](serde::Deserializer); see [`de::ResolvingDeserializer`].
+///
+/// # Example
+///
+/// ```rust
+/// use serde_resolve::{Resolved, SyncResolver};
+///
+/// struct Env;
+///
+/// impl SyncResolver for Env {
+///     type Error = std::convert::Infallible;
+///
+///     fn resolve(&self, input: &str) -> Result<Resolved, Self::Error> {
+///         match input.strip_prefix("env:") {
+///             Some(name) => Ok(Resolved::changed(std::env::var(name).unwrap_or_default())),
+///             None => Ok(Resolved::unchanged()),
+///         }
+///     }
+/// }
+/// ```
+pub trait SyncResolver {
+	/// Error type returned by this resolver.
+	type Error;
+
+	/// Resolve a string value.
+	///
+	/// # Errors
+	///
+	/// Returning an error aborts the entire operation.
+	fn resolve(&self, input: &str) -> Result<Resolved, Self::Error>;
+}
+
+impl<F, E> SyncResolver for F
+where
+	F: Fn(&str) -> Result<Resolved, E>,
+{
+	type Error = E;
+
+	#[inline]
+	fn resolve(&self, input: &str) -> Result<Resolved, Self::Error> {
 		self(input)
 	}
 }
 
+/// Default serializer/deserializer error of [`StructResolveError`].
+#[cfg(feature = "json")]
+type DefaultCodecError = serde_json::Error;
+#[cfg(all(not(feature = "json"), any(feature = "yaml", feature = "toml")))]
+type DefaultCodecError = core::convert::Infallible;
+
 /// Error type for generic struct resolution.
 ///
 /// This error type wraps errors that can occur during the serialize-resolve-deserialize
-/// round-trip when using [`resolve_struct`].
-#[cfg(feature = "json")]
+/// round-trip when using [`resolve_struct`] or the format-specific variants such as
+/// `yaml::resolve_struct` and `toml::resolve_struct`. `S` and `D` are the
+/// intermediate format's serialization and deserialization errors.
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
 #[derive(Debug)]
-pub enum StructResolveError<E> {
+pub enum StructResolveError<E, S = DefaultCodecError, D = S> {
 	/// Serialization error.
-	Serialize(serde_json::Error),
+	Serialize(S),
 	/// Resolution error.
 	Resolve(Error<E>),
 	/// Deserialization error.
-	Deserialize(serde_json::Error),
+	Deserialize {
+		/// Path of the value that failed to deserialize.
+		path: Path,
+		/// String at `path` before resolution, if the resolver changed it.
+		original: Option<String>,
+		/// String at `path` after resolution, if the resolver changed it.
+		resolved: Option<String>,
+		/// Underlying deserialization error, withheld if the string at `path`
+		/// is a secret since it may quote the value.
+		source: Option<D>,
+	},
 }
 
-#[cfg(feature = "json")]
-impl<E: core::fmt::Display> core::fmt::Display for StructResolveError<E> {
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+impl<E, S, D> core::fmt::Display for StructResolveError<E, S, D>
+where
+	E: core::fmt::Display,
+	S: core::fmt::Display,
+	D: core::fmt::Display,
+{
 	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		match self {
 			Self::Serialize(e) => write!(f, "serialization error: {e}"),
 			Self::Resolve(e) => write!(f, "resolution error: {e}"),
-			Self::Deserialize(e) => write!(f, "deserialization error: {e}"),
+			Self::Deserialize {
+				path,
+				original: Some(original),
+				resolved: Some(resolved),
+				source,
+			} => {
+				write!(
+					f,
+					"deserialization error at `{path}` (resolved {original:?} to {resolved:?}): "
+				)?;
+				match source {
+					Some(source) => write!(f, "{source}"),
+					None => f.write_str("invalid value"),
+				}
+			}
+			Self::Deserialize { path, source, .. } => match source {
+				Some(source) => write!(f, "deserialization error at `{path}`: {source}"),
+				None => write!(f, "deserialization error at `{path}`: invalid value"),
+			},
 		}
 	}
 }
 
-#[cfg(all(feature = "json", feature = "std"))]
-impl<E: std::error::Error + 'static> std::error::Error for StructResolveError<E> {
+#[cfg(all(
+	any(feature = "json", feature = "yaml", feature = "toml"),
+	feature = "std"
+))]
+impl<E, S, D> std::error::Error for StructResolveError<E, S, D>
+where
+	E: std::error::Error + 'static,
+	S: std::error::Error + 'static,
+	D: std::error::Error + 'static,
+{
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		match self {
-			Self::Serialize(e) | Self::Deserialize(e) => Some(e),
+			Self::Serialize(e) => Some(e),
 			Self::Resolve(e) => Some(e),
+			Self::Deserialize { source, .. } => source.as_ref().map(|e| e as _),
 		}
 	}
 }
@@ -444,13 +940,139 @@ pub async fn resolve_struct<T, R>(
 ) -> Result<T, StructResolveError<R::Error>>
 where
 	T: serde::Serialize + serde::de::DeserializeOwned,
-	R: Resolver,
+	R: Resolver + ?Sized,
+{
+	resolve_into(value, resolver, config).await
+}
+
+/// Resolve strings in a serializable value and deserialize the result into
+/// another type via JSON round-trip.
+///
+/// Combined with [`Config::coercion`], this turns a source whose fields are
+/// all strings into a typed target:
+///
+/// ```rust
+/// use serde_resolve::{Coercion, Config, Resolved};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let raw = serde_json::json!({ "port": "${PORT}" });
+/// let server: Server = serde_resolve::resolve_into(
+///     raw,
+///     &|_: &str| async { Ok::<_, std::convert::Infallible>(Resolved::changed("8080")) },
+///     &Config::default().coercion(Coercion::Strict),
+/// )
+/// .await?;
+///
+/// assert_eq!(server.port, 8080);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Same as [`resolve_struct`].
+#[cfg(feature = "json")]
+pub async fn resolve_into<S, T, R>(
+	value: S,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error>>
+where
+	S: serde::Serialize,
+	T: serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
 {
 	let json = serde_json::to_value(value).map_err(StructResolveError::Serialize)?;
-	let resolved = json::resolve(json, resolver, config)
-		.await
-		.map_err(StructResolveError::Resolve)?;
-	serde_json::from_value(resolved).map_err(StructResolveError::Deserialize)
+	resolve_struct_via(json, resolver, config).await
+}
+
+/// Resolve an intermediate value tree and deserialize it into `T`.
+///
+/// Deserialization errors carry the failing path and, if the resolver changed
+/// the string there, its original and resolved forms. Strings are coerced as
+/// configured by [`Config::coercion`].
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+pub(crate) async fn resolve_struct_via<V, T, R, S, D>(
+	value: V,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error, S, D>>
+where
+	V: ResolvableValue + serde::Deserializer<'static, Error = D>,
+	T: serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	let mut trace = value::Trace::recording();
+	let resolved = value::resolve_with(
+		value,
+		resolver,
+		&value::PassThrough(core::marker::PhantomData),
+		config,
+		&mut trace,
+	)
+	.await
+	.map_err(StructResolveError::Resolve)?;
+
+	let deserialized = if config.coercion == Coercion::Off {
+		serde_path_to_error::deserialize(resolved)
+	} else {
+		// Strings are already resolved and the depth already checked. The
+		// path is tracked beneath the coercion so that `Option` visitors
+		// reach it unwrapped.
+		let config = config.clone().unlimited_depth();
+		let mut track = serde_path_to_error::Track::new();
+		T::deserialize(de::ResolvingDeserializer::new(
+			serde_path_to_error::Deserializer::new(resolved, &mut track),
+			&de::Unchanged,
+			&config,
+		))
+		.map_err(|e| serde_path_to_error::Error::new(track.path(), e))
+	};
+	deserialized.map_err(|e| {
+		// A segment serde could not name (e.g. a non-string map key) ends the
+		// path there, and no change is looked up for the truncated path.
+		let path: Path = e
+			.path()
+			.iter()
+			.map_while(|segment| match segment {
+				serde_path_to_error::Segment::Seq { index } => Some(PathSegment::Index(*index)),
+				serde_path_to_error::Segment::Map { key }
+				| serde_path_to_error::Segment::Enum { variant: key } => Some(PathSegment::Key(key.clone())),
+				serde_path_to_error::Segment::Unknown => None,
+			})
+			.collect();
+		let complete = !e
+			.path()
+			.iter()
+			.any(|segment| matches!(segment, serde_path_to_error::Segment::Unknown));
+		let change = complete
+			.then(|| {
+				trace
+					.changes
+					.unwrap_or_default()
+					.into_iter()
+					.find(|change| change.path == path)
+			})
+			.flatten();
+		let secret = change
+			.as_ref()
+			.is_some_and(|change| change.resolved.is_none());
+		let (original, resolved) = change.map_or((None, None), |change| {
+			let resolved = change.resolved.unwrap_or_else(|| REDACTED.into());
+			(Some(change.original), Some(resolved))
+		});
+		StructResolveError::Deserialize {
+			path,
+			original,
+			resolved,
+			source: (!secret).then(|| e.into_inner()),
+		}
+	})
 }
 
 #[cfg(test)]
@@ -474,6 +1096,27 @@ mod tests {
 		assert_eq!(r, Resolved::Unchanged);
 	}
 
+	#[test]
+	fn test_resolved_secret() {
+		let r = Resolved::secret("hunter2");
+		assert!(r.is_secret());
+		assert!(r.is_changed());
+		assert_eq!(alloc::format!("{r:?}"), "Secret(\"[REDACTED]\")");
+		assert_eq!(r.into_changed().as_deref(), Some("hunter2"));
+	}
+
+	#[test]
+	fn test_resolved_with_source() {
+		let r = Resolved::secret("hunter2").with_source("env:PASS");
+		assert_eq!(r.source.as_deref(), Some("env:PASS"));
+		assert_eq!(r.resolved, Resolved::secret("hunter2"));
+		assert_eq!(
+			alloc::format!("{r:?}"),
+			"Sourced { resolved: Secret(\"[REDACTED]\"), source: Some(\"env:PASS\") }"
+		);
+		assert_eq!(Sourced::from(Resolved::unchanged()).source, None);
+	}
+
 	#[test]
 	fn test_resolved_from_string() {
 		let r: Resolved = String::from("test").into();
@@ -515,6 +1158,15 @@ mod tests {
 		assert_eq!(err.to_string(), "depth limit (10) exceeded");
 	}
 
+	#[test]
+	fn test_error_widen() {
+		let err: Error<&str> = Error::<core::convert::Infallible>::Cancelled.widen();
+		assert!(matches!(err, Error::Cancelled));
+
+		let err: Error<&str> = Error::<core::convert::Infallible>::depth_exceeded(3).widen();
+		assert!(matches!(err, Error::DepthExceeded { limit: 3 }));
+	}
+
 	#[test]
 	fn test_path_segment() {
 		let key = PathSegment::Key("foo".to_string());
@@ -532,6 +1184,7 @@ mod json_tests {
 	use alloc::string::ToString;
 	use core::convert::Infallible;
 	use serde::{Deserialize, Serialize};
+	use serde_json::json;
 
 	#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 	struct TestStruct {
@@ -598,4 +1251,228 @@ mod json_tests {
 
 		assert!(matches!(result, Err(StructResolveError::Resolve(_))));
 	}
+
+	#[tokio::test]
+	async fn test_resolve_struct_deserialize_error_path() {
+		use crate::testing::Port;
+		use alloc::borrow::ToOwned;
+		use alloc::vec;
+
+		#[derive(Debug, Serialize, Deserialize)]
+		struct Server {
+			port: Port,
+		}
+
+		#[derive(Debug, Serialize, Deserialize)]
+		struct Servers {
+			servers: Vec<Server>,
+		}
+
+		let input = Servers {
+			servers: vec![
+				Server {
+					port: Port("80".to_owned()),
+				},
+				Server {
+					port: Port("{{PORT}}".to_owned()),
+				},
+			],
+		};
+
+		let result = resolve_struct(
+			input,
+			&|s: &str| {
+				let resolved = if s == "{{PORT}}" {
+					Resolved::changed("abc")
+				} else {
+					Resolved::unchanged()
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default(),
+		)
+		.await;
+
+		let Err(err @ StructResolveError::Deserialize { .. }) = result else {
+			panic!("expected deserialize error");
+		};
+		assert_eq!(
+			err.to_string(),
+			"deserialization error at `servers[1].port` (resolved \"{{PORT}}\" to \"abc\"): invalid digit found in string"
+		);
+		let StructResolveError::Deserialize {
+			path,
+			original,
+			resolved,
+			..
+		} = err
+		else {
+			unreachable!()
+		};
+		assert_eq!(
+			path.segments(),
+			[
+				PathSegment::Key("servers".to_owned()),
+				PathSegment::Index(1),
+				PathSegment::Key("port".to_owned())
+			]
+		);
+		assert_eq!(original.as_deref(), Some("{{PORT}}"));
+		assert_eq!(resolved.as_deref(), Some("abc"));
+	}
+
+	#[test]
+	fn test_path_display() {
+		let path: Path = [
+			PathSegment::Key("a".into()),
+			PathSegment::Index(0),
+			PathSegment::Index(2),
+			PathSegment::Key("b".into()),
+		]
+		.into_iter()
+		.collect();
+		assert_eq!(path.to_string(), "a[0][2].b");
+		assert_eq!(Path::root().to_string(), ".");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_into_coercion() {
+		#[derive(Debug, Deserialize, PartialEq)]
+		struct Server {
+			port: u16,
+			debug: bool,
+			backup: Option<u16>,
+			note: Option<String>,
+		}
+
+		let raw = json!({
+			"port": "${PORT}",
+			"debug": "${DEBUG}",
+			"backup": "${BACKUP}",
+			"note": "${BACKUP}"
+		});
+		let env = |s: &str| {
+			let resolved = match s {
+				"${PORT}" => Resolved::changed("8080"),
+				"${DEBUG}" => Resolved::changed("on"),
+				"${BACKUP}" => Resolved::changed(""),
+				_ => Resolved::unchanged(),
+			};
+			async move { Ok::<_, Infallible>(resolved) }
+		};
+
+		let server: Server = resolve_into(
+			raw.clone(),
+			&env,
+			&Config::default().coercion(Coercion::Lenient),
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			server,
+			Server {
+				port: 8080,
+				debug: true,
+				backup: None,
+				note: Some(String::new()),
+			}
+		);
+
+		// Strict mode does not treat empty strings as null
+		let result: Result<Server, _> =
+			resolve_into(raw, &env, &Config::default().coercion(Coercion::Strict)).await;
+		let Err(StructResolveError::Deserialize {
+			path,
+			original,
+			resolved,
+			..
+		}) = result
+		else {
+			panic!("expected deserialize error");
+		};
+		assert_eq!(path.to_string(), "backup");
+		assert_eq!(original.as_deref(), Some("${BACKUP}"));
+		assert_eq!(resolved.as_deref(), Some(""));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_into_secret_redacted() {
+		#[derive(Debug, Deserialize)]
+		struct Db {
+			#[allow(dead_code)]
+			port: u16,
+		}
+
+		let result: Result<Db, _> = resolve_into(
+			json!({ "port": "${PORT}" }),
+			&|_: &str| async { Ok::<_, Infallible>(Resolved::secret("hunter2")) },
+			&Config::default().coercion(Coercion::Strict),
+		)
+		.await;
+
+		let Err(err @ StructResolveError::Deserialize { .. }) = result else {
+			panic!("expected deserialize error");
+		};
+		let StructResolveError::Deserialize { resolved, .. } = &err else {
+			unreachable!();
+		};
+		assert_eq!(resolved.as_deref(), Some(REDACTED));
+		assert!(
+			err
+				.to_string()
+				.contains("(resolved \"${PORT}\" to \"[REDACTED]\")")
+		);
+		assert!(!err.to_string().contains("hunter2"));
+		#[cfg(feature = "std")]
+		assert!(std::error::Error::source(&err).is_none());
+	}
+
+	#[cfg(feature = "secrecy")]
+	#[tokio::test]
+	async fn test_resolve_into_secret_string() {
+		use secrecy::ExposeSecret;
+
+		#[derive(Debug, Deserialize)]
+		struct Db {
+			password: secrecy::SecretString,
+		}
+
+		let db: Db = resolve_into(
+			json!({ "password": "${PASS}" }),
+			&|_: &str| async { Ok::<_, Infallible>(Resolved::secret("hunter2")) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(db.password.expose_secret(), "hunter2");
+		assert!(!alloc::format!("{db:?}").contains("hunter2"));
+	}
+
+	#[tokio::test]
+	async fn test_provenance_serialize() {
+		let (_, provenance) = resolve_with_provenance(
+			json!({ "db": { "host": "${HOST}", "pass": "${PASS}" }, "ports": ["80"] }),
+			&|s: &str| {
+				let resolved = match s {
+					"${HOST}" => Resolved::changed("db.local").with_source("env:DB_HOST"),
+					"${PASS}" => Resolved::secret("hunter2").with_source("env:DB_PASS"),
+					_ => Resolved::unchanged().into(),
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&provenance).unwrap(),
+			json!({
+				"db.host": { "source": "env:DB_HOST", "original": "${HOST}", "value": "db.local" },
+				"db.pass": { "source": "env:DB_PASS", "original": "${PASS}", "value": null },
+				"ports[0]": { "source": null, "original": "80", "value": "80" }
+			})
+		);
+	}
 }
@@ -0,0 +1,570 @@
+/* src/limit.rs */
+
+//! Concurrency and rate limiting for resolvers.
+//!
+//! This module requires the `std` feature.
+//!
+//! [`LimitedResolver`] caps how many calls to a resolver are in flight at
+//! once and how fast new calls start, using a token bucket. The limits apply
+//! to every resolve operation sharing the same `LimitedResolver`, for example
+//! through a reference or an `Arc`, so a batch of concurrent
+//! [`json::resolve`](crate::json::resolve) calls cannot exceed them together.
+//!
+//! Waiting for a rate-limit token sleeps through the [`Timer`] given to
+//! [`LimitedResolver::rate_limit`]; [`TokioTimer`](crate::TokioTimer) serves
+//! tokio applications. Time spent waiting is reported by
+//! [`LimitedResolver::stats`].
+//!
+//! # Example
+//!
+//! ```rust
+//! # #[cfg(feature = "tokio")] {
+//! use serde_resolve::limit::LimitedResolver;
+//! use serde_resolve::{Resolved, TokioTimer};
+//! use std::time::Duration;
+//!
+//! let backend = |s: &str| {
+//!     let upper = s.to_uppercase();
+//!     async move { Ok::<_, std::convert::Infallible>(Resolved::changed(upper)) }
+//! };
+//! let resolver = LimitedResolver::new(backend)
+//!     .max_in_flight(8)
+//!     .rate_limit(100, Duration::from_secs(1), TokioTimer);
+//!
+//! let stats = resolver.stats();
+//! assert_eq!(stats.calls, 0);
+//! # }
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+use crate::{Resolved, Resolver, Sourced, Timer};
+
+/// Resolver limiting the calls made to another resolver.
+///
+/// See the [module documentation](self) for details.
+pub struct LimitedResolver<R> {
+	inner: R,
+	permits: Option<Semaphore>,
+	rate: Option<RateLimit>,
+	stats: Mutex<LimitStats>,
+}
+
+/// Token bucket and the timer to wait for its tokens with.
+#[derive(Debug)]
+struct RateLimit {
+	bucket: Mutex<Bucket>,
+	timer: Arc<dyn Timer>,
+}
+
+/// Wait-time metrics of a [`LimitedResolver`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LimitStats {
+	/// Calls started.
+	pub calls: u64,
+	/// Calls that had to wait for a permit or a rate-limit token.
+	pub waited: u64,
+	/// Total time calls spent waiting.
+	pub total_wait: Duration,
+	/// Longest time a single call waited.
+	pub max_wait: Duration,
+	/// Calls currently in flight; only tracked with
+	/// [`max_in_flight`](LimitedResolver::max_in_flight).
+	pub in_flight: usize,
+}
+
+impl<R> LimitedResolver<R> {
+	/// Wrap `inner` without any limits.
+	#[must_use]
+	pub fn new(inner: R) -> Self {
+		Self {
+			inner,
+			permits: None,
+			rate: None,
+			stats: Mutex::new(LimitStats::default()),
+		}
+	}
+
+	/// Allow at most `max` calls in flight at once.
+	///
+	/// Values below 1 are treated as 1.
+	#[must_use]
+	pub fn max_in_flight(mut self, max: usize) -> Self {
+		self.permits = Some(Semaphore::new(max.max(1)));
+		self
+	}
+
+	/// Allow at most `calls` calls to start per `per`, with bursts of up to
+	/// `calls`, sleeping on `timer` while waiting for a token.
+	///
+	/// A `calls` of 0 is treated as 1.
+	#[must_use]
+	pub fn rate_limit(mut self, calls: u32, per: Duration, timer: impl Timer + 'static) -> Self {
+		let capacity = f64::from(calls.max(1));
+		self.rate = Some(RateLimit {
+			bucket: Mutex::new(Bucket {
+				capacity,
+				tokens: capacity,
+				per_token: per.div_f64(capacity),
+				last: Instant::now(),
+			}),
+			timer: Arc::new(timer),
+		});
+		self
+	}
+
+	/// The wrapped resolver.
+	#[inline]
+	#[must_use]
+	pub const fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/// A snapshot of the wait-time metrics.
+	#[must_use]
+	pub fn stats(&self) -> LimitStats {
+		let mut stats = *self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+		stats.in_flight = self.permits.as_ref().map_or(0, Semaphore::in_use);
+		stats
+	}
+
+	/// Wait for a rate-limit token, if rate limiting is enabled.
+	async fn take_token(&self) {
+		let Some(rate) = &self.rate else {
+			return;
+		};
+		loop {
+			let wait = rate
+				.bucket
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.take(Instant::now());
+			match wait {
+				Some(wait) => rate.timer.sleep(wait).await,
+				None => return,
+			}
+		}
+	}
+
+	/// Wait until a call may start, holding its permit if calls are capped.
+	async fn admit(&self) -> Option<Permit<'_>> {
+		let start = Instant::now();
+		let permit = match &self.permits {
+			Some(permits) => Some(permits.acquire().await),
+			None => None,
+		};
+		self.take_token().await;
+
+		let wait = start.elapsed();
+		#[cfg(feature = "tracing")]
+		if !wait.is_zero() {
+			tracing::trace!(?wait, "waited for resolver limit");
+		}
+		self.record(wait);
+		permit
+	}
+
+	fn record(&self, wait: Duration) {
+		let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+		stats.calls += 1;
+		if !wait.is_zero() {
+			stats.waited += 1;
+			stats.total_wait += wait;
+			stats.max_wait = stats.max_wait.max(wait);
+		}
+	}
+}
+
+impl<R: core::fmt::Debug> core::fmt::Debug for LimitedResolver<R> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("LimitedResolver")
+			.field("inner", &self.inner)
+			.field("max_in_flight", &self.permits.as_ref().map(|p| p.max))
+			.field("rate_limit", &self.rate.as_ref().map(|r| &r.timer))
+			.field("stats", &self.stats())
+			.finish_non_exhaustive()
+	}
+}
+
+impl<R: Resolver> Resolver for LimitedResolver<R> {
+	type Error = R::Error;
+
+	async fn resolve(&self, input: &str) -> Result<Resolved, R::Error> {
+		let _permit = self.admit().await;
+		self.inner.resolve(input).await
+	}
+
+	async fn resolve_sourced(&self, input: &str) -> Result<Sourced, R::Error> {
+		let _permit = self.admit().await;
+		self.inner.resolve_sourced(input).await
+	}
+}
+
+/// Token bucket refilling one token every `per_token`.
+#[derive(Debug)]
+struct Bucket {
+	capacity: f64,
+	tokens: f64,
+	per_token: Duration,
+	last: Instant,
+}
+
+impl Bucket {
+	/// Take a token, or return how long until one is available.
+	fn take(&mut self, now: Instant) -> Option<Duration> {
+		let elapsed = now.saturating_duration_since(self.last);
+		self.last = now;
+		self.tokens = (self.tokens + elapsed.div_duration_f64(self.per_token)).min(self.capacity);
+		if self.tokens >= 1.0 {
+			self.tokens -= 1.0;
+			None
+		} else {
+			Some(self.per_token.mul_f64(1.0 - self.tokens))
+		}
+	}
+}
+
+/// Counting semaphore handing each released permit to the longest waiting
+/// acquirer.
+#[derive(Debug)]
+struct Semaphore {
+	max: usize,
+	state: Mutex<SemaphoreState>,
+}
+
+#[derive(Debug)]
+struct SemaphoreState {
+	available: usize,
+	next: u64,
+	/// Wakers of queued acquirers, oldest first. Only the oldest may take a
+	/// free permit.
+	waiters: BTreeMap<u64, Waker>,
+}
+
+impl Semaphore {
+	fn new(max: usize) -> Self {
+		Self {
+			max,
+			state: Mutex::new(SemaphoreState {
+				available: max,
+				next: 0,
+				waiters: BTreeMap::new(),
+			}),
+		}
+	}
+
+	fn lock(&self) -> std::sync::MutexGuard<'_, SemaphoreState> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	fn in_use(&self) -> usize {
+		self.max - self.lock().available
+	}
+
+	fn acquire(&self) -> Acquire<'_> {
+		Acquire {
+			semaphore: self,
+			slot: None,
+		}
+	}
+}
+
+impl SemaphoreState {
+	/// Wake the oldest waiter if a permit is free.
+	fn wake_next(&self) -> Option<Waker> {
+		if self.available == 0 {
+			return None;
+		}
+		self
+			.waiters
+			.first_key_value()
+			.map(|(_, waker)| waker.clone())
+	}
+}
+
+/// Future returned by [`Semaphore::acquire`].
+struct Acquire<'a> {
+	semaphore: &'a Semaphore,
+	slot: Option<u64>,
+}
+
+impl<'a> Future for Acquire<'a> {
+	type Output = Permit<'a>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit<'a>> {
+		let semaphore = self.semaphore;
+		let mut state = semaphore.lock();
+		let first = state.waiters.first_key_value().map(|(slot, _)| *slot);
+		if state.available > 0 && (first.is_none() || first == self.slot) {
+			state.available -= 1;
+			if let Some(slot) = self.slot.take() {
+				state.waiters.remove(&slot);
+			}
+			// Permits left over go to the next in line
+			let waker = state.wake_next();
+			drop(state);
+			if let Some(waker) = waker {
+				waker.wake();
+			}
+			return Poll::Ready(Permit(semaphore));
+		}
+		let slot = self.slot.unwrap_or_else(|| {
+			let slot = state.next;
+			state.next += 1;
+			slot
+		});
+		state.waiters.insert(slot, cx.waker().clone());
+		drop(state);
+		self.slot = Some(slot);
+		Poll::Pending
+	}
+}
+
+impl Drop for Acquire<'_> {
+	fn drop(&mut self) {
+		let Some(slot) = self.slot else {
+			return;
+		};
+		let waker = {
+			let mut state = self.semaphore.lock();
+			// The first in line may have been woken for a permit it will not take
+			let first = state.waiters.first_key_value().map(|(first, _)| *first);
+			state.waiters.remove(&slot);
+			if first == Some(slot) {
+				state.wake_next()
+			} else {
+				None
+			}
+		};
+		if let Some(waker) = waker {
+			waker.wake();
+		}
+	}
+}
+
+/// Returns its permit to the semaphore when dropped.
+struct Permit<'a>(&'a Semaphore);
+
+impl Drop for Permit<'_> {
+	fn drop(&mut self) {
+		let waker = {
+			let mut state = self.0.lock();
+			state.available += 1;
+			state.wake_next()
+		};
+		if let Some(waker) = waker {
+			waker.wake();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::RecordingTimer;
+	use core::convert::Infallible;
+	use core::sync::atomic::{AtomicUsize, Ordering};
+
+	/// Tracks the highest number of concurrent calls.
+	#[derive(Debug, Default)]
+	struct Gauge {
+		current: AtomicUsize,
+		peak: AtomicUsize,
+	}
+
+	impl Resolver for Gauge {
+		type Error = Infallible;
+
+		async fn resolve(&self, input: &str) -> Result<Resolved, Infallible> {
+			let now = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+			self.peak.fetch_max(now, Ordering::SeqCst);
+			tokio::task::yield_now().await;
+			tokio::task::yield_now().await;
+			self.current.fetch_sub(1, Ordering::SeqCst);
+			Ok(Resolved::changed(input.to_uppercase()))
+		}
+	}
+
+	#[tokio::test]
+	async fn test_max_in_flight() {
+		let resolver = LimitedResolver::new(Gauge::default()).max_in_flight(2);
+		let results = tokio::join!(
+			resolver.resolve("a"),
+			resolver.resolve("b"),
+			resolver.resolve("c"),
+			resolver.resolve("d"),
+			resolver.resolve("e"),
+		);
+
+		assert_eq!(results.4.unwrap(), Resolved::changed("E"));
+		assert_eq!(resolver.get_ref().peak.load(Ordering::SeqCst), 2);
+		let stats = resolver.stats();
+		assert_eq!(stats.calls, 5);
+		assert_eq!(stats.in_flight, 0);
+	}
+
+	#[test]
+	fn test_bucket() {
+		let start = Instant::now();
+		let mut bucket = Bucket {
+			capacity: 2.0,
+			tokens: 2.0,
+			per_token: Duration::from_millis(500),
+			last: start,
+		};
+		assert_eq!(bucket.take(start), None);
+		assert_eq!(bucket.take(start), None);
+		assert_eq!(bucket.take(start), Some(Duration::from_millis(500)));
+		let later = start + Duration::from_millis(250);
+		assert_eq!(bucket.take(later), Some(Duration::from_millis(250)));
+		// Refilling stops at the capacity
+		let much_later = start + Duration::from_secs(60);
+		assert_eq!(bucket.take(much_later), None);
+		assert_eq!(bucket.take(much_later), None);
+		assert!(bucket.take(much_later).is_some());
+	}
+
+	#[tokio::test]
+	async fn test_rate_limit_waits() {
+		let clock = RecordingTimer::pending();
+		let resolver = LimitedResolver::new(Gauge::default()).rate_limit(
+			2,
+			Duration::from_secs(3600),
+			clock.clone(),
+		);
+
+		assert!(resolver.resolve("a").await.is_ok());
+		assert!(resolver.resolve("b").await.is_ok());
+		assert!(clock.sleeps().is_empty());
+
+		tokio::select! {
+			_ = resolver.resolve("c") => panic!("rate limit not enforced"),
+			() = tokio::task::yield_now() => {}
+		}
+		let wait = clock.sleeps()[0];
+		assert!(wait > Duration::from_secs(1700) && wait <= Duration::from_secs(1800));
+		assert_eq!(resolver.stats().calls, 2);
+	}
+
+	/// Waker recording whether it was woken.
+	#[derive(Debug, Default)]
+	struct Flag(core::sync::atomic::AtomicBool);
+
+	impl std::task::Wake for Flag {
+		fn wake(self: Arc<Self>) {
+			self.0.store(true, Ordering::SeqCst);
+		}
+	}
+
+	impl Flag {
+		fn woken(&self) -> bool {
+			self.0.swap(false, Ordering::SeqCst)
+		}
+	}
+
+	fn poll<F: Future + Unpin>(future: &mut F, flag: &Arc<Flag>) -> Poll<F::Output> {
+		let waker = Waker::from(Arc::clone(flag));
+		Pin::new(future).poll(&mut Context::from_waker(&waker))
+	}
+
+	#[test]
+	fn test_release_wakes_one() {
+		let semaphore = Semaphore::new(1);
+		let flags = [(); 3].map(|()| Arc::new(Flag::default()));
+		let Poll::Ready(permit) = poll(&mut semaphore.acquire(), &flags[0]) else {
+			panic!("permit not available");
+		};
+		let mut first = Box::pin(semaphore.acquire());
+		let mut second = Box::pin(semaphore.acquire());
+		assert!(poll(&mut first, &flags[1]).is_pending());
+		assert!(poll(&mut second, &flags[2]).is_pending());
+
+		drop(permit);
+		assert!(flags[1].woken());
+		assert!(!flags[2].woken());
+
+		// A woken waiter dropped before acquiring passes the permit on
+		drop(first);
+		assert!(flags[2].woken());
+		let permit = poll(&mut second, &flags[2]);
+		assert!(permit.is_ready());
+		assert!(semaphore.lock().waiters.is_empty());
+		assert_eq!(semaphore.in_use(), 1);
+	}
+
+	#[test]
+	fn test_released_permit_not_taken_by_newcomer() {
+		let semaphore = Semaphore::new(2);
+		let flags = [(); 4].map(|()| Arc::new(Flag::default()));
+		let Poll::Ready(a) = poll(&mut semaphore.acquire(), &flags[0]) else {
+			panic!("permit not available");
+		};
+		let Poll::Ready(b) = poll(&mut semaphore.acquire(), &flags[0]) else {
+			panic!("permit not available");
+		};
+		let mut first = Box::pin(semaphore.acquire());
+		let mut second = Box::pin(semaphore.acquire());
+		assert!(poll(&mut first, &flags[1]).is_pending());
+		assert!(poll(&mut second, &flags[2]).is_pending());
+
+		drop(a);
+		drop(b);
+		assert!(flags[1].woken());
+		// Both permits are free, but the waiters were first
+		let mut newcomer = Box::pin(semaphore.acquire());
+		assert!(poll(&mut newcomer, &flags[3]).is_pending());
+		assert!(poll(&mut second, &flags[2]).is_pending());
+
+		// Taking a permit passes the spare one to the next in line
+		let first_permit = poll(&mut first, &flags[1]);
+		assert!(first_permit.is_ready());
+		assert!(flags[2].woken());
+		let second_permit = poll(&mut second, &flags[2]);
+		assert!(second_permit.is_ready());
+		assert!(!flags[3].woken());
+		assert_eq!(semaphore.in_use(), 2);
+		assert!(poll(&mut newcomer, &flags[3]).is_pending());
+	}
+
+	#[test]
+	fn test_dropped_waiter_deregisters() {
+		let semaphore = Semaphore::new(1);
+		let flag = Arc::new(Flag::default());
+		let Poll::Ready(permit) = poll(&mut semaphore.acquire(), &flag) else {
+			panic!("permit not available");
+		};
+		let mut waiter = Box::pin(semaphore.acquire());
+		assert!(poll(&mut waiter, &flag).is_pending());
+		drop(waiter);
+		assert!(semaphore.lock().waiters.is_empty());
+		drop(permit);
+		assert!(!flag.woken());
+		assert_eq!(semaphore.in_use(), 0);
+	}
+
+	#[cfg(all(feature = "json", feature = "tokio"))]
+	#[tokio::test]
+	async fn test_shared_across_operations() {
+		let resolver = LimitedResolver::new(Gauge::default())
+			.max_in_flight(1)
+			.rate_limit(1000, Duration::from_secs(1), crate::TokioTimer);
+		let config = crate::Config::default();
+
+		let (a, b) = tokio::join!(
+			crate::json::resolve(serde_json::json!(["a", "b"]), &resolver, &config),
+			crate::json::resolve(serde_json::json!({ "c": "d" }), &resolver, &config),
+		);
+		assert_eq!(a.unwrap(), serde_json::json!(["A", "B"]));
+		assert_eq!(b.unwrap(), serde_json::json!({ "c": "D" }));
+		assert_eq!(resolver.get_ref().peak.load(Ordering::SeqCst), 1);
+		assert_eq!(resolver.stats().calls, 3);
+	}
+}
@@ -0,0 +1,430 @@
+/* src/msgpack.rs */
+
+//! MessagePack support via `rmpv`.
+//!
+//! This module requires the `std` feature.
+//!
+//! `Binary` and `Ext` values are left untouched by [`resolve`]. Use
+//! [`resolve_with_bytes`] to pass them to a [`BytesResolver`] as well.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::future::Future;
+use core::marker::PhantomData;
+use rmpv::Value;
+
+use crate::value::OtherHandler;
+use crate::{BatchResolver, Config, Error, Path, ResolvableValue, Resolver, ValueKind};
+
+/// Trait for async resolvers of MessagePack `Binary` and `Ext` payloads.
+///
+/// The `ext` argument is `None` for `Binary` values and `Some(type_id)` for
+/// `Ext` values; the extension type is preserved in the output.
+pub trait BytesResolver: Send + Sync {
+	/// Error type returned by this resolver.
+	type Error: Send;
+
+	/// Resolve a byte payload.
+	///
+	/// # Returns
+	///
+	/// - `Ok(Some(bytes))` - Use the transformed payload
+	/// - `Ok(None)` - Keep the original payload
+	/// - `Err(e)` - Abort the entire resolve operation
+	fn resolve_bytes(
+		&self,
+		ext: Option<i8>,
+		input: &[u8],
+	) -> impl Future<Output = Result<Option<Vec<u8>>, Self::Error>> + Send;
+}
+
+impl<F, Fut, E> BytesResolver for F
+where
+	F: Fn(Option<i8>, &[u8]) -> Fut + Send + Sync,
+	Fut: Future<Output = Result<Option<Vec<u8>>, E>> + Send,
+	E: Send,
+{
+	type Error = E;
+
+	#[inline]
+	fn resolve_bytes(
+		&self,
+		ext: Option<i8>,
+		input: &[u8],
+	) -> impl Future<Output = Result<Option<Vec<u8>>, Self::Error>> + Send {
+		self(ext, input)
+	}
+}
+
+/// Adapter passing `Binary` and `Ext` leaves to a [`BytesResolver`],
+/// converting its errors into `E`.
+struct BytesHandler<'a, B, E>(&'a B, PhantomData<fn() -> E>);
+
+impl<B, E> OtherHandler<Value> for BytesHandler<'_, B, E>
+where
+	B: BytesResolver,
+	B::Error: Into<E>,
+	E: Send,
+{
+	type Error = E;
+
+	async fn other(&self, value: Value, _path: &Path) -> Result<Value, E> {
+		match value {
+			Value::Binary(data) => match self
+				.0
+				.resolve_bytes(None, &data)
+				.await
+				.map_err(Into::into)?
+			{
+				Some(new_data) => Ok(Value::Binary(new_data)),
+				None => Ok(Value::Binary(data)),
+			},
+			Value::Ext(ty, data) => match self
+				.0
+				.resolve_bytes(Some(ty), &data)
+				.await
+				.map_err(Into::into)?
+			{
+				Some(new_data) => Ok(Value::Ext(ty, new_data)),
+				None => Ok(Value::Ext(ty, data)),
+			},
+			other => Ok(other),
+		}
+	}
+}
+
+/// Resolve all strings in a MessagePack [`Value`].
+///
+/// Strings that are not valid UTF-8 are left untouched, as are `Binary` and
+/// `Ext` values. When [`Config::resolve_keys`] is set, only string map keys are
+/// passed to the resolver.
+///
+/// See [`crate::json::resolve`] for detailed documentation.
+pub async fn resolve<R>(
+	value: Value,
+	resolver: &R,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	crate::resolve(value, resolver, config).await
+}
+
+/// Resolve all strings in a MessagePack [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+/// Resolve all strings and byte payloads in a MessagePack [`Value`].
+///
+/// Behaves like [`resolve`], additionally passing every `Binary` and `Ext`
+/// payload to `bytes`. Errors of `bytes` are converted into the error type of
+/// `resolver`.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Either resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_with_bytes<R, B>(
+	value: Value,
+	resolver: &R,
+	bytes: &B,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+	B: BytesResolver,
+	B::Error: Into<R::Error>,
+{
+	crate::value::resolve_with(
+		value,
+		resolver,
+		&BytesHandler(bytes, PhantomData),
+		config,
+		&mut crate::value::Trace::default(),
+	)
+	.await
+}
+
+/// Strings that are not valid UTF-8 are reported as [`ValueKind::Other`].
+impl ResolvableValue for Value {
+	type Key = Self;
+	type Tag = Infallible;
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::String(s) if s.is_str() => ValueKind::String(s.into_str().unwrap_or_default()),
+			Self::Array(arr) => ValueKind::Array(arr),
+			Self::Map(map) => ValueKind::Map(map),
+			other @ (Self::Nil
+			| Self::Boolean(_)
+			| Self::Integer(_)
+			| Self::F32(_)
+			| Self::F64(_)
+			| Self::String(_)
+			| Self::Binary(_)
+			| Self::Ext(..)) => ValueKind::Other(other),
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::String(s.into())
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Array(items)
+	}
+
+	#[inline]
+	fn from_map(entries: Vec<(Self, Self)>) -> Self {
+		Self::Map(entries)
+	}
+
+	fn from_tagged(tag: Infallible, _value: Self) -> Self {
+		match tag {}
+	}
+
+	// Only string keys are resolved
+	fn key_into_value(key: Self) -> Result<Self, Self> {
+		match key {
+			Self::String(ref k) if k.is_str() => Ok(key),
+			other => Err(other),
+		}
+	}
+
+	#[inline]
+	fn key_from_value(value: Self) -> Self {
+		value
+	}
+
+	fn key_to_string(key: &Self) -> String {
+		match key.as_str() {
+			Some(k) => String::from(k),
+			None => format!("{key}"),
+		}
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::Nil => "nil",
+			Self::Boolean(_) => "boolean",
+			Self::Integer(_) => "integer",
+			Self::F32(_) | Self::F64(_) => "float",
+			Self::String(_) => "string",
+			Self::Binary(_) => "binary",
+			Self::Array(_) => "array",
+			Self::Map(_) => "map",
+			Self::Ext(..) => "ext",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use alloc::vec;
+	use core::convert::Infallible;
+
+	#[tokio::test]
+	async fn test_resolve_string() {
+		let input = Value::from("hello");
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, Value::from("HELLO"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_map() {
+		let input = Value::Map(vec![
+			(Value::from("key"), Value::from("value")),
+			(
+				Value::from(1),
+				Value::Array(vec![Value::from("a"), Value::from(2)]),
+			),
+		]);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		let expected = Value::Map(vec![
+			(Value::from("key"), Value::from("VALUE")),
+			(
+				Value::from(1),
+				Value::Array(vec![Value::from("A"), Value::from(2)]),
+			),
+		]);
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_string_keys_only() {
+		let input = Value::Map(vec![
+			(Value::from("key"), Value::from("value")),
+			(Value::Binary(vec![1, 2]), Value::from("bin")),
+		]);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		let expected = Value::Map(vec![
+			(Value::from("KEY"), Value::from("VALUE")),
+			(Value::Binary(vec![1, 2]), Value::from("BIN")),
+		]);
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_binary_and_ext_untouched() {
+		let input = Value::Array(vec![
+			Value::Binary(vec![0xde, 0xad]),
+			Value::Ext(7, vec![0xbe, 0xef]),
+			Value::String(rmpv::Utf8String::from("ok")),
+		]);
+
+		let output = resolve(
+			input.clone(),
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, input);
+	}
+
+	#[tokio::test]
+	async fn test_invalid_utf8_untouched() {
+		let raw = [0x92, 0xa2, 0xff, 0xfe, 0xa2, b'o', b'k'];
+		let input = rmpv::decode::read_value(&mut &raw[..]).unwrap();
+
+		let output = resolve(
+			input.clone(),
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		let items = output.as_array().unwrap();
+		assert_eq!(items[0], input.as_array().unwrap()[0]);
+		assert_eq!(items[1], Value::from("OK"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_with_bytes() {
+		let input = Value::Array(vec![Value::Binary(vec![1, 2]), Value::Ext(3, vec![4])]);
+
+		let output = resolve_with_bytes(
+			input,
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&|ext: Option<i8>, data: &[u8]| {
+				let reversed: Vec<u8> = data.iter().rev().copied().collect();
+				async move { Ok::<_, Infallible>(ext.is_none().then_some(reversed)) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			Value::Array(vec![Value::Binary(vec![2, 1]), Value::Ext(3, vec![4])])
+		);
+	}
+
+	#[tokio::test]
+	async fn test_bytes_error_converted() {
+		let result = resolve_with_bytes(
+			Value::Binary(vec![1]),
+			&|_: &str| async { Ok::<_, String>(Resolved::Unchanged) },
+			&|_: Option<i8>, _: &[u8]| async { Err::<Option<Vec<u8>>, _>("bad payload") },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::Resolver(e)) if e == "bad payload"));
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let mut value = Value::from("deep");
+		for _ in 0..32 {
+			value = Value::Map(vec![(Value::from("nested"), value)]);
+		}
+
+		let result = resolve(
+			value,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s)) }
+			},
+			&Config::default().max_depth(32),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 32 })));
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let input = Value::from("hello");
+		let result = resolve(
+			input,
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::Resolver(MyError))));
+	}
+}
@@ -0,0 +1,302 @@
+/* src/properties.rs */
+
+//! Java `.properties` file support.
+//!
+//! Supports `key=value`, `key: value` and `key value` entries, `#` and `!`
+//! comments, backslash line continuations and the standard escapes including
+//! `\uXXXX`. Changed entries are written back on a single line.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::flat::{self, Document, Entry, FlatError, Line, ParseError};
+use crate::{Config, Resolver};
+
+/// Parse a properties file into a [`Document`].
+///
+/// # Errors
+///
+/// Returns an error if a `\u` escape is malformed.
+pub fn parse(input: &str) -> Result<Document, ParseError> {
+	let mut lines = Vec::new();
+	let mut physical = flat::physical_lines(input).enumerate();
+
+	while let Some((index, (content, newline))) = physical.next() {
+		let (indent, rest) = flat::split_indent(content);
+		let mut raw = alloc::format!("{content}{newline}");
+
+		if rest.is_empty() || rest.starts_with(['#', '!']) {
+			lines.push(Line::Other(raw));
+			continue;
+		}
+
+		// Join continuation lines into one logical line.
+		let mut logical = String::from(rest);
+		let mut line_end = newline;
+		while ends_with_continuation(&logical) {
+			logical.pop();
+			let Some((_, (next, next_newline))) = physical.next() else {
+				break;
+			};
+			logical.push_str(next.trim_start());
+			raw.push_str(next);
+			raw.push_str(next_newline);
+			line_end = next_newline;
+		}
+
+		let (key_raw, separator, value_raw) = split_entry(&logical);
+		let line = index + 1;
+		let key = unescape(key_raw).ok_or(ParseError {
+			line,
+			message: "malformed \\u escape",
+		})?;
+		let value = unescape(value_raw).ok_or(ParseError {
+			line,
+			message: "malformed \\u escape",
+		})?;
+
+		let mut entry = Entry::new(None, key, value, raw);
+		entry.prefix = String::from(indent);
+		entry.separator = String::from(separator);
+		entry.newline = String::from(line_end);
+		lines.push(Line::Entry(entry));
+	}
+
+	Ok(Document::new(lines, encode))
+}
+
+/// Parse, resolve and re-serialize a properties file.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The input cannot be parsed
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_str<R>(
+	input: &str,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, FlatError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let document = parse(input).map_err(FlatError::Parse)?;
+	let resolved = flat::resolve(document, resolver, config)
+		.await
+		.map_err(FlatError::Resolve)?;
+	Ok(alloc::string::ToString::to_string(&resolved))
+}
+
+fn encode(entry: &Entry, out: &mut String) {
+	out.push_str(&entry.prefix);
+	for (i, c) in entry.key().chars().enumerate() {
+		match c {
+			' ' | '=' | ':' => {
+				out.push('\\');
+				out.push(c);
+			}
+			'#' | '!' if i == 0 => {
+				out.push('\\');
+				out.push(c);
+			}
+			c => escape_char(c, out),
+		}
+	}
+	out.push_str(if entry.separator.is_empty() {
+		"="
+	} else {
+		&entry.separator
+	});
+	for (i, c) in entry.value().chars().enumerate() {
+		match c {
+			' ' if i == 0 => out.push_str("\\ "),
+			c => escape_char(c, out),
+		}
+	}
+	out.push_str(&entry.newline);
+}
+
+fn escape_char(c: char, out: &mut String) {
+	match c {
+		'\\' => out.push_str("\\\\"),
+		'\n' => out.push_str("\\n"),
+		'\r' => out.push_str("\\r"),
+		'\t' => out.push_str("\\t"),
+		'\x0c' => out.push_str("\\f"),
+		c => out.push(c),
+	}
+}
+
+/// Whether `line` ends with an odd number of backslashes.
+fn ends_with_continuation(line: &str) -> bool {
+	line.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
+}
+
+/// Split a logical line into raw key, separator and raw value.
+fn split_entry(line: &str) -> (&str, &str, &str) {
+	let mut escaped = false;
+	let key_end = line
+		.char_indices()
+		.find(|&(_, c)| {
+			if escaped {
+				escaped = false;
+				return false;
+			}
+			escaped = c == '\\';
+			matches!(c, '=' | ':' | ' ' | '\t' | '\x0c')
+		})
+		.map_or(line.len(), |(i, _)| i);
+
+	let after_key = &line[key_end..];
+	let (ws, rest) = flat::split_indent(after_key);
+	let sep_len = match rest.chars().next() {
+		Some('=' | ':') => ws.len() + 1 + flat::split_indent(&rest[1..]).0.len(),
+		_ => ws.len(),
+	};
+	(
+		&line[..key_end],
+		&after_key[..sep_len],
+		&after_key[sep_len..],
+	)
+}
+
+fn unescape(text: &str) -> Option<String> {
+	let mut out = String::with_capacity(text.len());
+	let mut chars = text.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('t') => out.push('\t'),
+			Some('n') => out.push('\n'),
+			Some('r') => out.push('\r'),
+			Some('f') => out.push('\x0c'),
+			Some('u') => {
+				let hex: String = chars.by_ref().take(4).collect();
+				let code = u32::from_str_radix(&hex, 16)
+					.ok()
+					.filter(|_| hex.len() == 4)?;
+				out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+			}
+			Some(c) => out.push(c),
+			None => {}
+		}
+	}
+	Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, Resolved};
+	use alloc::borrow::ToOwned;
+	use alloc::string::ToString;
+	use core::convert::Infallible;
+
+	const INPUT: &str = "# Application\n\
+		! legacy comment\n\
+		app.name = ${APP}\n\
+		db.url:jdbc:${DB}\n\
+		greeting Hello \\u0057orld\n\
+		long.list = one, \\\n    two, \\\n    ${THREE}\n";
+
+	fn expand(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+		let resolved = s
+			.replace("${APP}", "billing")
+			.replace("${DB}", "postgres://db")
+			.replace("${THREE}", "three");
+		let changed = resolved != s;
+		async move {
+			Ok(if changed {
+				Resolved::changed(resolved)
+			} else {
+				Resolved::unchanged()
+			})
+		}
+	}
+
+	#[test]
+	fn test_parse() {
+		let doc = parse(INPUT).unwrap();
+		assert_eq!(doc.get("app.name"), Some("${APP}"));
+		assert_eq!(doc.get("db.url"), Some("jdbc:${DB}"));
+		assert_eq!(doc.get("greeting"), Some("Hello World"));
+		assert_eq!(doc.get("long.list"), Some("one, two, ${THREE}"));
+		assert_eq!(doc.to_string(), INPUT);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_str() {
+		let output = resolve_str(INPUT, &expand, &Config::default())
+			.await
+			.unwrap();
+
+		let expected = "# Application\n\
+			! legacy comment\n\
+			app.name = billing\n\
+			db.url:jdbc:postgres://db\n\
+			greeting Hello \\u0057orld\n\
+			long.list = one, two, three\n";
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_escaping_round_trip() {
+		let output = resolve_str(
+			"key=value\n",
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::changed(" padded\tline\nnext \\ end")) },
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "key=\\ padded\\tline\\nnext \\\\ end\n");
+		assert_eq!(
+			parse(&output).unwrap().get("key"),
+			Some(" padded\tline\nnext \\ end")
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_keys() {
+		let output = resolve_str(
+			"my\\ key = v\n",
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(alloc::format!("{s}:x"))) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "my\\ key\\:x = v:x\n");
+		assert_eq!(parse(&output).unwrap().get("my key:x"), Some("v:x"));
+	}
+
+	#[test]
+	fn test_parse_error() {
+		assert_eq!(parse("a = ok\nb = \\u12\n").unwrap_err().line, 2);
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let result = resolve_str(
+			"a=1\n",
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(
+			result,
+			Err(FlatError::Resolve(Error::Resolver(MyError)))
+		));
+	}
+}
@@ -0,0 +1,373 @@
+/* src/retry.rs */
+
+//! Retrying wrapper for flaky resolvers.
+//!
+//! This module requires the `std` feature.
+//!
+//! [`RetryingResolver`] re-runs a failed resolution with exponential
+//! backoff and jitter, as long as the error is classified as retryable and
+//! attempts remain. Backoff sleeps go through the [`Timer`] given to
+//! [`RetryingResolver::new`], so any runtime works and tests can use a fake
+//! clock; [`TokioTimer`](crate::TokioTimer) serves tokio applications.
+//!
+//! # Example
+//!
+//! ```rust
+//! # #[cfg(feature = "tokio")] {
+//! use serde_resolve::retry::RetryingResolver;
+//! use serde_resolve::{Resolved, TokioTimer};
+//! use std::time::Duration;
+//!
+//! let backend = |_: &str| async { Err::<Resolved, _>(std::io::Error::other("busy")) };
+//! let resolver = RetryingResolver::new(backend, TokioTimer)
+//!     .max_attempts(5)
+//!     .backoff(Duration::from_millis(50), Duration::from_secs(2))
+//!     .retry_if(|e: &std::io::Error| e.kind() != std::io::ErrorKind::PermissionDenied);
+//! # }
+//! ```
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::hash::BuildHasher;
+use core::time::Duration;
+
+use crate::{Resolved, Resolver, Sourced, Timer};
+
+/// Resolver retrying another resolver's failures.
+///
+/// See the [module documentation](self) for details.
+pub struct RetryingResolver<R: Resolver, P = fn(&<R as Resolver>::Error) -> bool> {
+	inner: R,
+	retryable: P,
+	max_attempts: u32,
+	initial_backoff: Duration,
+	max_backoff: Duration,
+	multiplier: f64,
+	jitter: f64,
+	timer: Arc<dyn Timer>,
+}
+
+impl<R: Resolver> RetryingResolver<R> {
+	/// Wrap `inner`, retrying every error after sleeping on `timer`.
+	///
+	/// Defaults: 3 attempts, backoff from 100 ms doubling up to 10 s, and up
+	/// to 50% jitter.
+	#[must_use]
+	pub fn new(inner: R, timer: impl Timer + 'static) -> Self {
+		Self {
+			inner,
+			retryable: |_| true,
+			max_attempts: 3,
+			initial_backoff: Duration::from_millis(100),
+			max_backoff: Duration::from_secs(10),
+			multiplier: 2.0,
+			jitter: 0.5,
+			timer: Arc::new(timer),
+		}
+	}
+}
+
+impl<R: Resolver, P> RetryingResolver<R, P> {
+	/// Retry only errors for which `retryable` returns `true`.
+	#[must_use]
+	pub fn retry_if<Q>(self, retryable: Q) -> RetryingResolver<R, Q>
+	where
+		Q: Fn(&R::Error) -> bool + Send + Sync,
+	{
+		RetryingResolver {
+			inner: self.inner,
+			retryable,
+			max_attempts: self.max_attempts,
+			initial_backoff: self.initial_backoff,
+			max_backoff: self.max_backoff,
+			multiplier: self.multiplier,
+			jitter: self.jitter,
+			timer: self.timer,
+		}
+	}
+
+	/// Set the total number of attempts, including the first. Default: 3.
+	///
+	/// Values below 1 are treated as 1.
+	#[inline]
+	#[must_use]
+	pub const fn max_attempts(mut self, attempts: u32) -> Self {
+		self.max_attempts = attempts;
+		self
+	}
+
+	/// Set the delay before the first retry and the cap on later delays.
+	/// Default: 100 ms and 10 s.
+	#[inline]
+	#[must_use]
+	pub const fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+		self.initial_backoff = initial;
+		self.max_backoff = max;
+		self
+	}
+
+	/// Set the factor each delay grows by. Default: 2.
+	///
+	/// Values below 1 are treated as 1, and non-finite values as the default.
+	#[inline]
+	#[must_use]
+	pub const fn multiplier(mut self, multiplier: f64) -> Self {
+		self.multiplier = if multiplier.is_finite() {
+			multiplier.max(1.0)
+		} else {
+			2.0
+		};
+		self
+	}
+
+	/// Set the fraction of each delay that is randomized, from 0 (none) to
+	/// 1 (anywhere between zero and the full delay). Default: 0.5.
+	#[inline]
+	#[must_use]
+	pub const fn jitter(mut self, jitter: f64) -> Self {
+		self.jitter = jitter.clamp(0.0, 1.0);
+		self
+	}
+
+	/// The wrapped resolver.
+	#[inline]
+	#[must_use]
+	pub const fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/// The delay after the failed attempt number `attempt`, starting at 1.
+	fn delay(&self, attempt: u32) -> Duration {
+		let exponent = i32::try_from(attempt - 1).unwrap_or(i32::MAX);
+		// Clamp in f64 so large exponents cannot overflow `Duration`
+		let secs = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+			.min(self.max_backoff.as_secs_f64());
+		let base = Duration::try_from_secs_f64(secs).unwrap_or(self.max_backoff);
+		if self.jitter == 0.0 {
+			return base;
+		}
+		// 1 - jitter * fraction
+		base.mul_f64(self.jitter.mul_add(-random_fraction(), 1.0))
+	}
+
+	/// Run `attempt` until it succeeds, fails for good, or attempts run out.
+	async fn retry<T, Fut>(&self, attempt: impl Fn() -> Fut) -> Result<T, R::Error>
+	where
+		Fut: Future<Output = Result<T, R::Error>>,
+		P: Fn(&R::Error) -> bool,
+	{
+		let mut attempts = 1;
+		loop {
+			match attempt().await {
+				Err(e) if attempts < self.max_attempts && (self.retryable)(&e) => {
+					let delay = self.delay(attempts);
+					#[cfg(feature = "tracing")]
+					tracing::debug!(attempt = attempts, ?delay, "retrying failed resolution");
+
+					attempts += 1;
+					self.timer.sleep(delay).await;
+				}
+				result => return result,
+			}
+		}
+	}
+}
+
+impl<R, P> core::fmt::Debug for RetryingResolver<R, P>
+where
+	R: Resolver + core::fmt::Debug,
+{
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("RetryingResolver")
+			.field("inner", &self.inner)
+			.field("max_attempts", &self.max_attempts)
+			.field("initial_backoff", &self.initial_backoff)
+			.field("max_backoff", &self.max_backoff)
+			.field("multiplier", &self.multiplier)
+			.field("jitter", &self.jitter)
+			.field("timer", &self.timer)
+			.finish_non_exhaustive()
+	}
+}
+
+impl<R, P> Resolver for RetryingResolver<R, P>
+where
+	R: Resolver,
+	P: Fn(&R::Error) -> bool + Send + Sync,
+{
+	type Error = R::Error;
+
+	async fn resolve(&self, input: &str) -> Result<Resolved, R::Error> {
+		self.retry(|| self.inner.resolve(input)).await
+	}
+
+	async fn resolve_sourced(&self, input: &str) -> Result<Sourced, R::Error> {
+		self.retry(|| self.inner.resolve_sourced(input)).await
+	}
+}
+
+/// A uniformly distributed value in `[0, 1)`.
+fn random_fraction() -> f64 {
+	// `RandomState` is freshly keyed by the standard library on each call
+	let bits = std::hash::RandomState::new().hash_one(0_u8) >> 11;
+	#[allow(clippy::cast_precision_loss)]
+	let fraction = bits as f64 / (1_u64 << 53) as f64;
+	fraction
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::RecordingTimer;
+	use core::sync::atomic::{AtomicU32, Ordering};
+
+	/// Fails with `Err(attempt)` until attempt `succeed_at`.
+	#[derive(Debug)]
+	struct Flaky {
+		calls: AtomicU32,
+		succeed_at: u32,
+	}
+
+	impl Resolver for Flaky {
+		type Error = u32;
+
+		async fn resolve(&self, _input: &str) -> Result<Resolved, u32> {
+			let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+			if call >= self.succeed_at {
+				Ok(Resolved::changed("ok"))
+			} else {
+				Err(call)
+			}
+		}
+	}
+
+	fn flaky(succeed_at: u32) -> Flaky {
+		Flaky {
+			calls: AtomicU32::new(0),
+			succeed_at,
+		}
+	}
+
+	fn ms(n: u64) -> Duration {
+		Duration::from_millis(n)
+	}
+
+	#[tokio::test]
+	async fn test_retries_with_backoff() {
+		let clock = RecordingTimer::default();
+		let resolver = RetryingResolver::new(flaky(4), clock.clone())
+			.max_attempts(5)
+			.backoff(ms(100), ms(250))
+			.jitter(0.0);
+
+		let resolved = resolver.resolve("x").await.unwrap();
+		assert_eq!(resolved, Resolved::changed("ok"));
+		assert_eq!(resolver.get_ref().calls.load(Ordering::SeqCst), 4);
+		assert_eq!(clock.sleeps(), [ms(100), ms(200), ms(250)]);
+	}
+
+	#[tokio::test]
+	async fn test_gives_up() {
+		let clock = RecordingTimer::default();
+		let resolver = RetryingResolver::new(flaky(10), clock.clone())
+			.max_attempts(3)
+			.multiplier(3.0)
+			.jitter(0.0);
+
+		assert_eq!(resolver.resolve("x").await, Err(3));
+		assert_eq!(clock.sleeps(), [ms(100), ms(300)]);
+	}
+
+	#[tokio::test]
+	async fn test_many_attempts() {
+		let clock = RecordingTimer::default();
+		let resolver = RetryingResolver::new(flaky(u32::MAX), clock.clone())
+			.max_attempts(100)
+			.jitter(0.0);
+
+		assert_eq!(resolver.resolve("x").await, Err(100));
+		let sleeps = clock.sleeps();
+		assert_eq!(sleeps.len(), 99);
+		assert_eq!(sleeps[..3], [ms(100), ms(200), ms(400)]);
+		assert!(sleeps[7..].iter().all(|d| *d == Duration::from_secs(10)));
+	}
+
+	#[tokio::test]
+	async fn test_invalid_multiplier() {
+		for multiplier in [f64::NAN, f64::INFINITY, -3.0, 0.5] {
+			let clock = RecordingTimer::default();
+			let resolver = RetryingResolver::new(flaky(u32::MAX), clock.clone())
+				.max_attempts(40)
+				.multiplier(multiplier)
+				.jitter(0.0);
+
+			assert_eq!(resolver.resolve("x").await, Err(40));
+			let sleeps = clock.sleeps();
+			assert!(
+				sleeps
+					.iter()
+					.all(|d| (ms(100)..=Duration::from_secs(10)).contains(d))
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_predicate() {
+		let clock = RecordingTimer::default();
+		let resolver = RetryingResolver::new(flaky(10), clock.clone())
+			.max_attempts(10)
+			.retry_if(|attempt: &u32| *attempt < 2);
+
+		assert_eq!(resolver.resolve("x").await, Err(2));
+		assert_eq!(clock.sleeps().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_jitter_bounds() {
+		let clock = RecordingTimer::default();
+		let resolver = RetryingResolver::new(flaky(u32::MAX), clock.clone())
+			.max_attempts(50)
+			.backoff(ms(1000), ms(1000))
+			.jitter(0.5);
+
+		assert_eq!(resolver.resolve("x").await, Err(50));
+		let sleeps = clock.sleeps();
+		assert_eq!(sleeps.len(), 49);
+		assert!(sleeps.iter().all(|d| (ms(500)..=ms(1000)).contains(d)));
+		assert!(sleeps.iter().any(|d| *d != sleeps[0]));
+	}
+
+	#[tokio::test]
+	async fn test_keeps_source() {
+		let calls = AtomicU32::new(0);
+		let backend = |_: &str| {
+			let call = calls.fetch_add(1, Ordering::SeqCst);
+			async move {
+				if call == 0 {
+					Err(call)
+				} else {
+					Ok(Resolved::changed("ok").with_source("vault"))
+				}
+			}
+		};
+		let resolver = RetryingResolver::new(backend, RecordingTimer::default());
+
+		let sourced = resolver.resolve_sourced("x").await.unwrap();
+		assert_eq!(sourced, Resolved::changed("ok").with_source("vault"));
+		assert_eq!(calls.load(Ordering::SeqCst), 2);
+	}
+
+	#[cfg(feature = "json")]
+	#[tokio::test]
+	async fn test_in_tree() {
+		let resolver = RetryingResolver::new(flaky(2), RecordingTimer::default()).jitter(0.0);
+		let value = crate::json::resolve(
+			serde_json::json!({ "a": "x", "b": "y" }),
+			&resolver,
+			&crate::Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(value, serde_json::json!({ "a": "ok", "b": "ok" }));
+	}
+}
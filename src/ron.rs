@@ -0,0 +1,275 @@
+/* src/ron.rs */
+
+//! RON support via `ron` crate.
+//!
+//! This module is available with the `ron` feature and supports `no_std` environments.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use ron::Value;
+
+use crate::{BatchResolver, Config, Error, ResolvableValue, Resolver, ValueKind};
+
+/// Resolve all strings in a RON [`Value`].
+///
+/// Sequences, maps and `Some(..)` options are resolved recursively. Maps may
+/// use any value as key; when [`Config::resolve_keys`] is set, only string keys
+/// are passed to the resolver.
+///
+/// See [`crate::json::resolve`] for detailed documentation.
+pub async fn resolve<R>(
+	value: Value,
+	resolver: &R,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	crate::resolve(value, resolver, config).await
+}
+
+/// Resolve all strings in a RON [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+/// `Some(..)` is reported as [`ValueKind::Tagged`] with a `()` tag.
+impl ResolvableValue for Value {
+	type Key = Self;
+	type Tag = ();
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::String(s) => ValueKind::String(s),
+			Self::Seq(seq) => ValueKind::Array(seq),
+			Self::Map(map) => ValueKind::Map(map.into_iter().collect()),
+			Self::Option(Some(inner)) => ValueKind::Tagged((), *inner),
+			other @ (Self::Option(None)
+			| Self::Unit
+			| Self::Bool(_)
+			| Self::Char(_)
+			| Self::Number(_)
+			| Self::Bytes(_)) => ValueKind::Other(other),
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::String(s)
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Seq(items)
+	}
+
+	fn from_map(entries: Vec<(Self, Self)>) -> Self {
+		Self::Map(entries.into_iter().collect::<ron::Map>())
+	}
+
+	fn from_tagged((): (), value: Self) -> Self {
+		Self::Option(Some(Box::new(value)))
+	}
+
+	// Only string keys are resolved
+	fn key_into_value(key: Self) -> Result<Self, Self> {
+		match key {
+			Self::String(_) => Ok(key),
+			other => Err(other),
+		}
+	}
+
+	#[inline]
+	fn key_from_value(value: Self) -> Self {
+		value
+	}
+
+	fn key_to_string(key: &Self) -> String {
+		match key {
+			Self::String(s) => s.clone(),
+			other => alloc::format!("{other:?}"),
+		}
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::Bool(_) => "bool",
+			Self::Char(_) => "char",
+			Self::Map(_) => "map",
+			Self::Number(_) => "number",
+			Self::Option(_) => "option",
+			Self::String(_) => "string",
+			Self::Bytes(_) => "bytes",
+			Self::Seq(_) => "seq",
+			Self::Unit => "unit",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use alloc::borrow::ToOwned;
+	use alloc::vec;
+	use core::convert::Infallible;
+	use ron::Map;
+
+	#[tokio::test]
+	async fn test_resolve_string() {
+		let input = Value::String("hello".into());
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, Value::String("HELLO".into()));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_parsed() {
+		let input: Value = ron::from_str(r#"(name: "app", tags: ["a", "b"], port: 8080)"#).unwrap();
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		let expected: Value = ron::from_str(r#"(name: "APP", tags: ["A", "B"], port: 8080)"#).unwrap();
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_string_keys_only() {
+		let mut map = Map::new();
+		map.insert("key", "value");
+		map.insert(1, "one");
+		let input = Value::Map(map);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		let mut expected = Map::new();
+		expected.insert("KEY", "VALUE");
+		expected.insert(1, "ONE");
+		assert_eq!(output, Value::Map(expected));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_option() {
+		let input = Value::Seq(vec![
+			Value::Option(Some(Box::new(Value::String("some".into())))),
+			Value::Option(None),
+		]);
+
+		let output = resolve(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			Value::Seq(vec![
+				Value::Option(Some(Box::new(Value::String("SOME".into())))),
+				Value::Option(None),
+			])
+		);
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		let mut value = Value::String("deep".into());
+		for _ in 0..32 {
+			value = Value::Option(Some(Box::new(value)));
+		}
+
+		let result = resolve(
+			value,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s)) }
+			},
+			&Config::default().max_depth(32),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 32 })));
+	}
+
+	#[tokio::test]
+	async fn test_resolver_error() {
+		#[derive(Debug)]
+		struct MyError;
+
+		let input = Value::String("hello".into());
+		let result = resolve(
+			input,
+			&|_: &str| async move { Err::<Resolved, _>(MyError) },
+			&Config::default(),
+		)
+		.await;
+
+		assert!(matches!(result, Err(Error::Resolver(MyError))));
+	}
+
+	#[tokio::test]
+	async fn test_non_string_unchanged() {
+		let input = Value::Seq(vec![
+			Value::Unit,
+			Value::Char('x'),
+			Value::Bool(true),
+			Value::Bytes(vec![1, 2]),
+		]);
+		let output = resolve(
+			input.clone(),
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(output, input);
+	}
+}
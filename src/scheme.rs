@@ -0,0 +1,305 @@
+/* src/scheme.rs */
+
+//! Resolver dispatching on URI-style schemes such as `env:` or `vault:`.
+//!
+//! [`SchemeResolver`] maps scheme names to resolvers. A string is handled in
+//! one of two forms:
+//!
+//! - Whole-string: `vault:db/pass` is passed to the `vault` resolver as
+//!   `db/pass`, and its result is returned as is.
+//! - Embedded: every `${scheme:reference}` inside a larger string, such as
+//!   `postgres://${env:USER}:${vault:db/pass}@db`, is replaced by the result
+//!   of the scheme's resolver. The whole string becomes a
+//!   [`Resolved::Secret`] if any part is secret.
+//!
+//! Strings, and `${...}` parts, without a registered scheme are left
+//! unchanged. Resolvers receive the reference without the scheme, so a
+//! [`FileResolver`](crate::file::FileResolver) registered as `file` should use
+//! an empty prefix.
+//!
+//! Results without a source are annotated with the `scheme:reference` they
+//! came from; see [`Resolver::resolve_sourced`].
+//!
+//! # Example
+//!
+//! ```rust
+//! # #[cfg(feature = "json")] {
+//! use serde_resolve::scheme::SchemeResolver;
+//! use serde_resolve::{json, Config, Resolved};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let resolver = SchemeResolver::new()
+//!     .scheme("env", |name: &str| {
+//!         let value = std::env::var(name).ok();
+//!         async move { Ok::<_, std::env::VarError>(value.map_or(Resolved::Unchanged, Resolved::Changed)) }
+//!     })
+//!     .scheme("upper", |s: &str| {
+//!         let upper = s.to_uppercase();
+//!         async move { Ok::<_, std::convert::Infallible>(Resolved::changed(upper)) }
+//!     });
+//!
+//! let value = json::resolve(
+//!     serde_json::json!({ "home": "env:HOME", "greeting": "hello ${upper:world}" }),
+//!     &resolver,
+//!     &Config::default(),
+//! )
+//! .await?;
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{DynError, DynResolver, Resolved, Resolver, Sourced};
+
+/// Registry of resolvers keyed by scheme.
+///
+/// Resolvers are stored as [`DynResolver`]s, so their errors are reported as
+/// [`DynError`]. See the [module documentation](self) for details.
+pub struct SchemeResolver {
+	schemes: BTreeMap<String, Box<dyn DynResolver>>,
+}
+
+impl SchemeResolver {
+	/// Create an empty registry.
+	#[inline]
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			schemes: BTreeMap::new(),
+		}
+	}
+
+	/// Register `resolver` for strings starting with `name:`.
+	///
+	/// Replaces any resolver registered for `name` before.
+	#[must_use]
+	pub fn scheme<R>(mut self, name: impl Into<String>, resolver: R) -> Self
+	where
+		R: DynResolver + 'static,
+	{
+		self.schemes.insert(name.into(), Box::new(resolver));
+		self
+	}
+
+	/// Names of the registered schemes, in sorted order.
+	pub fn schemes(&self) -> impl Iterator<Item = &str> {
+		self.schemes.keys().map(String::as_str)
+	}
+
+	/// The resolver for `scheme:reference`, with the reference.
+	fn lookup<'s>(&self, input: &'s str) -> Option<(&dyn DynResolver, &'s str)> {
+		let (name, reference) = input.split_once(':')?;
+		Some((self.schemes.get(name)?.as_ref(), reference))
+	}
+
+	/// Resolve every `${scheme:reference}` embedded in `input`.
+	async fn resolve_embedded(&self, input: &str) -> Result<Sourced, DynError> {
+		let mut out = String::new();
+		let mut sources: Vec<String> = Vec::new();
+		let mut changed = false;
+		let mut secret = false;
+		let mut rest = input;
+
+		while let Some(start) = rest.find("${") {
+			let Some(len) = rest[start + 2..].find('}') else {
+				break;
+			};
+			let inner = &rest[start + 2..start + 2 + len];
+			out.push_str(&rest[..start]);
+			rest = &rest[start + 3 + len..];
+
+			let Sourced { resolved, source } = match self.lookup(inner) {
+				Some((scheme, reference)) => scheme.resolve_sourced_boxed(reference).await?,
+				None => Resolved::Unchanged.into(),
+			};
+			let source = source.unwrap_or_else(|| inner.into());
+			secret |= resolved.is_secret();
+			if let Some(value) = resolved.into_changed() {
+				out.push_str(&value);
+				sources.push(source);
+				changed = true;
+			} else {
+				out.push_str("${");
+				out.push_str(inner);
+				out.push('}');
+			}
+		}
+
+		if !changed {
+			return Ok(Resolved::Unchanged.into());
+		}
+		out.push_str(rest);
+		let resolved = if secret {
+			Resolved::Secret(out)
+		} else {
+			Resolved::Changed(out)
+		};
+		Ok(resolved.with_source(sources.join(", ")))
+	}
+}
+
+impl Default for SchemeResolver {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl core::fmt::Debug for SchemeResolver {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("SchemeResolver")
+			.field("schemes", &self.schemes.keys().collect::<Vec<_>>())
+			.finish()
+	}
+}
+
+impl Resolver for SchemeResolver {
+	type Error = DynError;
+
+	async fn resolve(&self, input: &str) -> Result<Resolved, DynError> {
+		Ok(self.resolve_sourced(input).await?.resolved)
+	}
+
+	async fn resolve_sourced(&self, input: &str) -> Result<Sourced, DynError> {
+		if let Some((scheme, reference)) = self.lookup(input) {
+			#[cfg(feature = "tracing")]
+			tracing::trace!(input, "dispatching whole string by scheme");
+
+			let sourced = scheme.resolve_sourced_boxed(reference).await?;
+			return Ok(match sourced {
+				Sourced {
+					resolved,
+					source: None,
+				} if resolved.is_changed() => resolved.with_source(input),
+				sourced => sourced,
+			});
+		}
+		if input.contains("${") {
+			return self.resolve_embedded(input).await;
+		}
+		Ok(Resolved::Unchanged.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::upper;
+	use alloc::string::ToString;
+
+	fn vault(s: &str) -> impl Future<Output = Result<Sourced, String>> + use<> {
+		let result = match s {
+			"db/pass" => Ok(Resolved::secret("hunter2").with_source("vault:secret/db")),
+			"missing" => Ok(Resolved::Unchanged.into()),
+			other => Err(alloc::format!("no such secret: {other}")),
+		};
+		async move { result }
+	}
+
+	fn registry() -> SchemeResolver {
+		SchemeResolver::new()
+			.scheme("upper", upper)
+			.scheme("vault", vault)
+	}
+
+	#[tokio::test]
+	async fn test_whole_string() {
+		let resolver = registry();
+
+		let sourced = resolver.resolve_sourced("upper:abc").await.unwrap();
+		assert_eq!(sourced.source.as_deref(), Some("upper:abc"));
+		assert_eq!(sourced.resolved.into_changed().as_deref(), Some("ABC"));
+
+		let sourced = resolver.resolve_sourced("vault:db/pass").await.unwrap();
+		assert!(sourced.resolved.is_secret());
+		assert_eq!(sourced.source.as_deref(), Some("vault:secret/db"));
+
+		assert_eq!(
+			resolver.resolve("vault:missing").await.unwrap(),
+			Resolved::Unchanged
+		);
+		assert_eq!(
+			resolver.resolve("aws-ssm:x").await.unwrap(),
+			Resolved::Unchanged
+		);
+		assert_eq!(
+			resolver.resolve("plain").await.unwrap(),
+			Resolved::Unchanged
+		);
+		assert_eq!(
+			resolver
+				.resolve("vault:other")
+				.await
+				.unwrap_err()
+				.to_string(),
+			"no such secret: other"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_embedded() {
+		let resolver = registry();
+
+		let Sourced { resolved, source } = resolver
+			.resolve_sourced("user=${upper:admin} host=${env:HOST} x=${upper:y}")
+			.await
+			.unwrap();
+		assert!(!resolved.is_secret());
+		assert_eq!(source.as_deref(), Some("upper:admin, upper:y"));
+		assert_eq!(
+			resolved.into_changed().as_deref(),
+			Some("user=ADMIN host=${env:HOST} x=Y")
+		);
+
+		let resolved = resolver
+			.resolve("postgres://${upper:u}:${vault:db/pass}@db")
+			.await
+			.unwrap();
+		assert!(resolved.is_secret());
+		assert_eq!(
+			resolved.into_changed().as_deref(),
+			Some("postgres://U:hunter2@db")
+		);
+
+		for unchanged in ["${vault:missing}", "${unknown:x}", "${upper:unterminated"] {
+			assert_eq!(
+				resolver.resolve(unchanged).await.unwrap(),
+				Resolved::Unchanged
+			);
+		}
+	}
+
+	#[cfg(feature = "json")]
+	#[tokio::test]
+	async fn test_resolve_tree() {
+		let resolver = registry();
+		assert_eq!(resolver.schemes().collect::<Vec<_>>(), ["upper", "vault"]);
+		assert_eq!(
+			alloc::format!("{resolver:?}"),
+			r#"SchemeResolver { schemes: ["upper", "vault"] }"#
+		);
+
+		let value = crate::json::resolve(
+			serde_json::json!({ "a": "upper:x", "b": ["${upper:y}!", "z"] }),
+			&resolver,
+			&crate::Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(value, serde_json::json!({ "a": "X", "b": ["Y!", "z"] }));
+
+		let err = crate::json::resolve(
+			serde_json::json!({ "a": "${vault:nope}" }),
+			&resolver,
+			&crate::Config::default(),
+		)
+		.await
+		.unwrap_err();
+		assert_eq!(err.to_string(), "resolver error: no such secret: nope");
+	}
+}
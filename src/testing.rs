@@ -0,0 +1,106 @@
+/* src/testing.rs */
+
+//! Fixtures shared by the unit tests.
+
+use core::convert::Infallible;
+use core::future::Future;
+
+use crate::Resolved;
+
+/// Resolver uppercasing every string.
+pub(crate) fn upper(s: &str) -> impl Future<Output = Result<Resolved, Infallible>> + use<> {
+	let resolved = Resolved::changed(s.to_uppercase());
+	async move { Ok(resolved) }
+}
+
+/// Port kept as text, validated on deserialization.
+#[cfg(any(feature = "json", feature = "yaml"))]
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct Port(pub(crate) alloc::string::String);
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+impl<'de> serde::Deserialize<'de> for Port {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = alloc::string::String::deserialize(deserializer)?;
+		s.parse::<u16>().map_err(serde::de::Error::custom)?;
+		Ok(Self(s))
+	}
+}
+
+/// A fresh directory under the system temp dir, removed on drop.
+#[cfg(feature = "file")]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(feature = "file")]
+impl TempDir {
+	/// Create the directory; `name` must be unique across all tests.
+	pub(crate) fn new(name: &str) -> Self {
+		let dir = std::env::temp_dir().join(alloc::format!(
+			"serde_resolve_{}_{name}",
+			std::process::id()
+		));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		Self(dir)
+	}
+
+	pub(crate) fn path(&self) -> &std::path::Path {
+		&self.0
+	}
+}
+
+#[cfg(feature = "file")]
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.0);
+	}
+}
+
+/// Timer whose sleeps complete at once, so any string still pending times out.
+#[cfg(any(feature = "json5", feature = "stream"))]
+#[derive(Debug)]
+pub(crate) struct Expired;
+
+#[cfg(any(feature = "json5", feature = "stream"))]
+impl crate::Timer for Expired {
+	fn sleep(&self, _duration: core::time::Duration) -> crate::BoxFuture<'static, ()> {
+		alloc::boxed::Box::pin(core::future::ready(()))
+	}
+}
+
+/// Timer recording the requested delays. Its sleeps complete at once, or
+/// never when created with [`RecordingTimer::pending`].
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct RecordingTimer {
+	sleeps: alloc::sync::Arc<std::sync::Mutex<alloc::vec::Vec<core::time::Duration>>>,
+	pending: bool,
+}
+
+#[cfg(feature = "std")]
+impl RecordingTimer {
+	/// A timer whose sleeps never complete.
+	pub(crate) fn pending() -> Self {
+		Self {
+			pending: true,
+			..Self::default()
+		}
+	}
+
+	/// The delays requested so far.
+	pub(crate) fn sleeps(&self) -> alloc::vec::Vec<core::time::Duration> {
+		self.sleeps.lock().unwrap().clone()
+	}
+}
+
+#[cfg(feature = "std")]
+impl crate::Timer for RecordingTimer {
+	fn sleep(&self, duration: core::time::Duration) -> crate::BoxFuture<'static, ()> {
+		self.sleeps.lock().unwrap().push(duration);
+		if self.pending {
+			alloc::boxed::Box::pin(core::future::pending())
+		} else {
+			alloc::boxed::Box::pin(core::future::ready(()))
+		}
+	}
+}
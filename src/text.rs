@@ -0,0 +1,605 @@
+/* src/text.rs */
+
+//! Resolution of raw JSON, YAML and TOML text.
+//!
+//! This module is available when at least one of the `json`, `yaml` or `toml`
+//! features is enabled. Formats whose feature is disabled are reported as
+//! [`TextError::Unsupported`]. File helpers require the `std` feature.
+
+use alloc::string::String;
+
+use crate::{BatchResolver, Config, Error, Resolver};
+
+/// Text format of an input or output document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Format {
+	/// Detect the format from the file extension or the content.
+	///
+	/// As an output format, `Auto` means "same as the input".
+	#[default]
+	Auto,
+	/// JSON, written pretty-printed.
+	Json,
+	/// YAML.
+	Yaml,
+	/// TOML. The document root must be a table.
+	Toml,
+}
+
+impl Format {
+	/// Format for a file extension such as `json`, `yml` or `toml`.
+	///
+	/// Matching is case-insensitive; unknown extensions return `None`.
+	#[must_use]
+	pub fn from_extension(ext: &str) -> Option<Self> {
+		if ext.eq_ignore_ascii_case("json") {
+			Some(Self::Json)
+		} else if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") {
+			Some(Self::Yaml)
+		} else if ext.eq_ignore_ascii_case("toml") {
+			Some(Self::Toml)
+		} else {
+			None
+		}
+	}
+
+	/// Format for a path, based on its extension.
+	#[cfg(feature = "std")]
+	#[must_use]
+	pub fn from_path(path: &std::path::Path) -> Option<Self> {
+		path
+			.extension()
+			.and_then(|ext| ext.to_str())
+			.and_then(Self::from_extension)
+	}
+
+	/// Sniff the format of `input` among the enabled formats.
+	///
+	/// JSON is tried first for documents starting with `{` or `[`, then TOML,
+	/// then YAML. YAML is only accepted if the document is a mapping or a
+	/// sequence, so plain text is not mistaken for a YAML scalar. Empty or
+	/// whitespace-only input is not detected as any format.
+	#[must_use]
+	pub fn detect(input: &str) -> Option<Self> {
+		detect(input).map(|(format, _)| format)
+	}
+
+	const fn name(self) -> &'static str {
+		match self {
+			Self::Auto => "auto",
+			Self::Json => "json",
+			Self::Yaml => "yaml",
+			Self::Toml => "toml",
+		}
+	}
+}
+
+impl core::fmt::Display for Format {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Parse or serialize error from one of the underlying format crates.
+#[derive(Debug)]
+pub enum FormatError {
+	/// JSON error.
+	#[cfg(feature = "json")]
+	Json(serde_json::Error),
+	/// YAML error.
+	#[cfg(feature = "yaml")]
+	Yaml(serde_yaml::Error),
+	/// TOML parse error.
+	#[cfg(feature = "toml")]
+	TomlParse(toml::de::Error),
+	/// TOML serialize error.
+	#[cfg(feature = "toml")]
+	TomlSerialize(toml::ser::Error),
+}
+
+impl core::fmt::Display for FormatError {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			#[cfg(feature = "json")]
+			Self::Json(e) => write!(f, "json: {e}"),
+			#[cfg(feature = "yaml")]
+			Self::Yaml(e) => write!(f, "yaml: {e}"),
+			#[cfg(feature = "toml")]
+			Self::TomlParse(e) => write!(f, "toml: {e}"),
+			#[cfg(feature = "toml")]
+			Self::TomlSerialize(e) => write!(f, "toml: {e}"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FormatError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			#[cfg(feature = "json")]
+			Self::Json(e) => Some(e),
+			#[cfg(feature = "yaml")]
+			Self::Yaml(e) => Some(e),
+			#[cfg(feature = "toml")]
+			Self::TomlParse(e) => Some(e),
+			#[cfg(feature = "toml")]
+			Self::TomlSerialize(e) => Some(e),
+		}
+	}
+}
+
+/// Error type for resolving raw text.
+#[derive(Debug)]
+pub enum TextError<E> {
+	/// The input format could not be detected.
+	Detect,
+	/// The format's feature is not enabled.
+	Unsupported(Format),
+	/// The input could not be parsed.
+	Parse(FormatError),
+	/// Resolution error.
+	Resolve(Error<E>),
+	/// The output could not be serialized.
+	Serialize(FormatError),
+	/// The input file could not be read.
+	#[cfg(feature = "std")]
+	Io(std::io::Error),
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for TextError<E> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::Detect => f.write_str("could not detect input format"),
+			Self::Unsupported(format) => write!(f, "format `{format}` is not enabled"),
+			Self::Parse(e) => write!(f, "parse error: {e}"),
+			Self::Resolve(e) => write!(f, "resolution error: {e}"),
+			Self::Serialize(e) => write!(f, "serialization error: {e}"),
+			#[cfg(feature = "std")]
+			Self::Io(e) => write!(f, "io error: {e}"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for TextError<E> {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Detect | Self::Unsupported(_) => None,
+			Self::Parse(e) | Self::Serialize(e) => Some(e),
+			Self::Resolve(e) => Some(e),
+			Self::Io(e) => Some(e),
+		}
+	}
+}
+
+/// A parsed document of any enabled format.
+enum Parsed {
+	#[cfg(feature = "json")]
+	Json(serde_json::Value),
+	#[cfg(feature = "yaml")]
+	Yaml(serde_yaml::Value),
+	#[cfg(feature = "toml")]
+	Toml(toml::Value),
+}
+
+/// Parse, resolve and re-serialize `input` in the same format.
+///
+/// With [`Format::Auto`] the format is detected from the content, see
+/// [`Format::detect`].
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The format cannot be detected or is not enabled
+/// - The input cannot be parsed
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - The output cannot be serialized
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(all(feature = "json", feature = "yaml"))]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use serde_resolve::text::{self, Format};
+/// use serde_resolve::{Config, Resolved};
+///
+/// let output = text::resolve_str_as(
+///     r#"{ "name": "app" }"#,
+///     Format::Auto,
+///     Format::Yaml,
+///     &|s: &str| {
+///         let s = s.to_uppercase();
+///         async move { Ok::<_, std::convert::Infallible>(Resolved::changed(s)) }
+///     },
+///     &Config::default(),
+/// ).await?;
+///
+/// assert_eq!(output, "name: APP\n");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve_str<R>(
+	input: &str,
+	format: Format,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, TextError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	resolve_str_as(input, format, Format::Auto, resolver, config).await
+}
+
+/// Parse and resolve `input`, then serialize it as `output`.
+///
+/// [`Format::Auto`] as `output` keeps the input format.
+///
+/// # Errors
+///
+/// See [`resolve_str`].
+pub async fn resolve_str_as<R>(
+	input: &str,
+	format: Format,
+	output: Format,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, TextError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let (format, parsed) = read(input, format)?;
+
+	#[cfg(feature = "tracing")]
+	tracing::debug!(%format, %output, "resolving text");
+
+	let resolved = match parsed {
+		#[cfg(feature = "json")]
+		Parsed::Json(value) => Parsed::Json(
+			crate::resolve(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+		#[cfg(feature = "yaml")]
+		Parsed::Yaml(value) => Parsed::Yaml(
+			crate::resolve(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+		#[cfg(feature = "toml")]
+		Parsed::Toml(value) => Parsed::Toml(
+			crate::resolve(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+	};
+	write(resolved, format, output)
+}
+
+/// Parse `input` and resolve it with a [`BatchResolver`], then serialize it
+/// as `output`.
+///
+/// See [`crate::resolve_batched`] for details. [`Format::Auto`] as `output`
+/// keeps the input format.
+///
+/// # Errors
+///
+/// Same as [`resolve_str`], and the resolver may return the wrong number of
+/// results.
+pub async fn resolve_batched<B>(
+	input: &str,
+	format: Format,
+	output: Format,
+	resolver: &B,
+	config: &Config,
+) -> Result<String, TextError<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	let (format, parsed) = read(input, format)?;
+
+	#[cfg(feature = "tracing")]
+	tracing::debug!(%format, %output, "resolving text in batches");
+
+	let resolved = match parsed {
+		#[cfg(feature = "json")]
+		Parsed::Json(value) => Parsed::Json(
+			crate::resolve_batched(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+		#[cfg(feature = "yaml")]
+		Parsed::Yaml(value) => Parsed::Yaml(
+			crate::resolve_batched(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+		#[cfg(feature = "toml")]
+		Parsed::Toml(value) => Parsed::Toml(
+			crate::resolve_batched(value, resolver, config)
+				.await
+				.map_err(TextError::Resolve)?,
+		),
+	};
+	write(resolved, format, output)
+}
+
+/// Read, resolve and re-serialize a file in its own format.
+///
+/// The format is taken from the file extension, falling back to content
+/// detection for unknown extensions. The file is read with blocking I/O, so
+/// this works on any executor.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, or any error of
+/// [`resolve_str`].
+#[cfg(feature = "std")]
+pub async fn resolve_file<R>(
+	path: impl AsRef<std::path::Path>,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, TextError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	resolve_file_as(path, Format::Auto, resolver, config).await
+}
+
+/// Read and resolve a file, then serialize it as `output`.
+///
+/// # Errors
+///
+/// See [`resolve_file`].
+#[cfg(feature = "std")]
+pub async fn resolve_file_as<R>(
+	path: impl AsRef<std::path::Path>,
+	output: Format,
+	resolver: &R,
+	config: &Config,
+) -> Result<String, TextError<R::Error>>
+where
+	R: Resolver + ?Sized,
+{
+	let path = path.as_ref();
+	let input = std::fs::read_to_string(path).map_err(TextError::Io)?;
+	let format = Format::from_path(path).unwrap_or(Format::Auto);
+	resolve_str_as(&input, format, output, resolver, config).await
+}
+
+/// Parse `input`, detecting its format for [`Format::Auto`].
+fn read<E>(input: &str, format: Format) -> Result<(Format, Parsed), TextError<E>> {
+	match format {
+		Format::Auto => detect(input).ok_or(TextError::Detect),
+		format => Ok((format, parse(input, format)?)),
+	}
+}
+
+/// Serialize a resolved document as `output`, or in its own `format`.
+fn write<E>(resolved: Parsed, format: Format, output: Format) -> Result<String, TextError<E>> {
+	let output = if output == Format::Auto {
+		format
+	} else {
+		output
+	};
+	match resolved {
+		#[cfg(feature = "json")]
+		Parsed::Json(value) => serialize(&value, output),
+		#[cfg(feature = "yaml")]
+		Parsed::Yaml(value) => serialize(&value, output),
+		#[cfg(feature = "toml")]
+		Parsed::Toml(value) => serialize(&value, output),
+	}
+}
+
+fn detect(input: &str) -> Option<(Format, Parsed)> {
+	// An empty document would otherwise parse as an empty TOML table
+	if input.trim().is_empty() {
+		return None;
+	}
+
+	#[cfg(feature = "json")]
+	if input.trim_start().starts_with(['{', '['])
+		&& let Ok(value) = serde_json::from_str(input)
+	{
+		return Some((Format::Json, Parsed::Json(value)));
+	}
+
+	#[cfg(feature = "toml")]
+	if let Ok(table) = toml::from_str::<toml::Table>(input) {
+		return Some((Format::Toml, Parsed::Toml(toml::Value::Table(table))));
+	}
+
+	#[cfg(feature = "yaml")]
+	if let Ok(value @ (serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_))) =
+		serde_yaml::from_str(input)
+	{
+		return Some((Format::Yaml, Parsed::Yaml(value)));
+	}
+
+	None
+}
+
+fn parse<E>(input: &str, format: Format) -> Result<Parsed, TextError<E>> {
+	match format {
+		#[cfg(feature = "json")]
+		Format::Json => serde_json::from_str(input)
+			.map(Parsed::Json)
+			.map_err(|e| TextError::Parse(FormatError::Json(e))),
+		#[cfg(feature = "yaml")]
+		Format::Yaml => serde_yaml::from_str(input)
+			.map(Parsed::Yaml)
+			.map_err(|e| TextError::Parse(FormatError::Yaml(e))),
+		#[cfg(feature = "toml")]
+		Format::Toml => toml::from_str::<toml::Table>(input)
+			.map(|table| Parsed::Toml(toml::Value::Table(table)))
+			.map_err(|e| TextError::Parse(FormatError::TomlParse(e))),
+		other => Err(TextError::Unsupported(other)),
+	}
+}
+
+fn serialize<T, E>(value: &T, format: Format) -> Result<String, TextError<E>>
+where
+	T: serde::Serialize,
+{
+	match format {
+		#[cfg(feature = "json")]
+		Format::Json => {
+			serde_json::to_string_pretty(value).map_err(|e| TextError::Serialize(FormatError::Json(e)))
+		}
+		#[cfg(feature = "yaml")]
+		Format::Yaml => {
+			serde_yaml::to_string(value).map_err(|e| TextError::Serialize(FormatError::Yaml(e)))
+		}
+		#[cfg(feature = "toml")]
+		Format::Toml => {
+			toml::to_string_pretty(value).map_err(|e| TextError::Serialize(FormatError::TomlSerialize(e)))
+		}
+		other => Err(TextError::Unsupported(other)),
+	}
+}
+
+#[cfg(all(test, feature = "json", feature = "yaml", feature = "toml"))]
+mod tests {
+	use super::*;
+	use crate::testing::upper;
+
+	#[test]
+	fn test_detect() {
+		assert_eq!(Format::detect(r#"{"a": 1}"#), Some(Format::Json));
+		assert_eq!(Format::detect("[server]\nport = 80\n"), Some(Format::Toml));
+		assert_eq!(Format::detect("a: 1\nb: [x]\n"), Some(Format::Yaml));
+		assert_eq!(Format::detect("just some text"), None);
+		assert_eq!(Format::detect(""), None);
+		assert_eq!(Format::detect(" \n\t"), None);
+	}
+
+	#[test]
+	fn test_from_extension() {
+		assert_eq!(Format::from_extension("JSON"), Some(Format::Json));
+		assert_eq!(Format::from_extension("yml"), Some(Format::Yaml));
+		assert_eq!(Format::from_extension("toml"), Some(Format::Toml));
+		assert_eq!(Format::from_extension("ini"), None);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_same_format() {
+		let output = resolve_str(
+			"name = \"app\"\n\n[db]\nhost = \"local\"\n",
+			Format::Auto,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "name = \"APP\"\n\n[db]\nhost = \"LOCAL\"\n");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_convert() {
+		let output = resolve_str_as(
+			"items:\n  - a\n  - 1\n",
+			Format::Yaml,
+			Format::Json,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(output, "{\n  \"items\": [\n    \"A\",\n    1\n  ]\n}");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_batched() {
+		let upper = |batch: &[crate::Candidate]| {
+			let resolved = batch
+				.iter()
+				.map(|c| crate::Resolved::changed(c.input.to_uppercase()))
+				.collect();
+			async move { Ok::<_, core::convert::Infallible>(resolved) }
+		};
+		let output = resolve_batched(
+			"name: app
+hosts: [a, b]
+",
+			Format::Auto,
+			Format::Toml,
+			&upper,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			"name = \"APP\"\nhosts = [\n    \"A\",\n    \"B\",\n]\n"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_errors() {
+		let result = resolve_str("{ broken", Format::Json, &upper, &Config::default()).await;
+		assert!(matches!(
+			result,
+			Err(TextError::Parse(FormatError::Json(_)))
+		));
+
+		let result = resolve_str("plain text", Format::Auto, &upper, &Config::default()).await;
+		assert!(matches!(result, Err(TextError::Detect)));
+		let result = resolve_str("\n", Format::Auto, &upper, &Config::default()).await;
+		assert!(matches!(result, Err(TextError::Detect)));
+
+		let result = resolve_str_as(
+			"[1, 2]",
+			Format::Json,
+			Format::Toml,
+			&upper,
+			&Config::default(),
+		)
+		.await;
+		assert!(matches!(
+			result,
+			Err(TextError::Serialize(FormatError::TomlSerialize(_)))
+		));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_file() {
+		let path = std::env::temp_dir().join(alloc::format!(
+			"serde_resolve_text_{}.yml",
+			std::process::id()
+		));
+		std::fs::write(&path, "greeting: hello\n").unwrap();
+
+		let same = resolve_file(&path, &upper, &Config::default()).await;
+		let json = resolve_file_as(&path, Format::Json, &upper, &Config::default()).await;
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(same.unwrap(), "greeting: HELLO\n");
+		assert_eq!(json.unwrap(), "{\n  \"greeting\": \"HELLO\"\n}");
+
+		let missing = resolve_file(&path, &upper, &Config::default()).await;
+		assert!(matches!(missing, Err(TextError::Io(_))));
+	}
+
+	#[test]
+	fn test_resolve_file_without_runtime() {
+		use core::future::Future;
+		use core::task::{Context, Poll, Waker};
+
+		let path = std::env::temp_dir().join(alloc::format!(
+			"serde_resolve_text_bare_{}.yml",
+			std::process::id()
+		));
+		std::fs::write(&path, "greeting: hello\n").unwrap();
+
+		let config = Config::default();
+		let future = core::pin::pin!(resolve_file(&path, &upper, &config));
+		let result = future.poll(&mut Context::from_waker(Waker::noop()));
+		std::fs::remove_file(&path).unwrap();
+
+		assert!(matches!(result, Poll::Ready(Ok(s)) if s == "greeting: HELLO\n"));
+	}
+}
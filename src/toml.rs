@@ -4,11 +4,15 @@
 //!
 //! This module requires the `std` feature.
 
-#[cfg(feature = "tracing")]
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::convert::Infallible;
 use toml::Value;
 
-use crate::{Config, Error, Resolver};
+use crate::{
+	BatchResolver, Config, Error, ResolvableValue, Resolver, ScalarResolver, StructResolveError,
+	ValueKind,
+};
 
 /// Resolve all strings in a TOML [`Value`].
 ///
@@ -19,55 +23,161 @@ pub async fn resolve<R>(
 	config: &Config,
 ) -> Result<Value, Error<R::Error>>
 where
-	R: Resolver,
+	R: Resolver + ?Sized,
 {
-	#[cfg(feature = "tracing")]
-	let mut path = Vec::new();
-
-	resolve_recursive(
-		value,
-		resolver,
-		config,
-		0,
-		#[cfg(feature = "tracing")]
-		&mut path,
-	)
-	.await
+	crate::resolve(value, resolver, config).await
 }
 
-impl_resolve_recursive!(
-		Value,
-		Value::String,
-		Value::Array,
-		Value::Table,
-		toml::map::Map::with_capacity,
-		|k: &alloc::string::String| k.clone(),
-		resolver, config, depth, path, key,
-		{
-				match resolver.resolve(&key).await.map_err(crate::Error::resolver)? {
-						crate::Resolved::Changed(new_key) => new_key,
-						crate::Resolved::Unchanged => key,
-				}
-		},
-		{
-				// TOML-specific types
-				Value::Datetime(dt) => Ok(Value::Datetime(dt)),
-
-				// Pass through unchanged
-				other @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_)) => Ok(other),
+/// Resolve all strings in a TOML [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+/// Resolve all strings in a TOML [`Value`], and all other scalars with
+/// `scalars`.
+///
+/// Integers, floats, booleans and [`Value::Datetime`]s are passed to
+/// `scalars` with their path.
+/// See [`crate::resolve_with_scalars`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_with_scalars`].
+pub async fn resolve_with_scalars<R, S>(
+	value: Value,
+	resolver: &R,
+	scalars: &S,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+	S: ScalarResolver<Value> + ?Sized,
+	S::Error: Into<R::Error>,
+{
+	crate::resolve_with_scalars(value, resolver, scalars, config).await
+}
+
+/// Resolve strings in any serializable struct via a TOML round-trip.
+///
+/// Unlike [`crate::resolve_struct`], TOML datetimes are kept as datetimes.
+/// The value must serialize to a TOML table. See [`crate::resolve_struct`] for
+/// details.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Serialization fails
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - Deserialization fails
+pub async fn resolve_struct<T, R>(
+	value: T,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error, toml::ser::Error, toml::de::Error>>
+where
+	T: serde::Serialize + serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	resolve_into(value, resolver, config).await
+}
+
+/// Resolve strings in a serializable value and deserialize the result into
+/// another type via a TOML round-trip.
+///
+/// See [`crate::resolve_into`] for details.
+///
+/// # Errors
+///
+/// Same as [`resolve_struct`].
+pub async fn resolve_into<S, T, R>(
+	value: S,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error, toml::ser::Error, toml::de::Error>>
+where
+	S: serde::Serialize,
+	T: serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	let table = Value::try_from(value).map_err(StructResolveError::Serialize)?;
+	crate::resolve_struct_via(table, resolver, config).await
+}
+
+impl ResolvableValue for Value {
+	type Key = String;
+	type Tag = Infallible;
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::String(s) => ValueKind::String(s),
+			Self::Array(arr) => ValueKind::Array(arr),
+			Self::Table(table) => ValueKind::Map(table.into_iter().collect()),
+			// TOML-specific types pass through unchanged
+			other @ (Self::Integer(_) | Self::Float(_) | Self::Boolean(_) | Self::Datetime(_)) => {
+				ValueKind::Other(other)
+			}
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::String(s)
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Array(items)
+	}
+
+	fn from_map(entries: Vec<(String, Self)>) -> Self {
+		Self::Table(entries.into_iter().collect::<toml::map::Map<_, _>>())
+	}
+
+	fn from_tagged(tag: Infallible, _value: Self) -> Self {
+		match tag {}
+	}
+
+	#[inline]
+	fn key_into_value(key: String) -> Result<Self, String> {
+		Ok(Self::String(key))
+	}
+
+	fn key_from_value(value: Self) -> String {
+		match value {
+			Self::String(s) => s,
+			other => other.to_string(),
+		}
+	}
+
+	#[inline]
+	fn key_to_string(key: &String) -> String {
+		key.clone()
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::String(_) => "string",
+			Self::Integer(_) => "integer",
+			Self::Float(_) => "float",
+			Self::Boolean(_) => "boolean",
+			Self::Datetime(_) => "datetime",
+			Self::Array(_) => "array",
+			Self::Table(_) => "table",
 		}
-);
-
-#[cfg(feature = "tracing")]
-fn value_type_name(value: &Value) -> &'static str {
-	match value {
-		Value::String(_) => "string",
-		Value::Integer(_) => "integer",
-		Value::Float(_) => "float",
-		Value::Boolean(_) => "boolean",
-		Value::Datetime(_) => "datetime",
-		Value::Array(_) => "array",
-		Value::Table(_) => "table",
 	}
 }
 
@@ -75,6 +185,7 @@ fn value_type_name(value: &Value) -> &'static str {
 mod tests {
 	use super::*;
 	use crate::Resolved;
+	use alloc::borrow::ToOwned;
 	use alloc::string::ToString;
 	use core::convert::Infallible;
 	use toml::map::Map;
@@ -240,4 +351,87 @@ mod tests {
 		.unwrap();
 		assert_eq!(output, input);
 	}
-}
\ No newline at end of file
+
+	#[tokio::test]
+	async fn test_resolve_struct_keeps_datetime() {
+		#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+		struct Release {
+			name: String,
+			at: toml::value::Datetime,
+		}
+
+		let at: toml::value::Datetime = "1979-05-27T07:32:00Z".parse().unwrap();
+		let output = resolve_struct(
+			Release {
+				name: "v1".into(),
+				at,
+			},
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			Release {
+				name: "V1".into(),
+				at,
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_struct_errors() {
+		#[derive(Debug, serde::Serialize, serde::Deserialize)]
+		enum Level {
+			Low,
+		}
+
+		let result = resolve_struct(
+			Some(Level::Low),
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::changed("Bogus")) },
+			&Config::default(),
+		)
+		.await;
+		assert!(matches!(
+			result,
+			Err(StructResolveError::Deserialize { .. })
+		));
+
+		let result = resolve_struct(
+			None::<Level>,
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::unchanged()) },
+			&Config::default(),
+		)
+		.await;
+		assert!(matches!(result, Err(StructResolveError::Serialize(_))));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_with_scalars() {
+		let input: Value = toml::from_str("released = 2024-01-02T03:04:05Z\nport = 80\n").unwrap();
+		let scalars = |path: &crate::Path, value: &Value| {
+			let replaced = match value {
+				Value::Datetime(dt) => Some(Value::String(dt.to_string())),
+				Value::Integer(n) if path.to_string() == "port" => Some(Value::Integer(n + 8000)),
+				_ => None,
+			};
+			async move { Ok::<_, Infallible>(replaced) }
+		};
+
+		let output = resolve_with_scalars(
+			input,
+			&|_: &str| async { Ok::<_, Infallible>(Resolved::Unchanged) },
+			&scalars,
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+		assert_eq!(output["released"].as_str(), Some("2024-01-02T03:04:05Z"));
+		assert_eq!(output["port"].as_integer(), Some(8080));
+	}
+}
@@ -0,0 +1,1064 @@
+/* src/value.rs */
+
+//! Generic traversal over any value tree implementing [`ResolvableValue`].
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+use crate::deadline::Guard;
+use crate::{Config, Error, Path, PathSegment, REDACTED, Resolved, Resolver, Sourced};
+
+/// Classification of a value, as seen by the traversal.
+///
+/// Produced by [`ResolvableValue::into_kind`] and turned back into a value by
+/// the matching `from_*` constructor once its contents are resolved.
+pub enum ValueKind<V: ResolvableValue> {
+	/// A string handed to the resolver.
+	String(String),
+	/// An ordered sequence of values.
+	Array(Vec<V>),
+	/// Map entries in iteration order.
+	Map(Vec<(V::Key, V)>),
+	/// A single value wrapped in format-specific metadata, such as a YAML tag,
+	/// a CBOR tag or a RON `Some(..)`.
+	Tagged(V::Tag, V),
+	/// Any other value; passed through unchanged.
+	Other(V),
+}
+
+impl<V> core::fmt::Debug for ValueKind<V>
+where
+	V: ResolvableValue + core::fmt::Debug,
+	V::Key: core::fmt::Debug,
+	V::Tag: core::fmt::Debug,
+{
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match self {
+			Self::String(s) => f.debug_tuple("String").field(s).finish(),
+			Self::Array(items) => f.debug_tuple("Array").field(items).finish(),
+			Self::Map(entries) => f.debug_tuple("Map").field(entries).finish(),
+			Self::Tagged(tag, value) => f.debug_tuple("Tagged").field(tag).field(value).finish(),
+			Self::Other(value) => f.debug_tuple("Other").field(value).finish(),
+		}
+	}
+}
+
+/// A value tree that can be traversed by [`resolve`].
+///
+/// Implemented for the value types of every enabled format module. Implement it
+/// for your own tree type to resolve it without going through another format.
+///
+/// # Example
+///
+/// ```rust
+/// use serde_resolve::{Config, Resolved, ResolvableValue, ValueKind};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Node {
+///     Text(String),
+///     List(Vec<Node>),
+///     Number(i64),
+/// }
+///
+/// impl ResolvableValue for Node {
+///     type Key = String;
+///     type Tag = std::convert::Infallible;
+///
+///     fn into_kind(self) -> ValueKind<Self> {
+///         match self {
+///             Node::Text(s) => ValueKind::String(s),
+///             Node::List(items) => ValueKind::Array(items),
+///             other => ValueKind::Other(other),
+///         }
+///     }
+///
+///     fn from_string(s: String) -> Self { Node::Text(s) }
+///     fn from_array(items: Vec<Self>) -> Self { Node::List(items) }
+///     fn from_map(_: Vec<(String, Self)>) -> Self { unreachable!() }
+///     fn from_tagged(tag: Self::Tag, _: Self) -> Self { match tag {} }
+///     fn key_into_value(key: String) -> Result<Self, String> { Ok(Node::Text(key)) }
+///     fn key_from_value(value: Self) -> String { format!("{value:?}") }
+///     fn key_to_string(key: &String) -> String { key.clone() }
+/// }
+///
+/// # async fn example() {
+/// let tree = Node::List(vec![Node::Text("a".into()), Node::Number(1)]);
+/// let resolved = serde_resolve::resolve(
+///     tree,
+///     &|s: &str| {
+///         let s = s.to_uppercase();
+///         async move { Ok::<_, std::convert::Infallible>(Resolved::changed(s)) }
+///     },
+///     &Config::default(),
+/// ).await.unwrap();
+///
+/// assert_eq!(resolved, Node::List(vec![Node::Text("A".into()), Node::Number(1)]));
+/// # }
+/// ```
+pub trait ResolvableValue: Sized + Send {
+	/// Map key type.
+	type Key: Send;
+	/// Metadata carried by [`ValueKind::Tagged`]. Use
+	/// [`Infallible`](core::convert::Infallible) if the format has none.
+	type Tag: Send;
+
+	/// Classify this value for traversal.
+	fn into_kind(self) -> ValueKind<Self>;
+
+	/// Rebuild a string value.
+	fn from_string(s: String) -> Self;
+
+	/// Rebuild an array value.
+	fn from_array(items: Vec<Self>) -> Self;
+
+	/// Rebuild a map value.
+	fn from_map(entries: Vec<(Self::Key, Self)>) -> Self;
+
+	/// Rebuild a tagged value.
+	fn from_tagged(tag: Self::Tag, value: Self) -> Self;
+
+	/// Convert a map key into a value so it can be resolved when
+	/// [`Config::resolve_keys`] is set.
+	///
+	/// Return `Err(key)` for keys that should be left untouched.
+	///
+	/// # Errors
+	///
+	/// Returns the key back if it should not be resolved.
+	fn key_into_value(key: Self::Key) -> Result<Self, Self::Key>;
+
+	/// Convert a resolved key value back into a map key.
+	fn key_from_value(value: Self) -> Self::Key;
+
+	/// Render a map key for paths and diagnostics.
+	fn key_to_string(key: &Self::Key) -> String;
+
+	/// Short name of this value's type, used in trace output.
+	fn type_name(&self) -> &'static str {
+		"value"
+	}
+}
+
+/// Resolve all strings in any [`ResolvableValue`].
+///
+/// This is the traversal behind every format module's `resolve` function.
+/// Arrays, maps and tagged values are resolved recursively; map keys are
+/// passed through [`ResolvableValue::key_into_value`] and resolved as values
+/// when [`Config::resolve_keys`] is set.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve<V, R>(value: V, resolver: &R, config: &Config) -> Result<V, Error<R::Error>>
+where
+	V: ResolvableValue,
+	R: Resolver + ?Sized,
+{
+	resolve_with(
+		value,
+		resolver,
+		&PassThrough(PhantomData),
+		config,
+		&mut Trace::default(),
+	)
+	.await
+}
+
+/// Trait for async resolvers of non-string scalars.
+///
+/// Receives every [`ValueKind::Other`] value, such as numbers, booleans,
+/// nulls or TOML datetimes, with its path. Map keys are never passed to it.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "json")] {
+/// use serde_resolve::{json, Config, Path, Resolved};
+/// use serde_json::{Value, json};
+///
+/// # async fn example() -> Result<(), serde_resolve::Error<std::convert::Infallible>> {
+/// // Replace nulls with a default
+/// let defaults = |_: &Path, value: &Value| {
+///     let replaced = value.is_null().then(|| json!(0));
+///     async move { Ok::<_, std::convert::Infallible>(replaced) }
+/// };
+///
+/// let output = json::resolve_with_scalars(
+///     json!({ "retries": null, "name": "app" }),
+///     &|_: &str| async { Ok(Resolved::Unchanged) },
+///     &defaults,
+///     &Config::default(),
+/// ).await?;
+///
+/// assert_eq!(output, json!({ "retries": 0, "name": "app" }));
+/// # Ok(())
+/// # }
+/// # }
+/// ```
+pub trait ScalarResolver<V>: Send + Sync {
+	/// Error type returned by this resolver.
+	type Error: Send;
+
+	/// Resolve a scalar value.
+	///
+	/// # Returns
+	///
+	/// - `Ok(Some(value))` - Use the replacement value
+	/// - `Ok(None)` - Keep the original value
+	/// - `Err(e)` - Abort the entire resolve operation
+	fn resolve_scalar(
+		&self,
+		path: &Path,
+		value: &V,
+	) -> impl Future<Output = Result<Option<V>, Self::Error>> + Send;
+}
+
+impl<V, F, Fut, E> ScalarResolver<V> for F
+where
+	F: Fn(&Path, &V) -> Fut + Send + Sync,
+	Fut: Future<Output = Result<Option<V>, E>> + Send,
+	E: Send,
+{
+	type Error = E;
+
+	#[inline]
+	fn resolve_scalar(
+		&self,
+		path: &Path,
+		value: &V,
+	) -> impl Future<Output = Result<Option<V>, Self::Error>> + Send {
+		self(path, value)
+	}
+}
+
+/// Resolve all strings in any [`ResolvableValue`], and all other scalars
+/// with `scalars`.
+///
+/// Behaves like [`resolve`], additionally passing every
+/// [`ValueKind::Other`] value to `scalars`. Errors of `scalars` are
+/// converted into the error type of `resolver`.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Either resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_with_scalars<V, R, S>(
+	value: V,
+	resolver: &R,
+	scalars: &S,
+	config: &Config,
+) -> Result<V, Error<R::Error>>
+where
+	V: ResolvableValue + Sync,
+	R: Resolver + ?Sized,
+	S: ScalarResolver<V> + ?Sized,
+	S::Error: Into<R::Error>,
+{
+	resolve_with(
+		value,
+		resolver,
+		&ScalarHandler(scalars, PhantomData),
+		config,
+		&mut Trace::default(),
+	)
+	.await
+}
+
+/// Handler for [`ValueKind::Other`] values.
+pub(crate) trait OtherHandler<V>: Send + Sync {
+	/// Error type shared with the string resolver.
+	type Error: Send;
+
+	/// Whether every value is kept unchanged, so [`other`](Self::other) need
+	/// not run under the deadlines.
+	const PASSTHROUGH: bool = false;
+
+	/// Transform a non-string leaf value at `path`.
+	fn other(&self, value: V, path: &Path) -> impl Future<Output = Result<V, Self::Error>> + Send;
+}
+
+/// Handler that keeps every [`ValueKind::Other`] value unchanged.
+pub(crate) struct PassThrough<E>(pub(crate) PhantomData<fn() -> E>);
+
+impl<V: Send, E: Send> OtherHandler<V> for PassThrough<E> {
+	type Error = E;
+
+	const PASSTHROUGH: bool = true;
+
+	#[inline]
+	async fn other(&self, value: V, _path: &Path) -> Result<V, E> {
+		Ok(value)
+	}
+}
+
+/// Adapter passing [`ValueKind::Other`] leaves to a [`ScalarResolver`],
+/// converting its errors into `E`.
+struct ScalarHandler<'a, S: ?Sized, E>(&'a S, PhantomData<fn() -> E>);
+
+impl<V, S, E> OtherHandler<V> for ScalarHandler<'_, S, E>
+where
+	V: Send + Sync,
+	S: ScalarResolver<V> + ?Sized,
+	S::Error: Into<E>,
+	E: Send,
+{
+	type Error = E;
+
+	async fn other(&self, value: V, path: &Path) -> Result<V, E> {
+		let replaced = self
+			.0
+			.resolve_scalar(path, &value)
+			.await
+			.map_err(Into::into)?;
+		Ok(replaced.unwrap_or(value))
+	}
+}
+
+/// A string changed by the resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+	/// Location of the string.
+	pub path: Path,
+	/// The string before resolution.
+	pub original: String,
+	/// The resolved string, or `None` if the resolver returned a
+	/// [`Resolved::Secret`](crate::Resolved::Secret).
+	pub resolved: Option<String>,
+}
+
+impl Change {
+	/// Returns `true` if the resolved value is a secret.
+	#[inline]
+	#[must_use]
+	pub const fn is_secret(&self) -> bool {
+		self.resolved.is_none()
+	}
+}
+
+impl core::fmt::Display for Change {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match &self.resolved {
+			Some(resolved) => write!(f, "{}: {:?} -> {resolved:?}", self.path, self.original),
+			None => write!(f, "{}: {:?} -> {REDACTED}", self.path, self.original),
+		}
+	}
+}
+
+/// The strings changed by [`resolve_with_report`].
+///
+/// Secret values are never stored, so a report is safe to log or persist for
+/// auditing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+	changes: Vec<Change>,
+}
+
+impl Report {
+	/// Changed strings in traversal order.
+	#[inline]
+	#[must_use]
+	pub fn changes(&self) -> &[Change] {
+		&self.changes
+	}
+
+	/// Paths of strings resolved to secrets.
+	pub fn secret_paths(&self) -> impl Iterator<Item = &Path> {
+		self
+			.changes
+			.iter()
+			.filter(|change| change.is_secret())
+			.map(|change| &change.path)
+	}
+
+	/// Returns `true` if no string was changed.
+	#[inline]
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+
+	/// Unwrap the recorded changes.
+	#[inline]
+	#[must_use]
+	pub fn into_changes(self) -> Vec<Change> {
+		self.changes
+	}
+}
+
+impl core::fmt::Display for Report {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		for (i, change) in self.changes.iter().enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{change}")?;
+		}
+		Ok(())
+	}
+}
+
+/// Resolve all strings in any [`ResolvableValue`], recording what changed.
+///
+/// Behaves like [`resolve`] and additionally returns a [`Report`] listing the
+/// path and original form of every changed string.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_with_report<V, R>(
+	value: V,
+	resolver: &R,
+	config: &Config,
+) -> Result<(V, Report), Error<R::Error>>
+where
+	V: ResolvableValue,
+	R: Resolver + ?Sized,
+{
+	let mut trace = Trace::recording();
+	let resolved = resolve_with(
+		value,
+		resolver,
+		&PassThrough(PhantomData),
+		config,
+		&mut trace,
+	)
+	.await?;
+	let report = Report {
+		changes: trace.changes.unwrap_or_default(),
+	};
+	Ok((resolved, report))
+}
+
+/// Where each string in a resolved value came from.
+///
+/// Produced by [`resolve_with_provenance`], with one [`Origin`] per string
+/// value, ordered by path. The [`Display`](core::fmt::Display) output is an
+/// "explain config" listing; the [`Serialize`](serde::Serialize) output maps
+/// each path to its origin. Secret values appear in neither.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+	origins: BTreeMap<Path, Origin>,
+}
+
+impl Provenance {
+	/// The origin of the string at `path`.
+	#[inline]
+	#[must_use]
+	pub fn get(&self, path: &Path) -> Option<&Origin> {
+		self.origins.get(path)
+	}
+
+	/// Paths and origins, ordered by path.
+	pub fn iter(&self) -> impl Iterator<Item = (&Path, &Origin)> {
+		self.origins.iter()
+	}
+
+	/// Number of recorded strings.
+	#[inline]
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.origins.len()
+	}
+
+	/// Returns `true` if no string was recorded.
+	#[inline]
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.origins.is_empty()
+	}
+}
+
+impl core::fmt::Display for Provenance {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		for (i, (path, origin)) in self.origins.iter().enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{path} = {origin}")?;
+		}
+		Ok(())
+	}
+}
+
+impl serde::Serialize for Provenance {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeMap;
+
+		let mut map = serializer.serialize_map(Some(self.origins.len()))?;
+		for (path, origin) in &self.origins {
+			map.serialize_entry(&alloc::format!("{path}"), origin)?;
+		}
+		map.end()
+	}
+}
+
+/// The origin of a single string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+	/// Source reported by the resolver with
+	/// [`Resolved::with_source`](crate::Resolved::with_source), or `None` if
+	/// there was none.
+	pub source: Option<String>,
+	/// The string before resolution.
+	pub original: String,
+	/// The final string, or `None` if it is a secret.
+	pub value: Option<String>,
+}
+
+impl Origin {
+	/// Returns `true` if the final string is a secret.
+	#[inline]
+	#[must_use]
+	pub const fn is_secret(&self) -> bool {
+		self.value.is_none()
+	}
+
+	/// Returns `true` if the resolver changed the string.
+	#[must_use]
+	pub fn is_changed(&self) -> bool {
+		self.value.as_ref() != Some(&self.original)
+	}
+}
+
+/// Renders as `"value" <- source ("original")`, with `literal` standing in
+/// for a missing source and the original omitted when unchanged.
+impl core::fmt::Display for Origin {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		match &self.value {
+			Some(value) => write!(f, "{value:?}")?,
+			None => f.write_str(REDACTED)?,
+		}
+		write!(f, " <- {}", self.source.as_deref().unwrap_or("literal"))?;
+		if self.is_changed() {
+			write!(f, " ({:?})", self.original)?;
+		}
+		Ok(())
+	}
+}
+
+impl serde::Serialize for Origin {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeStruct;
+
+		let mut state = serializer.serialize_struct("Origin", 3)?;
+		state.serialize_field("source", &self.source)?;
+		state.serialize_field("original", &self.original)?;
+		state.serialize_field("value", &self.value)?;
+		state.end()
+	}
+}
+
+/// Resolve all strings in any [`ResolvableValue`], recording their origins.
+///
+/// Behaves like [`resolve`] and additionally returns the [`Provenance`] of
+/// every string value. Map keys are not recorded.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+pub async fn resolve_with_provenance<V, R>(
+	value: V,
+	resolver: &R,
+	config: &Config,
+) -> Result<(V, Provenance), Error<R::Error>>
+where
+	V: ResolvableValue,
+	R: Resolver + ?Sized,
+{
+	let mut trace = Trace {
+		origins: Some(BTreeMap::new()),
+		..Trace::default()
+	};
+	let resolved = resolve_with(
+		value,
+		resolver,
+		&PassThrough(PhantomData),
+		config,
+		&mut trace,
+	)
+	.await?;
+	let provenance = Provenance {
+		origins: trace.origins.unwrap_or_default(),
+	};
+	Ok((resolved, provenance))
+}
+
+/// Traversal state threaded through the recursion.
+#[derive(Debug, Default)]
+pub(crate) struct Trace {
+	/// Path of the value being resolved.
+	pub(crate) path: Path,
+	/// Changed strings, if recording is enabled.
+	pub(crate) changes: Option<Vec<Change>>,
+	/// Origins of all strings, if recording is enabled.
+	origins: Option<BTreeMap<Path, Origin>>,
+	/// Whether a map key is being resolved. Keys are never recorded.
+	in_key: bool,
+	/// Deadlines and cancellation of the operation.
+	guard: Guard,
+}
+
+impl Trace {
+	/// A trace that records every changed string.
+	pub(crate) fn recording() -> Self {
+		Self {
+			changes: Some(Vec::new()),
+			..Self::default()
+		}
+	}
+
+	/// Record the outcome of resolving the string at the current path.
+	fn record(&mut self, original: &str, resolved: &Resolved, source: Option<String>) {
+		if self.in_key {
+			return;
+		}
+		let secret = resolved.is_secret();
+		let new = match resolved {
+			Resolved::Changed(s) | Resolved::Secret(s) => Some(s),
+			Resolved::Unchanged => None,
+		};
+		if let (Some(changes), Some(new)) = (&mut self.changes, new) {
+			changes.push(Change {
+				path: self.path.clone(),
+				original: original.into(),
+				resolved: (!secret).then(|| new.clone()),
+			});
+		}
+		if let Some(origins) = &mut self.origins {
+			origins.insert(
+				self.path.clone(),
+				Origin {
+					source,
+					original: original.into(),
+					value: (!secret).then(|| new.map_or(original, String::as_str).into()),
+				},
+			);
+		}
+	}
+}
+
+/// Resolve strings with `resolver` and other leaves with `handler`.
+pub(crate) async fn resolve_with<V, R, H>(
+	value: V,
+	resolver: &R,
+	handler: &H,
+	config: &Config,
+	trace: &mut Trace,
+) -> Result<V, Error<R::Error>>
+where
+	V: ResolvableValue,
+	R: Resolver + ?Sized,
+	H: OtherHandler<V, Error = R::Error>,
+{
+	trace.guard = Guard::new(config)?;
+	resolve_recursive(value, resolver, handler, config, 0, trace).await
+}
+
+fn resolve_recursive<'a, V, R, H>(
+	value: V,
+	resolver: &'a R,
+	handler: &'a H,
+	config: &'a Config,
+	depth: usize,
+	trace: &'a mut Trace,
+) -> Pin<Box<dyn Future<Output = Result<V, Error<R::Error>>> + Send + 'a>>
+where
+	V: ResolvableValue + 'a,
+	R: Resolver + ?Sized,
+	H: OtherHandler<V, Error = R::Error>,
+{
+	Box::pin(async move {
+		if depth >= config.max_depth {
+			return Err(Error::depth_exceeded(config.max_depth));
+		}
+
+		#[cfg(feature = "tracing")]
+		tracing::trace!(depth, path = %trace.path, value_type = ?value.type_name(), "resolving");
+
+		match value.into_kind() {
+			ValueKind::String(s) => {
+				// Sources are only asked for when provenance is recorded
+				let Sourced { resolved, source } = if trace.origins.is_some() {
+					let sourced = crate::fixpoint::resolve_str_sourced(resolver, &s, config);
+					trace.guard.run(&trace.path, sourced).await?
+				} else {
+					let resolved = crate::fixpoint::resolve_str(resolver, &s, config);
+					trace.guard.run(&trace.path, resolved).await?.into()
+				};
+				#[cfg(feature = "tracing")]
+				crate::trace_resolved(&s, &resolved);
+
+				trace.record(&s, &resolved, source);
+				Ok(V::from_string(resolved.into_changed().unwrap_or(s)))
+			}
+
+			ValueKind::Array(arr) => {
+				let mut result = Vec::with_capacity(arr.len());
+				for item in arr {
+					trace.path.push(PathSegment::Index(result.len()));
+					let res = resolve_recursive(item, resolver, handler, config, depth + 1, trace).await?;
+					result.push(res);
+					trace.path.pop();
+				}
+				Ok(V::from_array(result))
+			}
+
+			ValueKind::Map(map) => {
+				let mut result = Vec::with_capacity(map.len());
+				for (key, val) in map {
+					// Optionally resolve keys
+					let resolved_key = match V::key_into_value(key) {
+						Ok(key_value) if config.resolve_keys => {
+							let in_key = core::mem::replace(&mut trace.in_key, true);
+							let resolved =
+								resolve_recursive(key_value, resolver, handler, config, depth + 1, trace).await;
+							trace.in_key = in_key;
+							V::key_from_value(resolved?)
+						}
+						Ok(key_value) => V::key_from_value(key_value),
+						Err(key) => key,
+					};
+
+					trace
+						.path
+						.push(PathSegment::Key(V::key_to_string(&resolved_key)));
+					let resolved_val =
+						resolve_recursive(val, resolver, handler, config, depth + 1, trace).await?;
+					result.push((resolved_key, resolved_val));
+					trace.path.pop();
+				}
+				Ok(V::from_map(result))
+			}
+
+			// Tagged values - resolve inner
+			ValueKind::Tagged(tag, inner) => {
+				let resolved_inner =
+					resolve_recursive(inner, resolver, handler, config, depth + 1, trace).await?;
+				Ok(V::from_tagged(tag, resolved_inner))
+			}
+
+			// Keys are only ever resolved as strings
+			ValueKind::Other(other) if trace.in_key || H::PASSTHROUGH => Ok(other),
+			ValueKind::Other(other) => {
+				let path = &trace.path;
+				trace
+					.guard
+					.run(path, async {
+						handler.other(other, path).await.map_err(Error::resolver)
+					})
+					.await
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Resolved;
+	use crate::testing::upper;
+	use alloc::string::ToString;
+	use alloc::vec;
+	use core::convert::Infallible;
+
+	#[derive(Debug, Clone, PartialEq)]
+	enum Node {
+		Text(String),
+		Int(i64),
+		List(Vec<Self>),
+		Map(Vec<(Key, Self)>),
+		Boxed(&'static str, alloc::boxed::Box<Self>),
+	}
+
+	#[derive(Debug, Clone, PartialEq)]
+	enum Key {
+		Name(String),
+		Id(u32),
+	}
+
+	impl ResolvableValue for Node {
+		type Key = Key;
+		type Tag = &'static str;
+
+		fn into_kind(self) -> ValueKind<Self> {
+			match self {
+				Self::Text(s) => ValueKind::String(s),
+				Self::List(items) => ValueKind::Array(items),
+				Self::Map(entries) => ValueKind::Map(entries),
+				Self::Boxed(tag, inner) => ValueKind::Tagged(tag, *inner),
+				other @ Self::Int(_) => ValueKind::Other(other),
+			}
+		}
+
+		fn from_string(s: String) -> Self {
+			Self::Text(s)
+		}
+
+		fn from_array(items: Vec<Self>) -> Self {
+			Self::List(items)
+		}
+
+		fn from_map(entries: Vec<(Key, Self)>) -> Self {
+			Self::Map(entries)
+		}
+
+		fn from_tagged(tag: &'static str, value: Self) -> Self {
+			Self::Boxed(tag, alloc::boxed::Box::new(value))
+		}
+
+		fn key_into_value(key: Key) -> Result<Self, Key> {
+			match key {
+				Key::Name(name) => Ok(Self::Text(name)),
+				id @ Key::Id(_) => Err(id),
+			}
+		}
+
+		fn key_from_value(value: Self) -> Key {
+			match value {
+				Self::Text(name) => Key::Name(name),
+				_ => Key::Id(0),
+			}
+		}
+
+		fn key_to_string(key: &Key) -> String {
+			alloc::format!("{key:?}")
+		}
+	}
+
+	fn tree() -> Node {
+		Node::Map(vec![
+			(Key::Name("name".into()), Node::Text("app".into())),
+			(
+				Key::Id(7),
+				Node::List(vec![Node::Int(1), Node::Text("a".into())]),
+			),
+			(
+				Key::Name("opt".into()),
+				Node::Boxed("some", alloc::boxed::Box::new(Node::Text("b".into()))),
+			),
+		])
+	}
+
+	#[tokio::test]
+	async fn test_resolve_custom_tree() {
+		let output = resolve(tree(), &upper, &Config::default()).await.unwrap();
+
+		let expected = Node::Map(vec![
+			(Key::Name("name".into()), Node::Text("APP".into())),
+			(
+				Key::Id(7),
+				Node::List(vec![Node::Int(1), Node::Text("A".into())]),
+			),
+			(
+				Key::Name("opt".into()),
+				Node::Boxed("some", alloc::boxed::Box::new(Node::Text("B".into()))),
+			),
+		]);
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_custom_keys() {
+		let output = resolve(tree(), &upper, &Config::default().resolve_keys(true))
+			.await
+			.unwrap();
+
+		let Node::Map(entries) = output else {
+			panic!("expected map");
+		};
+		let keys: Vec<_> = entries.into_iter().map(|(k, _)| k).collect();
+		assert_eq!(
+			keys,
+			vec![
+				Key::Name("NAME".into()),
+				Key::Id(7),
+				Key::Name("OPT".into())
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_depth_limit() {
+		// Map (0) -> Boxed (1) -> Text (2)
+		let result = resolve(tree(), &upper, &Config::default().max_depth(2)).await;
+		assert!(matches!(result, Err(Error::DepthExceeded { limit: 2 })));
+
+		let result = resolve(tree(), &upper, &Config::default().max_depth(3)).await;
+		assert!(result.is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_resolve_with_report() {
+		let input = Node::Map(vec![
+			(Key::Name("user".into()), Node::Text("${USER}".into())),
+			(
+				Key::Name("db".into()),
+				Node::List(vec![
+					Node::Text("${PASS}".into()),
+					Node::Text("plain".into()),
+				]),
+			),
+		]);
+
+		let (output, report) = resolve_with_report(
+			input,
+			&|s: &str| {
+				let resolved = match s {
+					"${USER}" => Resolved::changed("admin"),
+					"${PASS}" => Resolved::secret("hunter2"),
+					_ => Resolved::unchanged(),
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output,
+			Node::Map(vec![
+				(Key::Name("user".into()), Node::Text("admin".into())),
+				(
+					Key::Name("db".into()),
+					Node::List(vec![
+						Node::Text("hunter2".into()),
+						Node::Text("plain".into())
+					]),
+				),
+			])
+		);
+		assert_eq!(report.changes().len(), 2);
+		assert_eq!(
+			report
+				.secret_paths()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			["Name(\"db\")[0]"]
+		);
+		let shown = report.to_string();
+		assert_eq!(
+			shown,
+			"Name(\"user\"): \"${USER}\" -> \"admin\"\nName(\"db\")[0]: \"${PASS}\" -> [REDACTED]"
+		);
+		assert!(!alloc::format!("{report:?}").contains("hunter2"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_with_provenance() {
+		let input = Node::Map(vec![
+			(Key::Name("${KEY}".into()), Node::Text("${HOST}".into())),
+			(Key::Name("pass".into()), Node::Text("${PASS}".into())),
+			(Key::Name("name".into()), Node::Text("app".into())),
+			(Key::Id(7), Node::Int(1)),
+		]);
+
+		let (_, provenance) = resolve_with_provenance(
+			input,
+			&|s: &str| {
+				let resolved = match s {
+					"${KEY}" => Resolved::changed("host").with_source("env:KEY"),
+					"${HOST}" => Resolved::changed("db.local").with_source("env:DB_HOST"),
+					"${PASS}" => Resolved::secret("hunter2").with_source("file:/run/secrets/db:3"),
+					_ => Resolved::unchanged().into(),
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+
+		// Keys are resolved but not recorded
+		assert_eq!(provenance.len(), 3);
+		let host: Path = [PathSegment::Key("Name(\"host\")".into())]
+			.into_iter()
+			.collect();
+		assert_eq!(
+			provenance.get(&host),
+			Some(&Origin {
+				source: Some("env:DB_HOST".into()),
+				original: "${HOST}".into(),
+				value: Some("db.local".into()),
+			})
+		);
+		assert_eq!(
+			provenance.to_string(),
+			"Name(\"host\") = \"db.local\" <- env:DB_HOST (\"${HOST}\")\n\
+			 Name(\"name\") = \"app\" <- literal\n\
+			 Name(\"pass\") = [REDACTED] <- file:/run/secrets/db:3 (\"${PASS}\")"
+		);
+	}
+
+	#[cfg(feature = "std")]
+	#[tokio::test]
+	async fn test_resolve_with_scalars() {
+		let input = Node::Map(vec![
+			(Key::Name("port".into()), Node::Int(80)),
+			(
+				Key::Id(7),
+				Node::List(vec![Node::Int(1), Node::Text("a".into())]),
+			),
+		]);
+		let seen = std::sync::Mutex::new(Vec::new());
+		let double = |path: &Path, value: &Node| {
+			seen.lock().unwrap().push(path.to_string());
+			let doubled = match value {
+				Node::Int(n) => Some(Node::Int(n * 2)),
+				_ => None,
+			};
+			async move { Ok::<_, Infallible>(doubled) }
+		};
+
+		let output = resolve_with_scalars(input, &upper, &double, &Config::default())
+			.await
+			.unwrap();
+		assert_eq!(
+			output,
+			Node::Map(vec![
+				(Key::Name("port".into()), Node::Int(160)),
+				(
+					Key::Id(7),
+					Node::List(vec![Node::Int(2), Node::Text("A".into())])
+				),
+			])
+		);
+		assert_eq!(*seen.lock().unwrap(), ["Name(\"port\")", "Id(7)[0]"]);
+	}
+
+	#[cfg(feature = "std")]
+	#[tokio::test]
+	async fn test_scalar_error_converted() {
+		let input = Node::List(vec![Node::Int(1), Node::Text("a".into())]);
+		let reject = |_: &Path, _: &Node| async { Err::<Option<Node>, _>("no scalars") };
+		let resolver = |_: &str| async { Ok::<_, String>(Resolved::Unchanged) };
+
+		let result = resolve_with_scalars(input, &resolver, &reject, &Config::default()).await;
+		assert!(matches!(result, Err(Error::Resolver(e)) if e == "no scalars"));
+	}
+
+	#[cfg(feature = "std")]
+	#[tokio::test]
+	async fn test_pass_through_skips_timer() {
+		let timer = crate::testing::RecordingTimer::default();
+		let config = Config::default()
+			.string_timeout(core::time::Duration::from_secs(1))
+			.timer(timer.clone());
+		let input = Node::List(vec![Node::Int(1), Node::Int(2), Node::Text("a".into())]);
+
+		resolve(input, &upper, &config).await.unwrap();
+		assert_eq!(timer.sleeps().len(), 1);
+	}
+}
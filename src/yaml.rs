@@ -5,11 +5,15 @@
 //! This module requires the `std` feature.
 
 use alloc::boxed::Box;
-#[cfg(feature = "tracing")]
+use alloc::string::String;
 use alloc::vec::Vec;
-use serde_yaml::Value;
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::{Mapping, Value};
 
-use crate::{Config, Error, Resolver};
+use crate::{
+	BatchResolver, Config, Error, ResolvableValue, Resolver, ScalarResolver, StructResolveError,
+	ValueKind,
+};
 
 /// Resolve all strings in a YAML [`Value`].
 ///
@@ -20,74 +24,161 @@ pub async fn resolve<R>(
 	config: &Config,
 ) -> Result<Value, Error<R::Error>>
 where
-	R: Resolver,
+	R: Resolver + ?Sized,
 {
-	#[cfg(feature = "tracing")]
-	let mut path = Vec::new();
-
-	resolve_recursive(
-		value,
-		resolver,
-		config,
-		0,
-		#[cfg(feature = "tracing")]
-		&mut path,
-	)
-	.await
+	crate::resolve(value, resolver, config).await
 }
 
-impl_resolve_recursive!(
-		Value,
-		Value::String,
-		Value::Sequence,
-		Value::Mapping,
-		serde_yaml::Mapping::with_capacity,
-		|k: &Value| format!("{k:?}"),
-		resolver, config, depth, path, key,
-		{
-				resolve_recursive(
-						key,
-						resolver,
-						config,
-						depth + 1,
-						#[cfg(feature = "tracing")]
-						path,
-				)
-				.await?
-		},
-		{
-				// Tagged values - resolve inner
-				Value::Tagged(tagged) => {
-						let resolved_inner = resolve_recursive(
-								tagged.value,
-								resolver,
-								config,
-								depth + 1,
-								#[cfg(feature = "tracing")]
-								path,
-						)
-						.await?;
-						Ok(Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
-								tag: tagged.tag,
-								value: resolved_inner,
-						})))
-				}
-
-				// Pass through unchanged
-				other @ (Value::Null | Value::Bool(_) | Value::Number(_)) => Ok(other),
+/// Resolve all strings in a YAML [`Value`] with a [`BatchResolver`].
+///
+/// See [`crate::resolve_batched`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_batched`].
+pub async fn resolve_batched<B>(
+	value: Value,
+	resolver: &B,
+	config: &Config,
+) -> Result<Value, Error<B::Error>>
+where
+	B: BatchResolver + ?Sized,
+{
+	crate::resolve_batched(value, resolver, config).await
+}
+
+/// Resolve all strings in a YAML [`Value`], and all other scalars with
+/// `scalars`.
+///
+/// Numbers, booleans and nulls, including tagged ones, are passed to
+/// `scalars` with their path; tags are kept.
+/// See [`crate::resolve_with_scalars`] for details.
+///
+/// # Errors
+///
+/// Same as [`crate::resolve_with_scalars`].
+pub async fn resolve_with_scalars<R, S>(
+	value: Value,
+	resolver: &R,
+	scalars: &S,
+	config: &Config,
+) -> Result<Value, Error<R::Error>>
+where
+	R: Resolver + ?Sized,
+	S: ScalarResolver<Value> + ?Sized,
+	S::Error: Into<R::Error>,
+{
+	crate::resolve_with_scalars(value, resolver, scalars, config).await
+}
+
+/// Resolve strings in any serializable struct via a YAML round-trip.
+///
+/// Unlike [`crate::resolve_struct`], map keys do not need to be strings and
+/// YAML tags are preserved. See [`crate::resolve_struct`] for details.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Serialization fails
+/// - The resolver returns an error
+/// - The depth limit is exceeded
+/// - Deserialization fails
+pub async fn resolve_struct<T, R>(
+	value: T,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error, serde_yaml::Error>>
+where
+	T: serde::Serialize + serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	resolve_into(value, resolver, config).await
+}
+
+/// Resolve strings in a serializable value and deserialize the result into
+/// another type via a YAML round-trip.
+///
+/// See [`crate::resolve_into`] for details.
+///
+/// # Errors
+///
+/// Same as [`resolve_struct`].
+pub async fn resolve_into<S, T, R>(
+	value: S,
+	resolver: &R,
+	config: &Config,
+) -> Result<T, StructResolveError<R::Error, serde_yaml::Error>>
+where
+	S: serde::Serialize,
+	T: serde::de::DeserializeOwned,
+	R: Resolver + ?Sized,
+{
+	let yaml = serde_yaml::to_value(value).map_err(StructResolveError::Serialize)?;
+	crate::resolve_struct_via(yaml, resolver, config).await
+}
+
+impl ResolvableValue for Value {
+	type Key = Self;
+	type Tag = Tag;
+
+	fn into_kind(self) -> ValueKind<Self> {
+		match self {
+			Self::String(s) => ValueKind::String(s),
+			Self::Sequence(seq) => ValueKind::Array(seq),
+			Self::Mapping(map) => ValueKind::Map(map.into_iter().collect()),
+			Self::Tagged(tagged) => {
+				let TaggedValue { tag, value } = *tagged;
+				ValueKind::Tagged(tag, value)
+			}
+			other @ (Self::Null | Self::Bool(_) | Self::Number(_)) => ValueKind::Other(other),
+		}
+	}
+
+	#[inline]
+	fn from_string(s: String) -> Self {
+		Self::String(s)
+	}
+
+	#[inline]
+	fn from_array(items: Vec<Self>) -> Self {
+		Self::Sequence(items)
+	}
+
+	fn from_map(entries: Vec<(Self, Self)>) -> Self {
+		Self::Mapping(entries.into_iter().collect::<Mapping>())
+	}
+
+	fn from_tagged(tag: Tag, value: Self) -> Self {
+		Self::Tagged(Box::new(TaggedValue { tag, value }))
+	}
+
+	#[inline]
+	fn key_into_value(key: Self) -> Result<Self, Self> {
+		Ok(key)
+	}
+
+	#[inline]
+	fn key_from_value(value: Self) -> Self {
+		value
+	}
+
+	fn key_to_string(key: &Self) -> String {
+		match key {
+			Self::String(k) => k.clone(),
+			other => format!("{other:?}"),
+		}
+	}
+
+	fn type_name(&self) -> &'static str {
+		match self {
+			Self::Null => "null",
+			Self::Bool(_) => "bool",
+			Self::Number(_) => "number",
+			Self::String(_) => "string",
+			Self::Sequence(_) => "sequence",
+			Self::Mapping(_) => "mapping",
+			Self::Tagged(_) => "tagged",
 		}
-);
-
-#[cfg(feature = "tracing")]
-fn value_type_name(value: &Value) -> &'static str {
-	match value {
-		Value::Null => "null",
-		Value::Bool(_) => "bool",
-		Value::Number(_) => "number",
-		Value::String(_) => "string",
-		Value::Sequence(_) => "sequence",
-		Value::Mapping(_) => "mapping",
-		Value::Tagged(_) => "tagged",
 	}
 }
 
@@ -95,9 +186,12 @@ fn value_type_name(value: &Value) -> &'static str {
 mod tests {
 	use super::*;
 	use crate::Resolved;
+	use crate::testing::Port;
+	use alloc::borrow::ToOwned;
 	use alloc::string::ToString;
 	use core::convert::Infallible;
 	use serde_yaml::Mapping;
+	use std::collections::BTreeMap;
 
 	#[tokio::test]
 	async fn test_resolve_string() {
@@ -291,4 +385,204 @@ mod tests {
 		.unwrap();
 		assert_eq!(output, input);
 	}
-}
\ No newline at end of file
+
+	#[tokio::test]
+	async fn test_resolve_struct_non_string_keys() {
+		#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+		struct Ports {
+			by_number: BTreeMap<u16, String>,
+		}
+
+		let input = Ports {
+			by_number: BTreeMap::from([(80, "http".into()), (443, "https".into())]),
+		};
+		let output = resolve_struct(
+			input,
+			&|s: &str| {
+				let s = s.to_owned();
+				async move { Ok::<_, Infallible>(Resolved::changed(s.to_uppercase())) }
+			},
+			&Config::default(),
+		)
+		.await
+		.unwrap();
+
+		assert_eq!(
+			output.by_number,
+			BTreeMap::from([(80, "HTTP".into()), (443, "HTTPS".into())])
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_struct_deserialize_error() {
+		#[derive(Debug, serde::Serialize, serde::Deserialize)]
+		enum Level {
+			Low,
+		}
+
+		let result = resolve_struct(
+			Level::Low,
+			&|_: &str| async move { Ok::<_, Infallible>(Resolved::changed("Bogus")) },
+			&Config::default(),
+		)
+		.await;
+		assert!(matches!(
+			result,
+			Err(StructResolveError::Deserialize { .. })
+		));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_into_coercion() {
+		#[derive(Debug, serde::Deserialize, PartialEq)]
+		struct Server {
+			port: u16,
+			ratio: f32,
+		}
+
+		let raw: Value = serde_yaml::from_str("port: ${PORT}\nratio: '0.25'\n").unwrap();
+		let server: Server = resolve_into(
+			raw,
+			&|s: &str| {
+				let resolved = if s == "${PORT}" {
+					Resolved::changed("443")
+				} else {
+					Resolved::unchanged()
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default().coercion(crate::Coercion::Strict),
+		)
+		.await
+		.unwrap();
+		assert_eq!(
+			server,
+			Server {
+				port: 443,
+				ratio: 0.25
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_with_scalars() {
+		let input: Value = serde_yaml::from_str("a: !Flag true\n1: null\n").unwrap();
+		let invert = |_: &crate::Path, value: &Value| {
+			let replaced = match value {
+				Value::Bool(b) => Some(Value::Bool(!b)),
+				Value::Null => Some(Value::String("default".into())),
+				_ => None,
+			};
+			async move { Ok::<_, Infallible>(replaced) }
+		};
+
+		let output = resolve_with_scalars(
+			input,
+			&|_: &str| async { Ok::<_, Infallible>(Resolved::Unchanged) },
+			&invert,
+			&Config::default().resolve_keys(true),
+		)
+		.await
+		.unwrap();
+		// The tag is kept and the numeric key is left alone
+		let expected: Value = serde_yaml::from_str("a: !Flag false\n1: default\n").unwrap();
+		assert_eq!(output, expected);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_struct_error_path() {
+		#[derive(Debug, serde::Serialize, serde::Deserialize)]
+		struct Servers {
+			servers: BTreeMap<String, Port>,
+		}
+
+		let input = Servers {
+			servers: BTreeMap::from([
+				("api".to_owned(), Port("80".to_owned())),
+				("web".to_owned(), Port("{{PORT}}".to_owned())),
+			]),
+		};
+		let result = resolve_struct(
+			input,
+			&|s: &str| {
+				let resolved = if s == "{{PORT}}" {
+					Resolved::changed("abc")
+				} else {
+					Resolved::unchanged()
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default(),
+		)
+		.await;
+
+		let Err(err @ StructResolveError::Deserialize { .. }) = result else {
+			panic!("expected deserialize error");
+		};
+		let StructResolveError::Deserialize {
+			path,
+			original,
+			resolved,
+			..
+		} = &err
+		else {
+			unreachable!();
+		};
+		assert_eq!(path.to_string(), "servers.web");
+		assert_eq!(original.as_deref(), Some("{{PORT}}"));
+		assert_eq!(resolved.as_deref(), Some("abc"));
+		assert!(
+			err
+				.to_string()
+				.starts_with("deserialization error at `servers.web` (resolved \"{{PORT}}\" to \"abc\"): ")
+		);
+	}
+
+	#[tokio::test]
+	async fn test_resolve_struct_error_path_stops_at_unnamed_key() {
+		#[derive(Debug, serde::Serialize, serde::Deserialize)]
+		struct Server {
+			port: Port,
+		}
+
+		#[derive(Debug, serde::Serialize, serde::Deserialize)]
+		struct Servers {
+			servers: BTreeMap<Option<String>, Server>,
+		}
+
+		let input = Servers {
+			servers: BTreeMap::from([(
+				None,
+				Server {
+					port: Port("{{PORT}}".to_owned()),
+				},
+			)]),
+		};
+		let result = resolve_struct(
+			input,
+			&|s: &str| {
+				let resolved = if s == "{{PORT}}" {
+					Resolved::changed("abc")
+				} else {
+					Resolved::unchanged()
+				};
+				async move { Ok::<_, Infallible>(resolved) }
+			},
+			&Config::default(),
+		)
+		.await;
+
+		let Err(StructResolveError::Deserialize {
+			path,
+			original,
+			resolved,
+			..
+		}) = result
+		else {
+			panic!("expected deserialize error");
+		};
+		assert_eq!(path.to_string(), "servers");
+		assert_eq!(original, None);
+		assert_eq!(resolved, None);
+	}
+}